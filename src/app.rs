@@ -1,12 +1,35 @@
-use crate::parsing::FormattedSpan;
+use crate::bookmarks::{Bookmark, BookmarkList, Bookmarks};
+use crate::config::{Config, Setting};
+use crate::history::SearchHistory;
+use crate::images::{self, GraphicsProtocol, ImagePlacement, ImageStore};
+use crate::keymap::{Action, KeyContext, KeyPress, KeyResult, Keymap, PendingKeys};
+use crate::parsing::{wrap_spans, FormattedSpan, LinkKind, WrappedLine};
+use crate::positions::ReadingPositions;
+use crate::session::{HistoryEntry, SavedSplit, SavedTab, Session};
 use crate::styles::Theme;
-use crate::utils::{create_shared, remainder, shared_copy};
-use crate::wikipedia::{self, SearchResult, WikiSearchResponse};
+use crate::text_input::TextInput;
+use crate::utils::{create_shared, format_timestamp, fuzzy_match, remainder, shared_copy};
+use crate::widgets::HalfBlockCache;
+use crate::wikipedia::{
+    self, FeaturedArticle, LoadInfo, SearchOperator, SearchOptions, SearchOutput, SearchPaging,
+    SearchResult, SearchSort, SectionFilter, SummaryState, NAMESPACES,
+};
 use crate::{caching::CachingSession, utils::Shared};
 
+use crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use std::cell::{Cell, RefCell};
 use std::char;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tui::layout::Rect;
+use unicode_width::UnicodeWidthStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AppState {
     Title,
     Search,
@@ -14,9 +37,32 @@ pub enum AppState {
     Article,
     ArticleMenu,
     Credit,
+    ThemePicker,
+    Settings,
+    CommandPalette,
+    Bookmarks,
 }
+
+impl AppState {
+    /// What the status bar calls the screen
+    pub fn label(&self) -> &'static str {
+        match self {
+            AppState::Title => "TITLE",
+            AppState::Search => "SEARCH",
+            AppState::SearchMenu | AppState::ArticleMenu => "MENU",
+            AppState::Article => "ARTICLE",
+            AppState::Credit => "CREDITS",
+            AppState::ThemePicker => "THEMES",
+            AppState::Settings => "SETTINGS",
+            AppState::CommandPalette => "COMMAND",
+            AppState::Bookmarks => "BOOKMARKS",
+        }
+    }
+}
+
 pub type AppAction = Arc<dyn Fn(&mut App) + Send + Sync>;
 
+#[derive(Clone)]
 pub struct ActionItem {
     label: String,
     action: AppAction,
@@ -77,99 +123,101 @@ pub trait ActionMenu {
     }
 }
 
+/// A screen with a text box that typing goes into
 pub trait TypeableState {
-    fn get_input(&self) -> String;
-    fn insert_to_input_at_cursor(&mut self, c: char) -> ();
-    fn remove_from_input_at_cursor(&mut self) -> ();
-    fn get_cursor_pos(&self) -> usize;
-    fn set_cursor_pos(&mut self, new_cursor_pos: usize) -> ();
-    fn trigger_text_focus(&mut self) -> () {}
-
-    fn move_cursor_to_start(&mut self) -> () {
-        self.set_cursor_pos(0);
+    fn text_input(&mut self) -> &mut TextInput;
+    /// Called after every edit or cursor move
+    fn trigger_text_focus(&mut self) {}
+
+    fn move_cursor_to_start(&mut self) {
+        self.text_input().move_to_start();
         self.trigger_text_focus();
     }
 
-    fn move_cursor_to_end(&mut self) -> () {
-        self.set_cursor_pos(self.get_input().len());
+    fn move_cursor_to_end(&mut self) {
+        self.text_input().move_to_end();
         self.trigger_text_focus();
     }
 
     fn move_cursor_one_step(&mut self, cursor_direction: CursorDirection) {
-        // Limit cursor_pos to be between 0 and (length of the input) - 1
         match cursor_direction {
-            CursorDirection::LEFT => {
-                self.set_cursor_pos(self.get_cursor_pos().saturating_sub(1));
-            }
-            CursorDirection::RIGHT => {
-                if self.get_cursor_pos() < self.get_input().len() {
-                    self.set_cursor_pos(self.get_cursor_pos() + 1);
-                }
-            }
+            CursorDirection::LEFT => self.text_input().move_left(),
+            CursorDirection::RIGHT => self.text_input().move_right(),
         }
-        self.set_cursor_pos(self.get_cursor_pos().clamp(0, self.get_input().len() + 1));
         self.trigger_text_focus();
     }
 
     fn type_char(&mut self, c: char) {
-        if !(self.get_cursor_pos() > self.get_input().len()) {
-            self.insert_to_input_at_cursor(c);
-            self.move_cursor_one_step(CursorDirection::RIGHT);
-        }
+        self.text_input().insert(c);
         self.trigger_text_focus();
     }
 
     fn backspace(&mut self) {
-        if !(self.get_input().is_empty()) {
-            if self.get_cursor_pos() > 0 {
-                self.remove_from_input_at_cursor();
-                self.move_cursor_one_step(CursorDirection::LEFT);
-            }
-        }
+        self.text_input().delete_backward();
         self.trigger_text_focus();
     }
 }
 
-pub struct TitleState {
-    pub input: String,
-    pub cursor_pos: usize,
-}
-
-impl TypeableState for TitleState {
-    fn get_input(&self) -> String {
-        self.input.clone()
+impl TypeableState for TextInput {
+    fn text_input(&mut self) -> &mut TextInput {
+        self
     }
+}
 
-    fn insert_to_input_at_cursor(&mut self, c: char) -> () {
-        self.input.insert(self.cursor_pos, c);
-    }
+pub struct TitleState {
+    pub input: TextInput,
+    /// The landing item picked with Up/Down, or `None` while typing in the box
+    pub selected_item: Option<usize>,
+    pub featured: Shared<Option<FeaturedArticle>>,
+    /// The wiki `featured` was loaded from
+    pub featured_language: String,
+}
 
-    fn remove_from_input_at_cursor(&mut self) -> () {
-        if self.cursor_pos <= self.input.len() && self.cursor_pos > 0 {
-            self.input.remove(self.cursor_pos - 1);
-        }
-    }
+/// Something to pick from under the title screen's search box
+#[derive(Clone, Debug, PartialEq)]
+pub enum LandingItem {
+    RecentSearch(String),
+    /// Something saved to read later or bookmarked
+    Bookmark(Bookmark),
+    Article(String),
+}
 
-    fn get_cursor_pos(&self) -> usize {
-        self.cursor_pos
+impl TypeableState for TitleState {
+    fn text_input(&mut self) -> &mut TextInput {
+        &mut self.input
     }
 
-    fn set_cursor_pos(&mut self, new_cursor_pos: usize) -> () {
-        self.cursor_pos = new_cursor_pos;
+    fn trigger_text_focus(&mut self) {
+        self.selected_item = None;
     }
 }
 
 pub struct SearchState {
-    pub input: String,
+    pub input: TextInput,
     pub current_query: String,
     pub results: Shared<Vec<SearchResult>>,
-    pub cursor_pos: usize,
     pub is_loading_query: Shared<bool>,
+    /// How the current results were loaded
+    pub load_info: Shared<Option<LoadInfo>>,
+    pub paging: Shared<SearchPaging>,
+    pub options: SearchOptions,
+    pub advanced: AdvancedSearchState,
+    pub preview: PreviewState,
+    pub autocomplete: AutocompleteState,
     pub selected_index: usize,
+    /// Whether "Did you mean" is selected instead of a result
+    pub suggestion_selected: bool,
     pub text_box_is_highlighted: bool,
+    /// The past query put in the search box with Up, as an index into the history
+    pub history_recall: Option<usize>,
 }
 
 impl SearchState {
+    /// Whether the suggestions dropdown is open under the search box
+    pub fn showing_suggestions(&self) -> bool {
+        self.text_box_is_highlighted && !self.autocomplete.suggestions.lock().unwrap().is_empty()
+    }
+
     pub fn currently_loading(&self) -> bool {
         match self.is_loading_query.try_lock() {
             Ok(is_loading) => (*is_loading),
@@ -177,11 +225,42 @@ impl SearchState {
         }
     }
 
+    /// The query the wiki suggests instead of the current one
+    pub fn did_you_mean(&self) -> Option<String> {
+        self.paging.lock().unwrap().suggestion.clone()
+    }
+
+    /// Whether there are results past the ones loaded so far
+    pub fn has_more_results(&self) -> bool {
+        self.paging.lock().unwrap().next_offset.is_some()
+    }
+
+    /// The query and offset of the page to load next, once the last loaded result is selected
+    /// and nothing else is loading
+    pub fn next_page(&self) -> Option<(String, u32)> {
+        let paging = self.paging.lock().unwrap().clone();
+        let total_results = self.results.lock().unwrap().len();
+        let offset = paging.next_offset?;
+        let is_at_end = self.selected_index + 1 >= total_results;
+        match is_at_end && !paging.is_loading_more && !self.currently_loading() {
+            true => Some((paging.query, offset)),
+            false => None,
+        }
+    }
+
     pub fn scroll_results(&mut self, scroll_direction: ScrollDirection) {
         if !self.currently_loading() {
+            let has_more_results = self.has_more_results();
             let results = self.results.lock().unwrap();
             if results.len() > 0 {
                 match scroll_direction {
+                    // The end isn't known yet, so there's nothing to wrap around to
+                    ScrollDirection::DOWN if has_more_results => {
+                        self.selected_index = (self.selected_index + 1).min(results.len() - 1);
+                    }
+                    ScrollDirection::UP if has_more_results => {
+                        self.selected_index = self.selected_index.saturating_sub(1);
+                    }
                     ScrollDirection::DOWN => {
                         self.selected_index = remainder(self.selected_index + 1, results.len());
                     }
@@ -204,31 +283,108 @@ impl SearchState {
     }
 }
 
-impl TypeableState for SearchState {
-    fn get_input(&self) -> String {
-        self.input.clone()
+/// Title suggestions for what's being typed into the search box
+pub struct AutocompleteState {
+    pub suggestions: Shared<Vec<String>>,
+    /// The highlighted suggestion, or `None` while typing
+    pub selected_index: Option<usize>,
+    /// The input as of the last update, and when it last changed
+    pub last_input: String,
+    pub last_change: Instant,
+    /// The input suggestions were last asked for
+    pub requested_for: String,
+    /// Moves on whenever the input changes, so requests for older input are dropped
+    pub generation: Arc<AtomicU64>,
+}
+
+impl AutocompleteState {
+    /// Drops the current suggestions and any still being fetched
+    pub fn dismiss(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.suggestions.lock().unwrap().clear();
+        self.selected_index = None;
+        self.requested_for = self.last_input.clone();
+    }
+
+    pub fn selected_suggestion(&self) -> Option<String> {
+        let suggestions = self.suggestions.lock().unwrap();
+        self.selected_index
+            .and_then(|index| suggestions.get(index))
+            .cloned()
     }
+}
+
+/// The summary of the selected result, for the preview pane next to the results
+pub struct PreviewState {
+    pub summary: Shared<SummaryState>,
+    /// The result selected as of the last update, and when the selection last changed
+    pub shown_for: Option<String>,
+    pub last_change: Instant,
+    /// The result a summary was last asked for
+    pub requested_for: Option<String>,
+    /// Moves on whenever the selection changes, so summaries of older results are dropped
+    pub generation: Arc<AtomicU64>,
+}
 
-    fn insert_to_input_at_cursor(&mut self, c: char) -> () {
-        self.input.insert(self.cursor_pos, c);
+/// The advanced search form, which builds a query out of a field per CirrusSearch operator
+pub struct AdvancedSearchState {
+    pub is_open: bool,
+    /// What's typed for each of `SearchOperator::ALL`
+    pub values: Vec<TextInput>,
+    /// One of the fields, or `NAMESPACE_ROW`
+    pub selected_row: usize,
+    /// Index into `NAMESPACES`
+    pub namespace: usize,
+}
+
+impl AdvancedSearchState {
+    pub const NAMESPACE_ROW: usize = SearchOperator::ALL.len();
+
+    /// The `srsearch` query the fields add up to
+    pub fn compiled_query(&self) -> String {
+        let values: Vec<(SearchOperator, &str)> = SearchOperator::ALL
+            .into_iter()
+            .zip(self.values.iter().map(TextInput::as_str))
+            .collect();
+        wikipedia::compile_search_query(&values)
     }
 
-    fn remove_from_input_at_cursor(&mut self) -> () {
-        if self.cursor_pos <= self.input.len() && self.cursor_pos > 0 {
-            self.input.remove(self.cursor_pos - 1);
+    pub fn select_row(&mut self, row: usize) {
+        self.selected_row = row;
+        if let Some(value) = self.selected_value() {
+            value.move_to_end();
         }
     }
 
-    fn get_cursor_pos(&self) -> usize {
-        self.cursor_pos
+    /// The field being typed into, unless the namespace row is selected
+    pub fn selected_value(&mut self) -> Option<&mut TextInput> {
+        self.values.get_mut(self.selected_row)
+    }
+
+    pub fn scroll(&mut self, scroll_direction: ScrollDirection) {
+        let total_rows = Self::NAMESPACE_ROW + 1;
+        self.select_row(match scroll_direction {
+            ScrollDirection::DOWN => remainder(self.selected_row + 1, total_rows),
+            ScrollDirection::UP => remainder(self.selected_row + total_rows - 1, total_rows),
+        });
+    }
+
+    pub fn cycle_namespace(&mut self, forward: bool) {
+        self.namespace = match forward {
+            true => remainder(self.namespace + 1, NAMESPACES.len()),
+            false => remainder(self.namespace + NAMESPACES.len() - 1, NAMESPACES.len()),
+        };
     }
+}
 
-    fn set_cursor_pos(&mut self, new_cursor_pos: usize) -> () {
-        self.cursor_pos = new_cursor_pos;
+impl TypeableState for SearchState {
+    fn text_input(&mut self) -> &mut TextInput {
+        &mut self.input
     }
 
-    fn trigger_text_focus(&mut self) -> () {
+    fn trigger_text_focus(&mut self) {
         self.text_box_is_highlighted = true;
+        self.suggestion_selected = false;
     }
 }
 pub struct MenuState {
@@ -277,10 +433,321 @@ impl ActionMenu for CreditState {
     }
 }
 
+/// What the command palette is listing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaletteKind {
+    Commands,
+    SearchHistory,
+}
+
+pub struct CommandPaletteState {
+    pub kind: PaletteKind,
+    pub input: TextInput,
+    /// Index into the filtered commands
+    pub selected_index: usize,
+    pub commands: Vec<ActionItem>,
+    pub return_state: AppState,
+}
+
+impl CommandPaletteState {
+    /// The commands that fuzzy-match the input, best first, with the positions of the matched
+    /// characters in their labels. Typing "open <title>" offers to open that article, unless
+    /// the palette is listing past searches.
+    pub fn matches(&self) -> Vec<(ActionItem, Vec<usize>)> {
+        let mut scored: Vec<(i64, ActionItem, Vec<usize>)> = self
+            .commands
+            .iter()
+            .filter_map(|command| {
+                let (score, positions) = fuzzy_match(self.input.as_str(), command.label())?;
+                Some((score, command.clone(), positions))
+            })
+            .collect();
+        // Stable, so equally good matches keep their order
+        scored.sort_by_key(|(score, _, _)| -score);
+
+        let mut matches: Vec<(ActionItem, Vec<usize>)> = scored
+            .into_iter()
+            .map(|(_, command, positions)| (command, positions))
+            .collect();
+        let title = self.input.as_str().trim();
+        let title = title
+            .strip_prefix("open article ")
+            .or(title.strip_prefix("open "))
+            .unwrap_or(title)
+            .trim()
+            .to_string();
+        if !title.is_empty() && self.kind == PaletteKind::Commands {
+            let open_article = ActionItem::new(&format!("Open article \"{title}\""), move |app| {
+                app.article.history.clear();
+                app.open_article(title.clone());
+            });
+            let position = match self.input.as_str().trim_start().starts_with("open ") {
+                true => 0,
+                false => matches.len(),
+            };
+            matches.insert(position, (open_article, vec![]));
+        }
+        matches
+    }
+
+    pub fn scroll(&mut self, scroll_direction: ScrollDirection) {
+        let total_matches = self.matches().len().max(1);
+        self.selected_index = match scroll_direction {
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_matches),
+            ScrollDirection::UP => {
+                remainder(self.selected_index + total_matches - 1, total_matches)
+            }
+        };
+    }
+}
+
+impl TypeableState for CommandPaletteState {
+    fn text_input(&mut self) -> &mut TextInput {
+        &mut self.input
+    }
+
+    /// The matches change with the input, so start again from the best one
+    fn trigger_text_focus(&mut self) {
+        self.selected_index = 0;
+    }
+}
+
+/// What part of a bookmark is being edited
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookmarkField {
+    Note,
+    /// Written as a comma-separated list
+    Tags,
+}
+
+pub struct BookmarkEdit {
+    pub field: BookmarkField,
+    /// Index into `App::bookmarks` of the bookmark being edited
+    pub entry: usize,
+    pub input: TextInput,
+}
+
+/// The bookmarks screen. Typing filters the list being shown.
+pub struct BookmarksState {
+    pub list: BookmarkList,
+    pub filter: TextInput,
+    /// Index into `App::visible_bookmarks`
+    pub selected_index: usize,
+    pub edit: Option<BookmarkEdit>,
+    pub return_state: AppState,
+}
+
+impl BookmarksState {
+    pub fn scroll(&mut self, scroll_direction: ScrollDirection, total_rows: usize) {
+        if total_rows == 0 {
+            return;
+        }
+        self.selected_index = match scroll_direction {
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_rows),
+            ScrollDirection::UP => remainder(self.selected_index + total_rows - 1, total_rows),
+        };
+    }
+}
+
+impl TypeableState for BookmarksState {
+    fn text_input(&mut self) -> &mut TextInput {
+        &mut self.filter
+    }
+
+    /// The matches change with the filter, so start again from the best one
+    fn trigger_text_focus(&mut self) {
+        self.selected_index = 0;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// Side by side
+    Vertical,
+    /// One above the other
+    Horizontal,
+}
+
+/// A second article shown next to the current one
+pub struct SplitState {
+    pub direction: SplitDirection,
+    /// The pane without focus. The focused one is always `App::article`.
+    pub other: ArticleState,
+    /// Whether the focused pane is the right or bottom one
+    pub focus_second: bool,
+    /// Keep the other pane at the same section as the focused one
+    pub sync_scroll: bool,
+    /// The focused pane's scroll the other pane was last lined up with
+    pub synced_scroll: Option<usize>,
+}
+
+/// A tab that isn't being read, along with the pane it was split with
+pub struct BackgroundTab {
+    pub article: ArticleState,
+    pub split: Option<SplitState>,
+}
+
+impl BackgroundTab {
+    fn panes(&self) -> (&ArticleState, Option<&SplitState>) {
+        (&self.article, self.split.as_ref())
+    }
+}
+
+/// Lines the article moves per step of the mouse wheel
+const WHEEL_SCROLL_LINES: usize = 3;
+
+/// Something on screen the mouse can act on, recorded by `ui` as it draws
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseTarget {
+    SearchResult(usize),
+    ResultsScrollBar,
+    /// A row of the menu, theme picker or settings list on screen
+    MenuItem(usize),
+    ArticleText,
+    ArticleScrollBar,
+    /// A title in the search box's suggestions dropdown
+    Suggestion(usize),
+    /// The "Did you mean" line above the results
+    DidYouMean,
+    /// A tab in the article tab bar
+    Tab(usize),
+    /// The article pane without focus
+    OtherPane,
+}
+
+pub struct ThemePickerState {
+    pub menu: MenuState,
+    /// Restored if the picker is closed without choosing
+    pub previous_theme: Theme,
+    pub return_state: AppState,
+    pub load_errors: Vec<String>,
+}
+
+pub struct SettingsState {
+    /// Index into `Setting::ALL`, followed by the "Save" and "Back" rows
+    pub selected_index: usize,
+    pub input: TextInput,
+    /// Whether the selected setting's value is being typed into `input`
+    pub is_editing: bool,
+    /// Result of the last change or save, or why it was refused
+    pub message: Option<String>,
+    pub return_state: AppState,
+}
+
+impl SettingsState {
+    pub const SAVE_ROW: usize = Setting::ALL.len();
+    pub const BACK_ROW: usize = Setting::ALL.len() + 1;
+
+    pub fn selected_setting(&self) -> Option<Setting> {
+        Setting::ALL.get(self.selected_index).copied()
+    }
+
+    pub fn scroll(&mut self, scroll_direction: ScrollDirection) {
+        let total_rows = Self::BACK_ROW + 1;
+        self.selected_index = match scroll_direction {
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_rows),
+            ScrollDirection::UP => remainder(self.selected_index + total_rows - 1, total_rows),
+        };
+    }
+}
+
 pub struct ArticleState {
     pub article_name: String,
-    pub markdown_spans: Shared<Vec<FormattedSpan>>,
+    /// The wiki the article is loaded from
+    pub language: String,
+    pub article_spans: Shared<Vec<FormattedSpan>>,
     pub is_loading_article: Shared<bool>,
+    pub show_hidden_sections: bool,
+    /// Show formulas as their LaTeX source instead of the Unicode rendering, for copying
+    pub show_math_source: bool,
+    /// First wrapped line shown in the article view
+    pub scroll: usize,
+    /// Index (into the visible spans) of the first span of the selected link
+    pub selected_link: Option<usize>,
+    /// Section to jump to once the article has finished loading
+    pub pending_section: Option<String>,
+    /// Previously read articles and where they were scrolled to
+    pub history: Vec<HistoryEntry>,
+    /// Width and height of the text area at the last draw, so the article can be wrapped
+    /// the same way outside of `ui`
+    pub view_size: Cell<(usize, usize)>,
+    /// Words in the shown sections and the furthest the article could scroll, at the last draw
+    pub word_count: Cell<usize>,
+    pub max_scroll: Cell<usize>,
+    /// How the current article was loaded
+    pub load_info: Shared<Option<LoadInfo>>,
+}
+
+impl ArticleState {
+    pub fn new(article_name: String, language: String) -> Self {
+        Self {
+            article_name,
+            language,
+            article_spans: create_shared(Vec::new()),
+            is_loading_article: create_shared(false),
+            show_hidden_sections: false,
+            show_math_source: false,
+            scroll: 0,
+            selected_link: None,
+            pending_section: None,
+            history: Vec::new(),
+            view_size: Cell::new((80, 24)),
+            word_count: Cell::new(0),
+            max_scroll: Cell::new(0),
+            load_info: create_shared(None),
+        }
+    }
+
+    /// The visible spans, wrapped the way they were at the last draw
+    pub fn layout(&self, section_filter: &SectionFilter) -> (Vec<FormattedSpan>, Vec<WrappedLine>) {
+        let spans = self.visible_spans(section_filter);
+        let (width, _) = self.view_size.get();
+        let lines = wrap_spans(&spans, width);
+        (spans, lines)
+    }
+
+    /// Whether the article has been loaded, as opposed to never opened or still loading
+    pub fn is_loaded(&self) -> bool {
+        !self.currently_loading() && !self.article_spans.lock().unwrap().is_empty()
+    }
+
+    pub fn currently_loading(&self) -> bool {
+        match self.is_loading_article.try_lock() {
+            Ok(is_loading) => *is_loading,
+            Err(_) => true,
+        }
+    }
+
+    /// The range of spans making up the selected link, which may be split over several spans
+    pub fn selected_link_spans(&self, spans: &[FormattedSpan]) -> Range<usize> {
+        match self.selected_link {
+            Some(start) if start < spans.len() && spans[start].link.is_some() => {
+                let end = (start..spans.len())
+                    .find(|&index| spans[index].link != spans[start].link)
+                    .unwrap_or(spans.len());
+                start..end
+            }
+            _ => 0..0,
+        }
+    }
+
+    /// The article's spans, minus the sections the filter hides for its wiki (unless they have
+    /// been revealed)
+    pub fn visible_spans(&self, section_filter: &SectionFilter) -> Vec<FormattedSpan> {
+        let mut spans = self.article_spans.lock().unwrap().clone();
+        if self.show_math_source {
+            for span in spans.iter_mut() {
+                if let Some(math_source) = &span.math_source {
+                    span.text = math_source.clone();
+                }
+            }
+        }
+        if self.show_hidden_sections {
+            spans
+        } else {
+            wikipedia::remove_unnecessary_spans(spans, section_filter, &self.language)
+        }
+    }
 }
 
 pub struct App {
@@ -288,29 +755,119 @@ pub struct App {
     pub search: SearchState,
     pub search_menu: MenuState,
     pub credit: CreditState,
+    /// The article in the tab being read
     pub article: ArticleState,
+    /// The other open tabs, in order. The current tab sits between `tabs[active_tab - 1]`
+    /// and `tabs[active_tab]`.
+    pub tabs: Vec<BackgroundTab>,
+    pub active_tab: usize,
+    /// The second article pane, when the current tab is split
+    pub split: Option<SplitState>,
     pub article_menu: MenuState,
+    pub theme_picker: ThemePickerState,
+    pub settings: SettingsState,
+    pub command_palette: CommandPaletteState,
+    pub bookmarks_screen: BookmarksState,
+    pub config: Config,
+    /// Where the settings screen saves `config`
+    pub config_path: PathBuf,
+    /// The config file as it was read, before the command line options
+    pub file_config: Config,
+    /// Settings given on the command line and not changed since. Saving keeps their
+    /// values from `file_config`.
+    pub overridden_settings: Vec<Setting>,
+    pub keymap: Keymap,
+    /// Keys typed so far of a binding that takes several, like "g g"
+    pub pending_keys: PendingKeys,
+    /// Whether the key binding overlay is open
+    pub show_help: bool,
     pub cache: Shared<CachingSession>,
     pub is_running: bool,
     pub state: AppState,
     pub theme: Theme,
+    pub language: String,
+    pub section_filter: SectionFilter,
+    pub images: ImageStore,
+    pub graphics_protocol: GraphicsProtocol,
+    /// Images the last draw left room for, to be drawn by `GraphicsOutput`
+    pub image_placements: RefCell<Vec<ImagePlacement>>,
+    /// Images as last scaled for drawing with half blocks
+    pub half_block_images: RefCell<HalfBlockCache>,
+    /// Where the last draw put things that can be clicked
+    pub mouse_areas: RefCell<Vec<(Rect, MouseTarget)>>,
+    /// The scroll bar being dragged, if the mouse went down on one
+    pub dragging: Option<(Rect, MouseTarget)>,
+    /// A short message for the status bar, and when it was shown
+    pub notification: Option<(String, Instant)>,
+    /// Queries searched for, latest first, with when
+    pub search_history: SearchHistory,
+    pub bookmarks: Bookmarks,
+    /// How far down articles were read, so they can be picked up again
+    pub reading_positions: ReadingPositions,
+    /// When `reading_positions` was last written to disk
+    pub positions_saved_at: Instant,
 }
 
+/// How long the search input has to stay the same before suggestions are fetched for it
+const AUTOCOMPLETE_DELAY: Duration = Duration::from_millis(250);
+
+/// How long a result has to stay selected before its preview is fetched
+const PREVIEW_DELAY: Duration = Duration::from_millis(200);
+
+/// How many recent searches are kept
+const MAX_RECENT_SEARCHES: usize = 8;
+
+/// How many of each bookmark list the title screen shows
+const MAX_LANDING_BOOKMARKS: usize = 3;
+
+/// How long a notification stays in the status bar
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+
+/// How often reading positions are saved while wik is running, besides when it quits
+const POSITIONS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
 impl Default for App {
     fn default() -> Self {
         let mut app = App {
             title: TitleState {
-                input: String::new(),
-                cursor_pos: 0,
+                input: TextInput::default(),
+                selected_item: None,
+                featured: create_shared(None),
+                featured_language: String::new(),
             },
             search: SearchState {
-                input: String::new(),
+                input: TextInput::default(),
                 current_query: String::new(),
                 results: create_shared(Vec::new()),
-                cursor_pos: 0,
                 is_loading_query: create_shared(false),
+                load_info: create_shared(None),
+                paging: create_shared(SearchPaging::default()),
+                options: SearchOptions::default(),
+                advanced: AdvancedSearchState {
+                    is_open: false,
+                    values: vec![TextInput::default(); SearchOperator::ALL.len()],
+                    selected_row: 0,
+                    namespace: 0,
+                },
+                preview: PreviewState {
+                    summary: create_shared(SummaryState::Loading),
+                    shown_for: None,
+                    last_change: Instant::now(),
+                    requested_for: None,
+                    generation: Arc::new(AtomicU64::new(0)),
+                },
+                autocomplete: AutocompleteState {
+                    suggestions: create_shared(Vec::new()),
+                    selected_index: None,
+                    last_input: String::new(),
+                    last_change: Instant::now(),
+                    requested_for: String::new(),
+                    generation: Arc::new(AtomicU64::new(0)),
+                },
                 selected_index: 0,
+                suggestion_selected: false,
                 text_box_is_highlighted: true,
+                history_recall: None,
             },
             search_menu: MenuState {
                 selected_index: 0,
@@ -320,29 +877,116 @@ impl Default for App {
                 selected_index: 0,
                 options: vec![],
             },
-            article: ArticleState {
-                article_name: String::from("Philosophy"),
-                markdown_spans: create_shared(Vec::new()),
-                is_loading_article: create_shared(false),
-            },
+            article: ArticleState::new(
+                String::from("Philosophy"),
+                String::from(wikipedia::DEFAULT_LANGUAGE),
+            ),
+            tabs: Vec::new(),
+            active_tab: 0,
+            split: None,
             article_menu: MenuState {
                 selected_index: 0,
                 options: vec![],
             },
-            cache: create_shared(CachingSession::new()),
+            theme_picker: ThemePickerState {
+                menu: MenuState {
+                    selected_index: 0,
+                    options: vec![],
+                },
+                previous_theme: Theme::default(),
+                return_state: AppState::SearchMenu,
+                load_errors: vec![],
+            },
+            settings: SettingsState {
+                selected_index: 0,
+                input: TextInput::default(),
+                is_editing: false,
+                message: None,
+                return_state: AppState::SearchMenu,
+            },
+            command_palette: CommandPaletteState {
+                kind: PaletteKind::Commands,
+                input: TextInput::default(),
+                selected_index: 0,
+                commands: vec![],
+                return_state: AppState::Search,
+            },
+            bookmarks_screen: BookmarksState {
+                list: BookmarkList::Bookmarks,
+                filter: TextInput::default(),
+                selected_index: 0,
+                edit: None,
+                return_state: AppState::Search,
+            },
+            config: Config::default(),
+            config_path: Config::default_path(),
+            file_config: Config::default(),
+            overridden_settings: Vec::new(),
+            keymap: Keymap::default(),
+            pending_keys: PendingKeys::default(),
+            show_help: false,
+            cache: create_shared(CachingSession::new(None)),
             is_running: false,
             state: AppState::Title,
             theme: Theme::default(),
+            language: String::from(wikipedia::DEFAULT_LANGUAGE),
+            section_filter: SectionFilter::default(),
+            images: create_shared(HashMap::new()),
+            graphics_protocol: GraphicsProtocol::detect(),
+            image_placements: RefCell::new(Vec::new()),
+            half_block_images: RefCell::new(HalfBlockCache::default()),
+            mouse_areas: RefCell::new(Vec::new()),
+            dragging: None,
+            notification: None,
+            search_history: SearchHistory::default(),
+            bookmarks: Bookmarks::default(),
+            reading_positions: ReadingPositions::default(),
+            positions_saved_at: Instant::now(),
         };
 
         app.search_menu.options = vec![
             ActionItem::new("Back", |app| app.state = AppState::Search),
+            ActionItem::new("Themes", |app| app.open_theme_picker(AppState::SearchMenu)),
+            ActionItem::new("Settings", |app| app.open_settings(AppState::SearchMenu)),
+            ActionItem::new("Search history", |app| app.open_search_history()),
+            ActionItem::new("Bookmarks", |app| app.open_bookmarks(AppState::SearchMenu)),
+            ActionItem::new("Advanced search", |app| {
+                app.state = AppState::Search;
+                if !app.search.advanced.is_open {
+                    app.toggle_advanced_search();
+                }
+            }),
+            ActionItem::new("Resume last session", |app| app.resume_session()),
             ActionItem::new("Credits", |app| app.state = AppState::Credit),
             ActionItem::new("Quit", |app| app.is_running = false),
         ];
 
         app.article_menu.options = vec![
             ActionItem::new("Back", |app| app.state = AppState::Article),
+            ActionItem::new("Show/hide stripped sections", |app| {
+                app.article.show_hidden_sections = !app.article.show_hidden_sections;
+                app.state = AppState::Article;
+                app.notify(match app.article.show_hidden_sections {
+                    true => "Showing stripped sections",
+                    false => "Hiding stripped sections",
+                });
+            }),
+            ActionItem::new("Show/hide LaTeX source of formulas", |app| {
+                app.perform_on_article(Action::ToggleMath);
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Bookmark this article", |app| {
+                app.state = AppState::Article;
+                app.add_bookmark(BookmarkList::Bookmarks);
+            }),
+            ActionItem::new("Read later", |app| {
+                app.state = AppState::Article;
+                app.add_bookmark(BookmarkList::ReadLater);
+            }),
+            ActionItem::new("Bookmarks", |app| app.open_bookmarks(AppState::ArticleMenu)),
+            ActionItem::new("Close tab", |app| app.close_tab()),
+            ActionItem::new("Themes", |app| app.open_theme_picker(AppState::ArticleMenu)),
+            ActionItem::new("Settings", |app| app.open_settings(AppState::ArticleMenu)),
             ActionItem::new("Search", |app| app.state = AppState::Search),
             ActionItem::new("Quit", |app| app.is_running = false),
         ];
@@ -359,47 +1003,2163 @@ impl Default for App {
 }
 
 impl App {
-    pub fn new() -> Self {
-        App::default()
+    pub fn new(config: Config, config_path: PathBuf) -> Self {
+        let mut app = App {
+            cache: create_shared(CachingSession::new(config.cache_dir())),
+            config,
+            config_path,
+            ..App::default()
+        };
+        // A store that can't be read starts out empty, and says why in the status bar
+        let mut load_errors: Vec<String> = Vec::new();
+        let mut report = |what: &str, e: Box<dyn Error>| {
+            load_errors.push(format!("Couldn't load {what}: {e}"));
+        };
+        app.search_history = SearchHistory::load().unwrap_or_else(|e| {
+            report("search history", e);
+            SearchHistory::default()
+        });
+        app.bookmarks = Bookmarks::load().unwrap_or_else(|e| {
+            report("bookmarks", e);
+            Bookmarks::default()
+        });
+        app.reading_positions = ReadingPositions::load().unwrap_or_else(|e| {
+            report("reading positions", e);
+            ReadingPositions::default()
+        });
+        app.apply_config();
+        if app.config.reopen_session {
+            if let Err(e) = app.restore_session() {
+                report("the last session", e);
+            }
+        }
+        if !load_errors.is_empty() {
+            app.notify(&load_errors.join("; "));
+        }
+        app
     }
 
-    pub fn load_wikipedia_search_query(&mut self) {
-        if self.search.input.len() > 0 {
-            if !self.search.currently_loading() {
-                let input = self.search.input.clone();
-                self.search.current_query = input.clone();
-
-                let loading_flag = shared_copy(&self.search.is_loading_query);
-                let app_results = shared_copy(&self.search.results);
-                let caching_session = shared_copy(&self.cache);
-
-                wikipedia::load_search_query_to_app(
-                    input,
-                    loading_flag,
-                    app_results,
-                    caching_session,
-                );
+    /// Brings everything that depends on the config up to date with it
+    pub fn apply_config(&mut self) {
+        let (themes, _) = Theme::available_themes();
+        if let Some(theme) = themes
+            .into_iter()
+            .find(|theme| theme.name == self.config.theme)
+        {
+            self.theme = theme;
+        }
+        self.language = self.config.language.clone();
+        self.section_filter.hidden_sections = self.config.stripped_sections.clone();
+        self.graphics_protocol = self.config.images.protocol();
+        if let Ok(keymap) = Keymap::build(&self.config.keymap, &self.config.keys) {
+            self.keymap = keymap;
+        }
+        if self.title.featured_language != self.language {
+            self.title.featured_language = self.language.clone();
+            wikipedia::load_featured_article_to_app(
+                self.language.clone(),
+                shared_copy(&self.title.featured),
+                shared_copy(&self.cache),
+            );
+        }
+    }
+
+    /// Whether typed characters go into a text box on the current screen
+    pub fn text_input_focused(&self) -> bool {
+        match self.state {
+            AppState::Title => true,
+            // The namespace row of the advanced search form is picked from, not typed into
+            AppState::Search => {
+                !self.search.advanced.is_open
+                    || self.search.advanced.selected_row != AdvancedSearchState::NAMESPACE_ROW
             }
+            AppState::Settings => self.settings.is_editing,
+            AppState::CommandPalette | AppState::Bookmarks => true,
+            _ => false,
         }
     }
 
-    pub fn view_selected_article(&mut self) {
-        if let Some(title) = self.search.selected_search_result_title() {
-            self.state = AppState::Article;
-            self.article.article_name = title.clone();
+    /// The text box typing and cursor keys go to on the current screen
+    fn focused_text_box(&mut self) -> Option<&mut dyn TypeableState> {
+        Some(match self.state {
+            AppState::Settings => &mut self.settings.input,
+            AppState::CommandPalette => &mut self.command_palette,
+            AppState::Title => &mut self.title,
+            AppState::Bookmarks if self.bookmarks_screen.edit.is_some() => {
+                &mut self.bookmarks_screen.edit.as_mut()?.input
+            }
+            AppState::Bookmarks => &mut self.bookmarks_screen,
+            AppState::Search if self.search.advanced.is_open => {
+                self.search.advanced.selected_value()?
+            }
+            _ => &mut self.search,
+        })
+    }
 
-            let markdown_spans = shared_copy(&self.article.markdown_spans);
-            let loading_flag = shared_copy(&self.article.is_loading_article);
-            let caching_session = shared_copy(&self.cache);
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        // Any key closes the help overlay
+        if self.show_help {
+            self.show_help = false;
+            self.pending_keys.clear();
+            return;
+        }
 
-            wikipedia::load_article_to_app(
-                title.clone(),
-                loading_flag,
-                markdown_spans,
-                caching_session,
-            );
+        let text_input_focused = self.text_input_focused();
+        let key = KeyPress::from_event(&key);
+        match self.keymap.resolve(
+            self.state,
+            text_input_focused,
+            &mut self.pending_keys,
+            key,
+            Instant::now(),
+        ) {
+            KeyResult::Action(action) => self.perform(action),
+            KeyResult::Pending => {}
+            KeyResult::Unbound => {
+                if let Some(c) = key.typed_char().filter(|_| text_input_focused) {
+                    if let Some(typeable) = self.focused_text_box() {
+                        typeable.type_char(c);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if self.show_help {
+            if let MouseEventKind::Down(_) = event.kind {
+                self.show_help = false;
+            }
+            return;
+        }
+
+        let target = self
+            .mouse_areas
+            .borrow()
+            .iter()
+            .rev()
+            .find(|(area, _)| {
+                (area.x..area.x + area.width).contains(&event.column)
+                    && (area.y..area.y + area.height).contains(&event.row)
+            })
+            .copied();
+
+        match event.kind {
+            MouseEventKind::ScrollUp => self.scroll_with_wheel(ScrollDirection::UP),
+            MouseEventKind::ScrollDown => self.scroll_with_wheel(ScrollDirection::DOWN),
+            MouseEventKind::Down(MouseButton::Left) => match target {
+                Some((area, MouseTarget::ResultsScrollBar | MouseTarget::ArticleScrollBar)) => {
+                    self.dragging = target;
+                    self.drag_scroll_bar(area, event.row);
+                }
+                Some((area, MouseTarget::ArticleText)) => {
+                    self.click_article(event.column - area.x, event.row - area.y)
+                }
+                Some((_, MouseTarget::SearchResult(index))) => self.click_search_result(index),
+                Some((_, MouseTarget::MenuItem(index))) => self.click_menu_item(index),
+                Some((_, MouseTarget::Suggestion(index))) => self.click_suggestion(index),
+                Some((_, MouseTarget::DidYouMean)) => self.search_for_suggestion(),
+                Some((_, MouseTarget::Tab(index))) => self.switch_tab(index),
+                Some((_, MouseTarget::OtherPane)) => self.switch_pane(),
+                None => {}
+            },
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some((area, _)) = self.dragging {
+                    self.drag_scroll_bar(area, event.row);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging = None,
+            _ => {}
+        }
+    }
+
+    fn scroll_with_wheel(&mut self, scroll_direction: ScrollDirection) {
+        match (self.state, scroll_direction) {
+            (AppState::Article, scroll_direction) => {
+                self.scroll_article(scroll_direction, WHEEL_SCROLL_LINES)
+            }
+            (_, ScrollDirection::UP) => self.perform(Action::ScrollUp),
+            (_, ScrollDirection::DOWN) => self.perform(Action::ScrollDown),
+        }
+    }
+
+    /// Clicking a result selects it, clicking the selected one opens it
+    fn click_search_result(&mut self, index: usize) {
+        if index == self.search.selected_index && !self.search.text_box_is_highlighted {
+            self.view_selected_article();
         } else {
-            self.state = AppState::SearchMenu;
+            self.search.selected_index = index;
+            self.search.text_box_is_highlighted = false;
+            self.search.suggestion_selected = false;
+        }
+    }
+
+    /// Clicking a suggestion selects it, clicking the selected one opens it
+    fn click_suggestion(&mut self, index: usize) {
+        if self.search.autocomplete.selected_index == Some(index) {
+            self.perform(Action::Confirm);
+        } else {
+            self.search.autocomplete.selected_index = Some(index);
+        }
+    }
+
+    /// Clicking a menu row selects it, clicking the selected one runs it
+    fn click_menu_item(&mut self, index: usize) {
+        let menu: &mut dyn ActionMenu = match self.state {
+            AppState::SearchMenu => &mut self.search_menu,
+            AppState::ArticleMenu => &mut self.article_menu,
+            AppState::Credit => &mut self.credit,
+            AppState::ThemePicker => &mut self.theme_picker.menu,
+            AppState::Title => {
+                if self.title.selected_item == Some(index) {
+                    self.open_landing_item(index);
+                } else if index < self.landing_items().len() {
+                    self.title.selected_item = Some(index);
+                }
+                return;
+            }
+            AppState::CommandPalette => {
+                if index == self.command_palette.selected_index {
+                    self.run_selected_command();
+                } else {
+                    self.command_palette.selected_index = index;
+                }
+                return;
+            }
+            AppState::Bookmarks => {
+                if index == self.bookmarks_screen.selected_index {
+                    self.perform_on_bookmarks(Action::Confirm);
+                } else if self.bookmarks_screen.edit.is_none()
+                    && index < self.visible_bookmarks().len()
+                {
+                    self.bookmarks_screen.selected_index = index;
+                }
+                return;
+            }
+            AppState::Search if self.search.advanced.is_open => {
+                let advanced = &mut self.search.advanced;
+                if index == AdvancedSearchState::NAMESPACE_ROW && index == advanced.selected_row {
+                    advanced.cycle_namespace(true);
+                } else if index <= AdvancedSearchState::NAMESPACE_ROW {
+                    advanced.select_row(index);
+                }
+                return;
+            }
+            AppState::Settings => {
+                if index == self.settings.selected_index {
+                    self.activate_setting();
+                } else if !self.settings.is_editing {
+                    self.settings.selected_index = index;
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if index == menu.get_index() {
+            self.perform(Action::Confirm);
+        } else if index < menu.total_options() {
+            menu.set_index(index);
+            if self.state == AppState::ThemePicker {
+                self.theme_picker.menu.get_selected_action()(self);
+            }
+        }
+    }
+
+    /// Follows the link under a click at `column`, `row` of the article text
+    fn click_article(&mut self, column: u16, row: u16) {
+        let (spans, lines) = self.article_layout();
+        let Some(line) = lines.get(self.article.scroll + row as usize) else {
+            return;
+        };
+
+        let mut line_width = 0;
+        let clicked_span = line.fragments.iter().find_map(|fragment| {
+            line_width += fragment.text.width();
+            ((column as usize) < line_width).then_some(fragment.span_index)
+        });
+        let Some(span_index) = clicked_span.filter(|&index| spans[index].link.is_some()) else {
+            return;
+        };
+
+        // A link can be split over several spans, the selection starts at the first
+        let link_start = (0..=span_index)
+            .rev()
+            .take_while(|&index| spans[index].link == spans[span_index].link)
+            .last()
+            .unwrap_or(span_index);
+        self.article.selected_link = Some(link_start);
+        self.follow_selected_link();
+    }
+
+    /// Moves whatever the scroll bar in `area` controls to match the mouse's row
+    fn drag_scroll_bar(&mut self, area: Rect, row: u16) {
+        let track = area.height.saturating_sub(1).max(1) as f64;
+        let fraction = (row.saturating_sub(area.y) as f64 / track).min(1.0);
+        match self.dragging.map(|(_, target)| target) {
+            Some(MouseTarget::ResultsScrollBar) => {
+                let total_results = self.search.results.lock().unwrap().len();
+                if total_results > 0 {
+                    self.search.selected_index =
+                        (fraction * (total_results - 1) as f64).round() as usize;
+                    self.search.text_box_is_highlighted = false;
+                }
+            }
+            Some(MouseTarget::ArticleScrollBar) => {
+                let (_, lines) = self.article_layout();
+                let max_scroll = self.max_article_scroll(&lines);
+                self.article.scroll = (fraction * max_scroll as f64).round() as usize;
+            }
+            _ => {}
+        }
+    }
+
+    /// Carries out the action in whatever way it means on the current screen
+    pub fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.is_running = false,
+            Action::Help => self.show_help = true,
+            Action::CommandPalette if self.state != AppState::CommandPalette => {
+                self.open_command_palette()
+            }
+            Action::SearchHistory => self.open_search_history(),
+            Action::OpenBookmarks if self.state != AppState::Bookmarks => {
+                self.open_bookmarks(self.state)
+            }
+            Action::CursorLeft
+            | Action::CursorRight
+            | Action::CursorToStart
+            | Action::CursorToEnd
+            | Action::DeleteBackward => {
+                let Some(typeable) = self.focused_text_box() else {
+                    return;
+                };
+                match action {
+                    Action::CursorLeft => typeable.move_cursor_one_step(CursorDirection::LEFT),
+                    Action::CursorRight => typeable.move_cursor_one_step(CursorDirection::RIGHT),
+                    Action::CursorToStart => typeable.move_cursor_to_start(),
+                    Action::CursorToEnd => typeable.move_cursor_to_end(),
+                    _ => typeable.backspace(),
+                }
+            }
+            _ => match self.state {
+                AppState::Title => self.perform_on_title(action),
+                AppState::Search => self.perform_on_search(action),
+                AppState::SearchMenu | AppState::ArticleMenu | AppState::Credit => {
+                    self.perform_on_menu(action)
+                }
+                AppState::Article => self.perform_on_article(action),
+                AppState::ThemePicker => self.perform_on_theme_picker(action),
+                AppState::Settings => self.perform_on_settings(action),
+                AppState::CommandPalette => self.perform_on_command_palette(action),
+                AppState::Bookmarks => self.perform_on_bookmarks(action),
+            },
+        }
+    }
+
+    fn perform_on_command_palette(&mut self, action: Action) {
+        match action {
+            Action::Close => self.state = self.command_palette.return_state,
+            Action::ScrollUp => self.command_palette.scroll(ScrollDirection::UP),
+            Action::ScrollDown => self.command_palette.scroll(ScrollDirection::DOWN),
+            Action::Confirm => self.run_selected_command(),
+            _ => {}
         }
     }
+
+    fn perform_on_title(&mut self, action: Action) {
+        let total_items = self.landing_items().len();
+        match action {
+            Action::ScrollDown if total_items > 0 => {
+                self.title.selected_item = Some(match self.title.selected_item {
+                    Some(index) => (index + 1).min(total_items - 1),
+                    None => 0,
+                });
+            }
+            Action::ScrollUp => {
+                self.title.selected_item = self
+                    .title
+                    .selected_item
+                    .and_then(|index| index.checked_sub(1));
+            }
+            Action::Confirm => match self.title.selected_item {
+                Some(index) => self.open_landing_item(index),
+                None => {
+                    let query = self.title.input.as_str().trim().to_string();
+                    self.search_from_title(query);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Recent searches, the latest of each bookmark list, then today's featured article once it
+    /// has loaded
+    pub fn landing_items(&self) -> Vec<LandingItem> {
+        let recent_searches = match self.config.search_history {
+            true => self.search_history.recent(MAX_RECENT_SEARCHES),
+            false => Vec::new(),
+        };
+        let mut items: Vec<LandingItem> = recent_searches
+            .into_iter()
+            .map(LandingItem::RecentSearch)
+            .collect();
+        for list in [BookmarkList::ReadLater, BookmarkList::Bookmarks] {
+            items.extend(
+                self.bookmarks
+                    .in_list(list)
+                    .into_iter()
+                    .take(MAX_LANDING_BOOKMARKS)
+                    .map(|index| LandingItem::Bookmark(self.bookmarks.entries[index].clone())),
+            );
+        }
+        if let Some(featured) = self.title.featured.lock().unwrap().as_ref() {
+            items.push(LandingItem::Article(featured.title.clone()));
+        }
+        items
+    }
+
+    fn open_landing_item(&mut self, index: usize) {
+        match self.landing_items().get(index) {
+            Some(LandingItem::RecentSearch(query)) => self.search_from_title(query.clone()),
+            Some(LandingItem::Bookmark(bookmark)) => self.open_bookmark(bookmark.clone()),
+            Some(LandingItem::Article(title)) => {
+                self.article.history.clear();
+                self.open_article(title.clone());
+            }
+            None => {}
+        }
+    }
+
+    /// Runs `query` and goes straight to its results. Without a query it just goes to searching.
+    fn search_from_title(&mut self, query: String) {
+        self.state = AppState::Search;
+        if query.is_empty() {
+            return;
+        }
+        self.search.input.set(query);
+        self.search.selected_index = 0;
+        self.load_wikipedia_search_query();
+        self.search.text_box_is_highlighted = false;
+    }
+
+    fn perform_on_search(&mut self, action: Action) {
+        match action {
+            Action::AdvancedSearch => self.toggle_advanced_search(),
+            _ if self.search.advanced.is_open => self.perform_on_advanced_search(action),
+            // Enter Escape menu, from where one can exit normally
+            Action::OpenMenu => self.state = AppState::SearchMenu,
+            Action::Confirm => {
+                if let Some(title) = self
+                    .search
+                    .autocomplete
+                    .selected_suggestion()
+                    .filter(|_| self.search.showing_suggestions())
+                {
+                    self.search.autocomplete.dismiss();
+                    self.article.history.clear();
+                    self.open_article(title);
+                } else if self.search.suggestion_selected {
+                    self.search_for_suggestion();
+                } else if self.search.text_box_is_highlighted {
+                    self.search.autocomplete.dismiss();
+                    self.load_wikipedia_search_query();
+                    self.search.text_box_is_highlighted = false;
+                } else {
+                    self.view_selected_article();
+                }
+            }
+            Action::ViewResult => self.view_selected_article(),
+            Action::TogglePreview => self.toggle_preview(),
+            Action::CycleSort => self.set_search_sort(self.search.options.sort.next()),
+            Action::ScrollDown if self.search.showing_suggestions() => {
+                let autocomplete = &mut self.search.autocomplete;
+                let last_index = autocomplete.suggestions.lock().unwrap().len() - 1;
+                autocomplete.selected_index = Some(match autocomplete.selected_index {
+                    Some(index) => (index + 1).min(last_index),
+                    None => 0,
+                });
+            }
+            // Up from the first suggestion goes back to typing
+            Action::ScrollUp
+                if self.search.showing_suggestions()
+                    && self.search.autocomplete.selected_index.is_some() =>
+            {
+                let autocomplete = &mut self.search.autocomplete;
+                autocomplete.selected_index = autocomplete
+                    .selected_index
+                    .and_then(|index| index.checked_sub(1));
+            }
+            Action::ScrollUp if self.can_browse_history() => self.recall_history(true),
+            Action::ScrollDown
+                if self.search.history_recall.is_some() && self.can_browse_history() =>
+            {
+                self.recall_history(false)
+            }
+            // "Did you mean" sits above the first result
+            Action::ScrollUp
+                if !self.search.text_box_is_highlighted
+                    && self.search.selected_index == 0
+                    && self.search.did_you_mean().is_some() =>
+            {
+                self.search.suggestion_selected = true;
+            }
+            Action::ScrollDown if self.search.suggestion_selected => {
+                self.search.suggestion_selected = false;
+            }
+            Action::ScrollUp => self.search.scroll_results(ScrollDirection::UP),
+            Action::ScrollDown => self.search.scroll_results(ScrollDirection::DOWN),
+            _ => {}
+        }
+    }
+
+    fn perform_on_menu(&mut self, action: Action) {
+        let (menu, close_state): (&mut dyn ActionMenu, AppState) = match self.state {
+            AppState::SearchMenu => (&mut self.search_menu, AppState::Search),
+            AppState::ArticleMenu => (&mut self.article_menu, AppState::Article),
+            _ => (&mut self.credit, AppState::SearchMenu),
+        };
+        match action {
+            Action::Close => self.state = close_state,
+            Action::ScrollUp => menu.scroll(ScrollDirection::UP),
+            Action::ScrollDown => menu.scroll(ScrollDirection::DOWN),
+            Action::Confirm => menu.get_selected_action()(self),
+            _ => {}
+        }
+    }
+
+    fn perform_on_article(&mut self, action: Action) {
+        let (_, height) = self.article.view_size.get();
+        match action {
+            Action::OpenMenu => self.state = AppState::ArticleMenu,
+            Action::ScrollUp => self.scroll_article(ScrollDirection::UP, 1),
+            Action::ScrollDown => self.scroll_article(ScrollDirection::DOWN, 1),
+            Action::PageUp => self.scroll_article_by_page(ScrollDirection::UP),
+            Action::PageDown => self.scroll_article_by_page(ScrollDirection::DOWN),
+            Action::HalfPageUp => self.scroll_article(ScrollDirection::UP, (height / 2).max(1)),
+            Action::HalfPageDown => self.scroll_article(ScrollDirection::DOWN, (height / 2).max(1)),
+            Action::ScrollToTop => self.scroll_article(ScrollDirection::UP, usize::MAX),
+            Action::ScrollToBottom => self.scroll_article(ScrollDirection::DOWN, usize::MAX),
+            Action::NextLink => self.select_link(ScrollDirection::DOWN),
+            Action::PreviousLink => self.select_link(ScrollDirection::UP),
+            Action::FollowLink => self.follow_selected_link(),
+            Action::Back => self.go_back(),
+            Action::OpenLinkInNewTab => self.open_selected_link_in_new_tab(),
+            Action::NextTab => self.switch_tab(remainder(self.active_tab + 1, self.total_tabs())),
+            Action::PreviousTab => self.switch_tab(remainder(
+                self.active_tab + self.total_tabs() - 1,
+                self.total_tabs(),
+            )),
+            Action::CloseTab => self.close_tab(),
+            Action::SplitVertical => self.split_view(SplitDirection::Vertical),
+            Action::SplitHorizontal => self.split_view(SplitDirection::Horizontal),
+            Action::SwitchPane => self.switch_pane(),
+            Action::ClosePane => self.close_pane(),
+            Action::ToggleSyncScroll => self.toggle_sync_scroll(),
+            Action::AddBookmark => self.add_bookmark(BookmarkList::Bookmarks),
+            Action::ReadLater => self.add_bookmark(BookmarkList::ReadLater),
+            Action::MoveTabLeft => self.active_tab = self.active_tab.saturating_sub(1),
+            Action::MoveTabRight => self.active_tab = (self.active_tab + 1).min(self.tabs.len()),
+            Action::ToggleMath => {
+                self.article.show_math_source = !self.article.show_math_source;
+                self.notify(match self.article.show_math_source {
+                    true => "Showing LaTeX source of formulas",
+                    false => "Showing rendered formulas",
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn perform_on_theme_picker(&mut self, action: Action) {
+        match action {
+            Action::Close => self.close_theme_picker(false),
+            Action::ScrollUp => self.preview_theme(ScrollDirection::UP),
+            Action::ScrollDown => self.preview_theme(ScrollDirection::DOWN),
+            Action::Confirm => self.close_theme_picker(true),
+            _ => {}
+        }
+    }
+
+    fn perform_on_settings(&mut self, action: Action) {
+        match action {
+            Action::Close => self.close_settings(),
+            Action::Confirm => self.activate_setting(),
+            _ if self.settings.is_editing => {}
+            Action::ScrollUp => self.settings.scroll(ScrollDirection::UP),
+            Action::ScrollDown => self.settings.scroll(ScrollDirection::DOWN),
+            Action::PreviousChoice => self.cycle_setting(false),
+            Action::NextChoice => self.cycle_setting(true),
+            _ => {}
+        }
+    }
+
+    pub fn load_wikipedia_search_query(&mut self) {
+        if !self.search.input.as_str().is_empty() && !self.search.currently_loading() {
+            let input = self.search.input.as_str().to_string();
+            self.search.current_query = input.clone();
+            self.record_search(&input);
+            self.search.history_recall = None;
+
+            self.search.selected_index = 0;
+            self.search.suggestion_selected = false;
+            self.load_search_page(input, 0);
+        }
+    }
+
+    /// Asks for title suggestions once the search input has stopped changing for a moment
+    fn update_autocomplete(&mut self) {
+        let input = self.search.input.as_str();
+        let autocomplete = &mut self.search.autocomplete;
+        if input != autocomplete.last_input {
+            autocomplete.last_input = input.to_string();
+            autocomplete.last_change = Instant::now();
+            autocomplete.generation.fetch_add(1, Ordering::SeqCst);
+            autocomplete.suggestions.lock().unwrap().clear();
+            autocomplete.selected_index = None;
+        }
+
+        let query = autocomplete.last_input.trim();
+        if self.state != AppState::Search
+            || !self.search.text_box_is_highlighted
+            || query.is_empty()
+            || autocomplete.requested_for == autocomplete.last_input
+            || autocomplete.last_change.elapsed() < AUTOCOMPLETE_DELAY
+        {
+            return;
+        }
+        autocomplete.requested_for = autocomplete.last_input.clone();
+        wikipedia::load_suggestions_to_app(
+            query.to_string(),
+            self.language.clone(),
+            autocomplete.generation.load(Ordering::SeqCst),
+            Arc::clone(&autocomplete.generation),
+            shared_copy(&autocomplete.suggestions),
+        );
+    }
+
+    /// Fetches the selected result's summary once the selection has settled for a moment
+    fn update_preview(&mut self) {
+        if self.state != AppState::Search || !self.config.search_preview {
+            return;
+        }
+        let title = self.search.selected_search_result_title();
+        let preview = &mut self.search.preview;
+        if title != preview.shown_for {
+            preview.shown_for = title;
+            preview.last_change = Instant::now();
+            preview.generation.fetch_add(1, Ordering::SeqCst);
+            *preview.summary.lock().unwrap() = SummaryState::Loading;
+        }
+
+        if let SummaryState::Loaded(summary) = &*preview.summary.lock().unwrap() {
+            if let Some(thumbnail) = &summary.thumbnail {
+                images::load_image_to_app(thumbnail.source.clone(), shared_copy(&self.images));
+            }
+        }
+        if preview.requested_for == preview.shown_for
+            || preview.last_change.elapsed() < PREVIEW_DELAY
+        {
+            return;
+        }
+        preview.requested_for = preview.shown_for.clone();
+        if let Some(title) = &preview.shown_for {
+            wikipedia::load_summary_to_app(
+                title.clone(),
+                self.language.clone(),
+                shared_copy(&self.cache),
+                preview.generation.load(Ordering::SeqCst),
+                Arc::clone(&preview.generation),
+                shared_copy(&preview.summary),
+            );
+        }
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.config.search_preview = !self.config.search_preview;
+        self.keep_setting(Setting::SearchPreview);
+        self.notify(match self.config.search_preview {
+            true => "Showing previews",
+            false => "Hiding previews",
+        });
+    }
+
+    /// Searches for the wiki's respelling of the query instead
+    pub fn search_for_suggestion(&mut self) {
+        if let Some(suggestion) = self.search.did_you_mean() {
+            self.search.input.set(suggestion);
+            self.search.autocomplete.dismiss();
+            self.load_wikipedia_search_query();
+            self.search.text_box_is_highlighted = false;
+        }
+    }
+
+    pub fn toggle_advanced_search(&mut self) {
+        let advanced = &mut self.search.advanced;
+        advanced.is_open = !advanced.is_open;
+        if advanced.is_open {
+            advanced.namespace = self.search.options.namespace;
+            advanced.select_row(advanced.selected_row);
+            self.search.autocomplete.dismiss();
+            self.search.text_box_is_highlighted = false;
+        }
+    }
+
+    fn perform_on_advanced_search(&mut self, action: Action) {
+        let advanced = &mut self.search.advanced;
+        let on_namespace_row = advanced.selected_row == AdvancedSearchState::NAMESPACE_ROW;
+        match action {
+            Action::OpenMenu | Action::Close => advanced.is_open = false,
+            Action::ScrollUp => advanced.scroll(ScrollDirection::UP),
+            Action::ScrollDown => advanced.scroll(ScrollDirection::DOWN),
+            Action::PreviousChoice if on_namespace_row => advanced.cycle_namespace(false),
+            Action::NextChoice if on_namespace_row => advanced.cycle_namespace(true),
+            Action::Confirm => self.run_advanced_search(),
+            _ => {}
+        }
+    }
+
+    /// Searches for the query the advanced search form compiles to, in its namespace
+    fn run_advanced_search(&mut self) {
+        let query = self.search.advanced.compiled_query();
+        if query.is_empty() {
+            self.notify("Fill in at least one field");
+            return;
+        }
+        self.search.input.set(query);
+        self.search.options.namespace = self.search.advanced.namespace;
+        self.search.advanced.is_open = false;
+        self.search.autocomplete.dismiss();
+        self.load_wikipedia_search_query();
+        self.search.text_box_is_highlighted = false;
+    }
+
+    /// Changes the order of the results, searching the current query again in the new order
+    pub fn set_search_sort(&mut self, sort: SearchSort) {
+        self.search.options.sort = sort;
+        self.notify(&format!("Sorting {} by {}", sort.scope(), sort.label()));
+        let query = self.search.current_query.clone();
+        if !query.is_empty() && !self.search.currently_loading() {
+            self.search.selected_index = 0;
+            self.search.suggestion_selected = false;
+            self.load_search_page(query, 0);
+        }
+    }
+
+    /// Fetches the next page of results once the last loaded one is selected
+    fn load_more_results(&mut self) {
+        if let Some((query, offset)) = self.search.next_page() {
+            self.load_search_page(query, offset);
+        }
+    }
+
+    fn load_search_page(&self, query: String, offset: u32) {
+        wikipedia::load_search_query_to_app(
+            query,
+            self.language.clone(),
+            shared_copy(&self.cache),
+            self.config.search_result_limit,
+            offset,
+            self.search.options,
+            SearchOutput {
+                loading_flag: shared_copy(&self.search.is_loading_query),
+                results: shared_copy(&self.search.results),
+                paging: shared_copy(&self.search.paging),
+                load_info: shared_copy(&self.search.load_info),
+            },
+        );
+    }
+
+    pub fn open_theme_picker(&mut self, return_state: AppState) {
+        let (themes, load_errors) = Theme::available_themes();
+        let current_index = themes
+            .iter()
+            .position(|theme| theme.name == self.theme.name)
+            .unwrap_or(0);
+
+        self.theme_picker = ThemePickerState {
+            menu: MenuState {
+                selected_index: current_index,
+                options: themes
+                    .into_iter()
+                    .map(|theme| {
+                        ActionItem::new(&theme.name.clone(), move |app| {
+                            app.theme = theme.clone();
+                        })
+                    })
+                    .collect(),
+            },
+            previous_theme: self.theme.clone(),
+            return_state,
+            load_errors,
+        };
+        self.state = AppState::ThemePicker;
+    }
+
+    /// Moves through the themes, applying each one as it is highlighted
+    pub fn preview_theme(&mut self, scroll_direction: ScrollDirection) {
+        self.theme_picker.menu.scroll(scroll_direction);
+        self.theme_picker.menu.get_selected_action()(self);
+    }
+
+    pub fn close_theme_picker(&mut self, keep_theme: bool) {
+        if keep_theme {
+            self.config.theme = self.theme.name.clone();
+            self.keep_setting(Setting::Theme);
+        } else {
+            self.theme = self.theme_picker.previous_theme.clone();
+        }
+        self.state = self.theme_picker.return_state;
+    }
+
+    /// Gathers every command that makes sense from the current screen: the menu items, the
+    /// screen's key actions, and a few that need a value such as the theme to switch to
+    pub fn open_command_palette(&mut self) {
+        let return_state = self.state;
+        let on_article_side = matches!(return_state, AppState::Article | AppState::ArticleMenu);
+        // Article commands need an article to act on
+        let has_article = self.article.is_loaded() || self.article.currently_loading();
+        let mut commands: Vec<ActionItem> = match on_article_side {
+            true => self.article_menu.options.clone(),
+            false => self.search_menu.options.clone(),
+        };
+        if !on_article_side && has_article {
+            commands.extend(self.article_menu.options.clone());
+        } else if on_article_side {
+            commands.extend(self.search_menu.options.clone());
+        }
+
+        // Every screen's actions, the one the palette was opened over first. Each action is
+        // listed once, and runs on the screen it was found on.
+        let home_screen = match return_state {
+            AppState::ArticleMenu => AppState::Article,
+            AppState::SearchMenu => AppState::Search,
+            state => state,
+        };
+        let contexts = std::iter::once(KeyContext::Screen(home_screen))
+            .chain(KeyContext::ALL)
+            .filter_map(|context| match context {
+                KeyContext::Screen(AppState::Article) if !has_article => None,
+                KeyContext::Screen(state) => Some(state),
+                KeyContext::TextInput => None,
+            });
+        let mut listed_actions: Vec<Action> = Vec::new();
+        for screen_state in contexts {
+            for (action, keys) in self.keymap.bindings_in(KeyContext::Screen(screen_state)) {
+                // Moving around a screen and acting on what's selected in it only make sense
+                // from that screen
+                let screen_local = matches!(
+                    action,
+                    Action::Close
+                        | Action::ScrollUp
+                        | Action::ScrollDown
+                        | Action::DeleteBookmark
+                        | Action::EditBookmarkNote
+                        | Action::EditBookmarkTags
+                );
+                if listed_actions.contains(&action)
+                    || (screen_local && screen_state != home_screen)
+                    || matches!(
+                        action,
+                        Action::CommandPalette
+                            | Action::Confirm
+                            | Action::PreviousChoice
+                            | Action::NextChoice
+                    )
+                {
+                    continue;
+                }
+                listed_actions.push(action);
+                let label = format!("{} ({})", action.description(), keys.join(", "));
+                commands.push(ActionItem::new(&label, move |app| {
+                    app.state = screen_state;
+                    app.perform(action);
+                }));
+            }
+        }
+
+        let (themes, _) = Theme::available_themes();
+        for theme in themes {
+            commands.push(ActionItem::new(
+                &format!("Set theme {}", theme.name),
+                move |app| {
+                    app.theme = theme.clone();
+                    app.config.theme = theme.name.clone();
+                },
+            ));
+        }
+        for sort in SearchSort::ALL {
+            commands.push(ActionItem::new(
+                &format!("Sort {} by {}", sort.scope(), sort.label()),
+                move |app| {
+                    app.state = AppState::Search;
+                    app.set_search_sort(sort);
+                },
+            ));
+        }
+        commands.push(ActionItem::new("Clear cache", |app| {
+            let cleared = app.cache.lock().unwrap().clear_session();
+            match cleared {
+                Ok(()) => app.notify("Cleared cache"),
+                Err(e) => app.notify(&format!("Couldn't clear cache: {e}")),
+            }
+        }));
+        commands.push(ActionItem::new("Credits", |app| {
+            app.state = AppState::Credit
+        }));
+
+        // The same label can come from several places, the first one wins
+        let mut labels: Vec<String> = Vec::new();
+        commands.retain(|command| {
+            let is_new = !labels.iter().any(|label| label == command.label());
+            labels.push(command.label().to_string());
+            is_new
+        });
+
+        commands.push(ActionItem::new("Clear search history", |app| {
+            app.clear_search_history()
+        }));
+        self.show_palette(PaletteKind::Commands, commands, return_state);
+    }
+
+    fn show_palette(
+        &mut self,
+        kind: PaletteKind,
+        commands: Vec<ActionItem>,
+        return_state: AppState,
+    ) {
+        self.command_palette = CommandPaletteState {
+            kind,
+            input: TextInput::default(),
+            selected_index: 0,
+            commands,
+            return_state,
+        };
+        self.state = AppState::CommandPalette;
+    }
+
+    /// Lists past searches in the palette, so they can be fuzzy-searched and run again
+    pub fn open_search_history(&mut self) {
+        if !self.config.search_history {
+            self.notify("Search history is turned off in the settings");
+            return;
+        }
+        let mut commands: Vec<ActionItem> = self
+            .search_history
+            .entries
+            .iter()
+            .map(|entry| {
+                let query = entry.query.clone();
+                ActionItem::new(
+                    &format!("{}  ({})", entry.query, format_timestamp(entry.searched_at)),
+                    move |app| app.search_from_title(query.clone()),
+                )
+            })
+            .collect();
+        commands.push(ActionItem::new("Clear search history", |app| {
+            app.clear_search_history()
+        }));
+        // Back out to where the palette was opened from, not to another palette
+        let return_state = match self.state {
+            AppState::CommandPalette => self.command_palette.return_state,
+            state => state,
+        };
+        self.show_palette(PaletteKind::SearchHistory, commands, return_state);
+    }
+
+    pub fn clear_search_history(&mut self) {
+        self.search_history.entries.clear();
+        self.search.history_recall = None;
+        match self.search_history.save() {
+            Ok(()) => self.notify("Cleared search history"),
+            Err(e) => self.notify(&format!("Couldn't clear search history: {e}")),
+        }
+    }
+
+    /// Adds `query` to the search history and saves it, if history is on
+    fn record_search(&mut self, query: &str) {
+        if !self.config.search_history {
+            return;
+        }
+        self.search_history.record(query);
+        if let Err(e) = self.search_history.save() {
+            self.notify(&format!("Couldn't save search history: {e}"));
+        }
+    }
+
+    /// Whether Up and Down in the search box step through past queries: it has to be empty,
+    /// or still showing the query that was recalled
+    fn can_browse_history(&self) -> bool {
+        self.config.search_history
+            && self.search.text_box_is_highlighted
+            && match self.search.history_recall {
+                Some(index) => self
+                    .search_history
+                    .entries
+                    .get(index)
+                    .is_some_and(|entry| entry.query == self.search.input.as_str()),
+                None => self.search.input.as_str().is_empty(),
+            }
+    }
+
+    /// Puts an older or newer past query in the search box. Newer than the latest empties it.
+    fn recall_history(&mut self, older: bool) {
+        let total_entries = self.search_history.entries.len();
+        let recalled = match (self.search.history_recall, older) {
+            (None, true) if total_entries > 0 => Some(0),
+            (Some(index), true) => Some((index + 1).min(total_entries - 1)),
+            (Some(index), false) => index.checked_sub(1),
+            _ => None,
+        };
+        self.search.history_recall = recalled;
+        let input = recalled
+            .and_then(|index| self.search_history.entries.get(index))
+            .map(|entry| entry.query.clone())
+            .unwrap_or_default();
+        // Recalled queries have been searched already, so they don't need suggestions
+        self.search.autocomplete.last_input = input.clone();
+        self.search.autocomplete.dismiss();
+        self.search.input.set(input);
+    }
+
+    /// Saves the current article, and where it's scrolled to, to `list`
+    pub fn add_bookmark(&mut self, list: BookmarkList) {
+        let title = self.article.article_name.clone();
+        self.bookmarks.add(Bookmark::new(
+            title.clone(),
+            self.article.language.clone(),
+            self.article.scroll,
+            list,
+        ));
+        match self.save_bookmarks() {
+            true if list == BookmarkList::ReadLater => {
+                self.notify(&format!("Added {title} to read later"))
+            }
+            true => self.notify(&format!("Bookmarked {title}")),
+            false => {}
+        }
+    }
+
+    /// Saves the bookmarks file, saying so in the status bar if it can't be
+    fn save_bookmarks(&mut self) -> bool {
+        match self.bookmarks.save() {
+            Ok(()) => true,
+            Err(e) => {
+                self.notify(&format!("Couldn't save bookmarks: {e}"));
+                false
+            }
+        }
+    }
+
+    pub fn open_bookmarks(&mut self, return_state: AppState) {
+        self.bookmarks_screen = BookmarksState {
+            list: self.bookmarks_screen.list,
+            filter: TextInput::default(),
+            selected_index: 0,
+            edit: None,
+            return_state,
+        };
+        self.state = AppState::Bookmarks;
+    }
+
+    /// Indices into `bookmarks` of the ones in the list being shown that match the filter,
+    /// best match first
+    pub fn visible_bookmarks(&self) -> Vec<usize> {
+        let screen = &self.bookmarks_screen;
+        let mut scored: Vec<(i64, usize)> = self
+            .bookmarks
+            .in_list(screen.list)
+            .into_iter()
+            .filter_map(|index| {
+                let text = self.bookmarks.entries[index].search_text();
+                let (score, _) = fuzzy_match(screen.filter.as_str(), &text)?;
+                Some((score, index))
+            })
+            .collect();
+        // Stable, so equally good matches stay latest first
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, index)| index).collect()
+    }
+
+    fn selected_bookmark(&self) -> Option<usize> {
+        self.visible_bookmarks()
+            .get(self.bookmarks_screen.selected_index)
+            .copied()
+    }
+
+    fn perform_on_bookmarks(&mut self, action: Action) {
+        if self.bookmarks_screen.edit.is_some() {
+            match action {
+                Action::Close => self.bookmarks_screen.edit = None,
+                Action::Confirm => self.finish_bookmark_edit(),
+                _ => {}
+            }
+            return;
+        }
+
+        match action {
+            Action::Close => self.state = self.bookmarks_screen.return_state,
+            Action::ScrollUp | Action::ScrollDown => {
+                let total_rows = self.visible_bookmarks().len();
+                let scroll_direction = match action {
+                    Action::ScrollUp => ScrollDirection::UP,
+                    _ => ScrollDirection::DOWN,
+                };
+                self.bookmarks_screen.scroll(scroll_direction, total_rows);
+            }
+            Action::PreviousChoice | Action::NextChoice => {
+                let screen = &mut self.bookmarks_screen;
+                screen.list = screen.list.other();
+                screen.selected_index = 0;
+            }
+            Action::Confirm => {
+                if let Some(index) = self.selected_bookmark() {
+                    self.open_bookmark(self.bookmarks.entries[index].clone());
+                }
+            }
+            Action::DeleteBookmark => {
+                if let Some(index) = self.selected_bookmark() {
+                    let bookmark = self.bookmarks.entries.remove(index);
+                    if self.save_bookmarks() {
+                        self.notify(&format!("Removed {}", bookmark.title));
+                    }
+                    let total_rows = self.visible_bookmarks().len();
+                    let screen = &mut self.bookmarks_screen;
+                    screen.selected_index = screen.selected_index.min(total_rows.saturating_sub(1));
+                }
+            }
+            Action::EditBookmarkNote => self.start_bookmark_edit(BookmarkField::Note),
+            Action::EditBookmarkTags => self.start_bookmark_edit(BookmarkField::Tags),
+            _ => {}
+        }
+    }
+
+    fn start_bookmark_edit(&mut self, field: BookmarkField) {
+        let Some(entry) = self.selected_bookmark() else {
+            return;
+        };
+        let bookmark = &self.bookmarks.entries[entry];
+        let input = match field {
+            BookmarkField::Note => bookmark.note.clone(),
+            BookmarkField::Tags => bookmark.tags.join(", "),
+        };
+        self.bookmarks_screen.edit = Some(BookmarkEdit {
+            field,
+            entry,
+            input: TextInput::new(input),
+        });
+    }
+
+    fn finish_bookmark_edit(&mut self) {
+        let Some(edit) = self.bookmarks_screen.edit.take() else {
+            return;
+        };
+        let Some(bookmark) = self.bookmarks.entries.get_mut(edit.entry) else {
+            return;
+        };
+        match edit.field {
+            BookmarkField::Note => bookmark.note = edit.input.as_str().trim().to_string(),
+            BookmarkField::Tags => {
+                let mut tags: Vec<String> = Vec::new();
+                for tag in edit.input.as_str().split(',') {
+                    let tag = tag.trim().trim_start_matches('#').to_string();
+                    if !tag.is_empty() && !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                bookmark.tags = tags;
+            }
+        }
+        self.save_bookmarks();
+    }
+
+    /// Opens a bookmarked article where it was when it was bookmarked
+    pub fn open_bookmark(&mut self, bookmark: Bookmark) {
+        self.article.history.clear();
+        self.open_article_at(bookmark.title, bookmark.language, Some(bookmark.scroll));
+    }
+
+    fn run_selected_command(&mut self) {
+        let matches = self.command_palette.matches();
+        self.state = self.command_palette.return_state;
+        if let Some((command, _)) = matches.get(self.command_palette.selected_index) {
+            command.action_clone()(self);
+        }
+    }
+
+    pub fn open_settings(&mut self, return_state: AppState) {
+        self.settings = SettingsState {
+            selected_index: 0,
+            input: TextInput::default(),
+            is_editing: false,
+            message: None,
+            return_state,
+        };
+        self.state = AppState::Settings;
+    }
+
+    /// Enter on the settings screen: edits the selected setting, or saves or leaves
+    pub fn activate_setting(&mut self) {
+        match self.settings.selected_setting() {
+            Some(setting) if self.settings.is_editing => {
+                let mut config = self.config.clone();
+                match setting.set(&mut config, self.settings.input.as_str()) {
+                    Ok(()) => self.change_config(setting, config),
+                    Err(e) => self.settings.message = Some(e),
+                }
+                self.settings.is_editing = false;
+            }
+            Some(setting) => {
+                self.settings.input.set(setting.value(&self.config));
+                self.settings.is_editing = true;
+                self.settings.message = None;
+            }
+            None if self.settings.selected_index == SettingsState::SAVE_ROW => {
+                let config = self
+                    .config
+                    .with_file_values(&self.file_config, &self.overridden_settings);
+                self.settings.message = Some(match config.save(&self.config_path) {
+                    Ok(()) => format!("Saved to {}", self.config_path.display()),
+                    Err(e) => format!("Couldn't save: {e}"),
+                });
+            }
+            None => self.close_settings(),
+        }
+    }
+
+    /// Left and right on the settings screen step through the choices for the selected setting
+    pub fn cycle_setting(&mut self, forwards: bool) {
+        if let Some(setting) = self.settings.selected_setting() {
+            let mut config = self.config.clone();
+            setting.cycle(&mut config, forwards);
+            self.change_config(setting, config);
+        }
+    }
+
+    /// A setting changed while wik runs is saved as it is now, even if it was given on the
+    /// command line
+    fn keep_setting(&mut self, setting: Setting) {
+        self.overridden_settings
+            .retain(|overridden| *overridden != setting);
+    }
+
+    pub fn close_settings(&mut self) {
+        if self.settings.is_editing {
+            self.settings.is_editing = false;
+        } else {
+            self.state = self.settings.return_state;
+        }
+    }
+
+    /// Switches to `config`, where `setting` was changed, if it is valid. Otherwise keeps the
+    /// current one and says why.
+    fn change_config(&mut self, setting: Setting, config: Config) {
+        match config.validate() {
+            Ok(()) => {
+                let needs_restart = config.cache_dir != self.config.cache_dir;
+                self.config = config;
+                self.keep_setting(setting);
+                self.apply_config();
+                self.settings.message = Some(String::from(match needs_restart {
+                    true => "The new cache directory is used from the next start",
+                    false => "Changed for this session, save to keep it",
+                }));
+            }
+            Err(errors) => self.settings.message = Some(errors.join("; ")),
+        }
+    }
+
+    pub fn view_selected_article(&mut self) {
+        if let Some(title) = self.search.selected_search_result_title() {
+            self.article.history.clear();
+            self.open_article(title);
+        } else {
+            self.state = AppState::SearchMenu;
+        }
+    }
+
+    /// Opens `title`, where it was left if it has been read before
+    pub fn open_article(&mut self, title: String) {
+        self.open_article_at(title, self.language.clone(), None);
+    }
+
+    /// Opens `title` from the `language` wiki, scrolled down to line `scroll`, or to where it
+    /// was left without one
+    pub fn open_article_at(&mut self, title: String, language: String, scroll: Option<usize>) {
+        self.remember_positions();
+        let scroll = scroll.unwrap_or_else(|| {
+            let remembered = self.reading_positions.get(&language, &title);
+            if remembered.is_some() {
+                self.notify("Picking up where you left off");
+            }
+            remembered.unwrap_or(0)
+        });
+        self.state = AppState::Article;
+        self.article.article_name = title;
+        self.article.show_hidden_sections = false;
+        self.article.scroll = scroll;
+        self.article.selected_link = None;
+        self.article.pending_section = None;
+        self.article.language = language;
+        self.load_article(&self.article);
+    }
+
+    /// Starts loading the article named in `article` into it, in the background
+    fn load_article(&self, article: &ArticleState) {
+        wikipedia::load_article_to_app(
+            article.article_name.clone(),
+            article.language.clone(),
+            shared_copy(&article.is_loading_article),
+            shared_copy(&article.article_spans),
+            shared_copy(&self.cache),
+            shared_copy(&self.images),
+            shared_copy(&article.load_info),
+        );
+    }
+
+    pub fn total_tabs(&self) -> usize {
+        self.tabs.len() + 1
+    }
+
+    /// The titles of every open tab, in order
+    pub fn tab_titles(&self) -> Vec<&str> {
+        let mut titles: Vec<&str> = self
+            .tabs
+            .iter()
+            .map(|tab| tab.article.article_name.as_str())
+            .collect();
+        titles.insert(self.active_tab, &self.article.article_name);
+        titles
+    }
+
+    pub fn switch_tab(&mut self, index: usize) {
+        if index == self.active_tab || index >= self.total_tabs() {
+            return;
+        }
+        // `tabs` skips the current tab, so the positions on either side of it are off by one
+        let (tabs_index, insert_at) = match index > self.active_tab {
+            true => (index - 1, self.active_tab),
+            false => (index, self.active_tab - 1),
+        };
+        // A split stays with its tab
+        let tab = self.tabs.remove(tabs_index);
+        let previous_tab = BackgroundTab {
+            article: std::mem::replace(&mut self.article, tab.article),
+            split: std::mem::replace(&mut self.split, tab.split),
+        };
+        self.tabs.insert(insert_at, previous_tab);
+        self.active_tab = index;
+        self.article.selected_link = None;
+    }
+
+    /// Closes the current tab and both its panes, going back to searching once the last one is
+    /// closed
+    pub fn close_tab(&mut self) {
+        self.remember_positions();
+        if self.tabs.is_empty() {
+            self.article = ArticleState::new(String::new(), self.language.clone());
+            self.split = None;
+            self.state = AppState::Search;
+            return;
+        }
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+        let tab = self.tabs.remove(self.active_tab);
+        self.article = tab.article;
+        self.split = tab.split;
+        self.state = AppState::Article;
+    }
+
+    /// Opens the selected link in a tab next to this one, without switching to it
+    pub fn open_selected_link_in_new_tab(&mut self) {
+        let (spans, _) = self.article_layout();
+        let Some(link) = self
+            .article
+            .selected_link
+            .and_then(|index| spans.get(index))
+            .and_then(|span| span.link.clone())
+        else {
+            return;
+        };
+        if link.kind != LinkKind::Internal {
+            return self.follow_selected_link();
+        }
+
+        let mut tab = ArticleState::new(link.target.clone(), self.article.language.clone());
+        if link.section.is_none() {
+            tab.scroll = self
+                .reading_positions
+                .get(&tab.language, &tab.article_name)
+                .unwrap_or(0);
+        }
+        tab.pending_section = link.section;
+        self.load_article(&tab);
+        self.tabs.insert(
+            self.active_tab,
+            BackgroundTab {
+                article: tab,
+                split: None,
+            },
+        );
+        self.notify(&format!("Opened {} in a new tab", link.target));
+    }
+
+    /// Shows the current article a second time, in a new pane that gets the focus
+    pub fn split_view(&mut self, direction: SplitDirection) {
+        if let Some(split) = &mut self.split {
+            split.direction = direction;
+            return;
+        }
+        let mut pane = ArticleState::new(
+            self.article.article_name.clone(),
+            self.article.language.clone(),
+        );
+        pane.scroll = self.article.scroll;
+        pane.view_size.set(self.article.view_size.get());
+        self.load_article(&pane);
+        let other = std::mem::replace(&mut self.article, pane);
+        self.split = Some(SplitState {
+            direction,
+            other,
+            focus_second: true,
+            sync_scroll: false,
+            synced_scroll: None,
+        });
+    }
+
+    pub fn switch_pane(&mut self) {
+        if let Some(split) = &mut self.split {
+            std::mem::swap(&mut self.article, &mut split.other);
+            split.focus_second = !split.focus_second;
+            split.synced_scroll = None;
+        }
+    }
+
+    /// Closes the focused pane, leaving the other one on its own
+    pub fn close_pane(&mut self) {
+        self.remember_positions();
+        if let Some(split) = self.split.take() {
+            self.article = split.other;
+        }
+    }
+
+    pub fn toggle_sync_scroll(&mut self) {
+        let Some(split) = &mut self.split else {
+            return;
+        };
+        split.sync_scroll = !split.sync_scroll;
+        split.synced_scroll = None;
+        let message = match split.sync_scroll {
+            true => "Panes scroll together by section",
+            false => "Panes scroll separately",
+        };
+        self.notify(message);
+    }
+
+    /// Scrolls the other pane to the section the focused pane is reading, matching headings by
+    /// name and falling back to their position in the article
+    fn sync_split_scroll(&mut self) {
+        let Some(split) = &self.split else {
+            return;
+        };
+        if !split.sync_scroll
+            || split.synced_scroll == Some(self.article.scroll)
+            || self.article.currently_loading()
+            || split.other.currently_loading()
+        {
+            return;
+        }
+
+        let (spans, lines) = self.article_layout();
+        // Every heading down to the top line of the view, the last one being the section read
+        let mut read: Vec<usize> = lines
+            .iter()
+            .take(self.article.scroll + 1)
+            .flat_map(|line| line.fragments.iter().map(|fragment| fragment.span_index))
+            .filter(|&index| spans[index].is_heading)
+            .collect();
+        read.dedup();
+
+        let (other_spans, other_lines) = split.other.layout(&self.section_filter);
+        let other_headings: Vec<usize> = (0..other_spans.len())
+            .filter(|&index| other_spans[index].is_heading)
+            .collect();
+        let target = match read.last() {
+            None => Some(0),
+            Some(&heading) => other_headings
+                .iter()
+                .find(|&&index| {
+                    other_spans[index].text.to_lowercase() == spans[heading].text.to_lowercase()
+                })
+                .or(other_headings.get(read.len() - 1))
+                .and_then(|&index| line_of_span(&other_lines, index)),
+        };
+
+        let scroll = self.article.scroll;
+        if let Some(split) = &mut self.split {
+            if let Some(line) = target {
+                let (_, height) = split.other.view_size.get();
+                split.other.scroll = line.min(other_lines.len().saturating_sub(height));
+            }
+            split.synced_scroll = Some(scroll);
+        }
+    }
+
+    /// Saves the open tabs and their splits so the next run can resume them. A run that never
+    /// opened an article keeps the last session's tabs.
+    pub fn save_session(&self) -> Result<(), Box<dyn Error>> {
+        let mut active_tab = self.active_tab;
+        let mut tabs: Vec<SavedTab> = Vec::new();
+        let all_tabs = self.tabs[..self.active_tab]
+            .iter()
+            .map(BackgroundTab::panes)
+            .chain(std::iter::once((&self.article, self.split.as_ref())))
+            .chain(
+                self.tabs[self.active_tab..]
+                    .iter()
+                    .map(BackgroundTab::panes),
+            );
+        for (index, (tab, split)) in all_tabs.enumerate() {
+            if !tab.is_loaded() {
+                if index < self.active_tab {
+                    active_tab -= 1;
+                }
+                continue;
+            }
+            tabs.push(SavedTab {
+                title: tab.article_name.clone(),
+                language: tab.language.clone(),
+                scroll: tab.scroll,
+                history: tab.history.clone(),
+                split: split
+                    .filter(|split| split.other.is_loaded())
+                    .map(|split| SavedSplit {
+                        title: split.other.article_name.clone(),
+                        language: split.other.language.clone(),
+                        scroll: split.other.scroll,
+                        history: split.other.history.clone(),
+                        vertical: split.direction == SplitDirection::Vertical,
+                        focus_second: split.focus_second,
+                        sync_scroll: split.sync_scroll,
+                    }),
+            });
+        }
+        // A session that can't be read is replaced
+        let previous_session = Session::load().ok().flatten().unwrap_or_default();
+        if tabs.is_empty() {
+            tabs = previous_session.tabs;
+            active_tab = previous_session.active_tab;
+        }
+        Session {
+            active_tab: active_tab.min(tabs.len().saturating_sub(1)),
+            tabs,
+        }
+        .save()
+    }
+
+    /// Reopens the tabs that were open when wik last quit
+    pub fn resume_session(&mut self) {
+        match self.restore_session() {
+            Ok(true) => {}
+            Ok(false) => self.notify("There is no saved session"),
+            Err(e) => self.notify(&format!("Couldn't load the last session: {e}")),
+        }
+    }
+
+    /// Replaces the open tabs with the last session's, if it had any
+    fn restore_session(&mut self) -> Result<bool, Box<dyn Error>> {
+        let Some(session) = Session::load()?.filter(|session| !session.tabs.is_empty()) else {
+            return Ok(false);
+        };
+
+        self.remember_positions();
+        let restore = |title: String, language: String, scroll: usize, history| {
+            let mut article = ArticleState::new(title, language);
+            article.scroll = scroll;
+            article.history = history;
+            self.load_article(&article);
+            article
+        };
+        let mut tabs: Vec<BackgroundTab> = session
+            .tabs
+            .into_iter()
+            .map(|saved_tab| BackgroundTab {
+                article: restore(
+                    saved_tab.title,
+                    saved_tab.language,
+                    saved_tab.scroll,
+                    saved_tab.history,
+                ),
+                split: saved_tab.split.map(|saved_split| SplitState {
+                    direction: match saved_split.vertical {
+                        true => SplitDirection::Vertical,
+                        false => SplitDirection::Horizontal,
+                    },
+                    other: restore(
+                        saved_split.title,
+                        saved_split.language,
+                        saved_split.scroll,
+                        saved_split.history,
+                    ),
+                    focus_second: saved_split.focus_second,
+                    sync_scroll: saved_split.sync_scroll,
+                    synced_scroll: None,
+                }),
+            })
+            .collect();
+        self.active_tab = session.active_tab.min(tabs.len() - 1);
+        let tab = tabs.remove(self.active_tab);
+        self.article = tab.article;
+        self.split = tab.split;
+        self.tabs = tabs;
+        self.state = AppState::Article;
+        Ok(true)
+    }
+
+    /// Remembers how far down every open article has been read, and saves that
+    pub fn save_reading_positions(&mut self) -> Result<(), Box<dyn Error>> {
+        self.remember_positions();
+        self.positions_saved_at = Instant::now();
+        self.reading_positions.save()
+    }
+
+    /// Called before an article is closed or replaced, so its position isn't lost. It's only
+    /// written to disk by `save_reading_positions`.
+    fn remember_positions(&mut self) {
+        // The article being read goes last, so it ends up as the latest
+        let open_articles = self
+            .tabs
+            .iter()
+            .flat_map(|tab| {
+                std::iter::once(&tab.article).chain(tab.split.iter().map(|split| &split.other))
+            })
+            .chain(self.split.iter().map(|split| &split.other))
+            .chain(std::iter::once(&self.article));
+        for article in open_articles.filter(|article| article.is_loaded()) {
+            self.reading_positions
+                .record(&article.language, &article.article_name, article.scroll);
+        }
+    }
+
+    /// Called once per frame, for work that has to wait on background loading
+    pub fn update(&mut self) {
+        if let Some((_, shown_at)) = &self.notification {
+            if shown_at.elapsed() > NOTIFICATION_DURATION {
+                self.notification = None;
+            }
+        }
+        if self.article.pending_section.is_some() && !self.article.currently_loading() {
+            if let Some(section_id) = self.article.pending_section.take() {
+                self.scroll_to_section(&section_id);
+            }
+        }
+        self.sync_split_scroll();
+        if self.positions_saved_at.elapsed() > POSITIONS_SAVE_INTERVAL {
+            if let Err(e) = self.save_reading_positions() {
+                self.notify(&format!("Couldn't save reading positions: {e}"));
+            }
+        }
+        if self.state == AppState::Search {
+            self.load_more_results();
+        }
+        self.update_autocomplete();
+        self.update_preview();
+    }
+
+    /// Shows `message` in the status bar for a few seconds
+    pub fn notify(&mut self, message: &str) {
+        self.notification = Some((message.to_string(), Instant::now()));
+    }
+
+    fn article_layout(&self) -> (Vec<FormattedSpan>, Vec<WrappedLine>) {
+        self.article.layout(&self.section_filter)
+    }
+
+    fn max_article_scroll(&self, lines: &[WrappedLine]) -> usize {
+        let (_, height) = self.article.view_size.get();
+        lines.len().saturating_sub(height)
+    }
+
+    pub fn scroll_article(&mut self, scroll_direction: ScrollDirection, amount: usize) {
+        let (_, lines) = self.article_layout();
+        self.article.scroll = match scroll_direction {
+            ScrollDirection::UP => self.article.scroll.saturating_sub(amount),
+            ScrollDirection::DOWN => self.article.scroll.saturating_add(amount),
+        }
+        .min(self.max_article_scroll(&lines));
+    }
+
+    pub fn scroll_article_by_page(&mut self, scroll_direction: ScrollDirection) {
+        let (_, height) = self.article.view_size.get();
+        self.scroll_article(scroll_direction, height.saturating_sub(1).max(1));
+    }
+
+    /// Scrolls so the heading with the given id is at the top of the view
+    pub fn scroll_to_section(&mut self, section_id: &str) -> bool {
+        let (spans, lines) = self.article_layout();
+        let heading_index = spans.iter().position(|span| {
+            span.section_id.as_deref() == Some(section_id)
+                || (span.is_heading && span.text.replace(' ', "_") == section_id)
+        });
+        match heading_index.and_then(|index| line_of_span(&lines, index)) {
+            Some(line) => {
+                self.article.scroll = line.min(self.max_article_scroll(&lines));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the link selection to the next or previous link, scrolling to keep it in view
+    pub fn select_link(&mut self, scroll_direction: ScrollDirection) {
+        let (spans, lines) = self.article_layout();
+        let (_, height) = self.article.view_size.get();
+        let link_starts: Vec<usize> = (0..spans.len())
+            .filter(|&index| {
+                spans[index].link.is_some()
+                    && (index == 0 || spans[index - 1].link != spans[index].link)
+            })
+            .collect();
+
+        let visible_lines = self.article.scroll..self.article.scroll + height;
+        let selection_is_visible = self
+            .article
+            .selected_link
+            .and_then(|index| line_of_span(&lines, index))
+            .is_some_and(|line| visible_lines.contains(&line));
+
+        let next_link = if selection_is_visible {
+            let current = self.article.selected_link.unwrap_or(0);
+            match scroll_direction {
+                ScrollDirection::DOWN => link_starts.iter().find(|&&index| index > current),
+                ScrollDirection::UP => link_starts.iter().rev().find(|&&index| index < current),
+            }
+        } else {
+            // Start from whatever is on screen rather than from a link scrolled out of view
+            let on_screen = |index: &&usize| {
+                line_of_span(&lines, **index).is_some_and(|line| visible_lines.contains(&line))
+            };
+            match scroll_direction {
+                ScrollDirection::DOWN => link_starts.iter().find(on_screen),
+                ScrollDirection::UP => link_starts.iter().rev().find(on_screen),
+            }
+        };
+
+        if let Some(&index) = next_link {
+            self.article.selected_link = Some(index);
+            if let Some(line) = line_of_span(&lines, index) {
+                if line < self.article.scroll {
+                    self.article.scroll = line;
+                } else if line >= self.article.scroll + height {
+                    self.article.scroll = (line + 1).saturating_sub(height);
+                }
+            }
+        }
+    }
+
+    pub fn follow_selected_link(&mut self) {
+        let (spans, _) = self.article_layout();
+        let link = match self
+            .article
+            .selected_link
+            .and_then(|index| spans.get(index))
+        {
+            Some(span) => match &span.link {
+                Some(link) => link.clone(),
+                None => return,
+            },
+            None => return,
+        };
+
+        match link.kind {
+            LinkKind::Internal => {
+                if same_title(&link.target, &self.article.article_name) {
+                    if let Some(section_id) = link.section {
+                        self.scroll_to_section(&section_id);
+                    }
+                } else {
+                    self.article.history.push(HistoryEntry {
+                        title: self.article.article_name.clone(),
+                        language: self.article.language.clone(),
+                        scroll: self.article.scroll,
+                    });
+                    // A link to a section goes to the section, not to where the article was left
+                    let language = self.article.language.clone();
+                    match link.section {
+                        Some(section_id) => {
+                            self.open_article_at(link.target, language, Some(0));
+                            self.article.pending_section = Some(section_id);
+                        }
+                        None => self.open_article_at(link.target, language, None),
+                    }
+                }
+            }
+            LinkKind::Anchor => {
+                self.scroll_to_section(&link.target);
+            }
+            LinkKind::External | LinkKind::Interwiki => {
+                webbrowser::open(&link.target).unwrap_or(());
+            }
+            // Red links point at pages that don't exist, there's nothing to open
+            LinkKind::Red => {}
+        }
+    }
+
+    pub fn go_back(&mut self) {
+        if let Some(entry) = self.article.history.pop() {
+            self.open_article_at(entry.title, entry.language, Some(entry.scroll));
+        }
+    }
+}
+
+fn line_of_span(lines: &[WrappedLine], span_index: usize) -> Option<usize> {
+    lines.iter().position(|line| line.contains_span(span_index))
+}
+
+fn same_title(first: &str, second: &str) -> bool {
+    first.replace('_', " ") == second.replace('_', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyCode;
+
+    /// An app whose searches never start, so nothing goes to the network or to disk
+    fn offline_app() -> App {
+        let app = App::default();
+        *app.search.is_loading_query.lock().unwrap() = true;
+        app
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn searches_for_the_suggested_spelling() {
+        let mut app = offline_app();
+        app.state = AppState::Search;
+        app.search.input.set(String::from("Tokio"));
+        app.search_for_suggestion();
+        assert_eq!(app.search.input.as_str(), "Tokio");
+
+        app.search.paging.lock().unwrap().suggestion = Some(String::from("Tōkyō"));
+        app.search.text_box_is_highlighted = true;
+        app.search_for_suggestion();
+        assert_eq!(app.search.input.as_str(), "Tōkyō");
+        assert_eq!(app.search.input.split_at_cursor(), ("Tōkyō", None, ""));
+        assert!(!app.search.text_box_is_highlighted);
+    }
+
+    #[test]
+    fn searches_from_the_title_screen() {
+        let mut app = offline_app();
+        app.search_from_title(String::new());
+        assert_eq!(app.state, AppState::Search);
+        assert_eq!(app.search.input.as_str(), "");
+
+        app.state = AppState::Title;
+        app.search.selected_index = 3;
+        app.search_from_title(String::from("日本"));
+        assert_eq!(app.state, AppState::Search);
+        assert_eq!(app.search.input.as_str(), "日本");
+        assert_eq!(app.search.selected_index, 0);
+    }
+
+    #[test]
+    fn runs_the_advanced_search_form() {
+        let mut app = offline_app();
+        app.state = AppState::Search;
+        app.perform(Action::AdvancedSearch);
+        app.perform(Action::Confirm);
+        assert!(app.search.advanced.is_open);
+
+        type_text(&mut app, "Zoë");
+        app.perform(Action::ScrollDown);
+        type_text(&mut app, "Big Apple");
+        // Coming back to a field carries on at its end
+        app.perform(Action::ScrollUp);
+        type_text(&mut app, "s");
+        // The namespace row is picked from rather than typed into
+        app.perform(Action::ScrollUp);
+        assert_eq!(
+            app.search.advanced.selected_row,
+            AdvancedSearchState::NAMESPACE_ROW
+        );
+        type_text(&mut app, "x");
+        app.perform(Action::NextChoice);
+
+        app.perform(Action::Confirm);
+        assert!(!app.search.advanced.is_open);
+        assert_eq!(app.search.input.as_str(), "Zoës intitle:\"Big Apple\"");
+        assert_eq!(app.search.options.namespace_label(), "Talk");
+    }
+
+    #[test]
+    fn filters_and_edits_bookmarks() {
+        let mut app = offline_app();
+        let bookmark = |title: &str, list: BookmarkList| {
+            Bookmark::new(String::from(title), String::from("en"), 0, list)
+        };
+        let mut kyoto = bookmark("Kyoto", BookmarkList::Bookmarks);
+        kyoto.note = String::from("京都へ");
+        kyoto.tags = vec![String::from("japan")];
+        app.bookmarks.entries = vec![
+            bookmark("Paris", BookmarkList::Bookmarks),
+            bookmark("Nara", BookmarkList::ReadLater),
+            kyoto,
+        ];
+        let titles = |app: &App| -> Vec<String> {
+            app.visible_bookmarks()
+                .into_iter()
+                .map(|index| app.bookmarks.entries[index].title.clone())
+                .collect()
+        };
+
+        app.open_bookmarks(AppState::Title);
+        assert_eq!(titles(&app), ["Paris", "Kyoto"]);
+        app.perform(Action::NextChoice);
+        assert_eq!(titles(&app), ["Nara"]);
+        app.perform(Action::PreviousChoice);
+
+        // The filter matches tags and notes as well as titles
+        type_text(&mut app, "#jap");
+        assert_eq!(titles(&app), ["Kyoto"]);
+        app.bookmarks_screen.filter.set(String::from("京都"));
+        assert_eq!(titles(&app), ["Kyoto"]);
+
+        // Editing starts from what is there, and leaving it keeps the bookmark as it was
+        app.perform(Action::EditBookmarkTags);
+        let edit = app.bookmarks_screen.edit.as_ref().unwrap();
+        assert_eq!(edit.input.as_str(), "japan");
+        type_text(&mut app, ", temples");
+        let edit = app.bookmarks_screen.edit.as_ref().unwrap();
+        assert_eq!(edit.input.as_str(), "japan, temples");
+        assert_eq!(app.bookmarks_screen.filter.as_str(), "京都");
+        app.perform(Action::Close);
+        assert!(app.bookmarks_screen.edit.is_none());
+        assert_eq!(app.bookmarks.entries[2].tags, ["japan"]);
+        assert_eq!(app.state, AppState::Bookmarks);
+    }
+
+    #[test]
+    fn changes_a_setting_only_to_a_valid_value() {
+        let mut app = offline_app();
+        app.open_settings(AppState::Title);
+        while app.settings.selected_setting() != Some(Setting::SearchResultLimit) {
+            app.perform(Action::ScrollDown);
+        }
+        let edit_to = |app: &mut App, text: &str| {
+            app.perform(Action::Confirm);
+            app.settings.input.set(String::new());
+            type_text(app, text);
+            app.perform(Action::Confirm);
+        };
+
+        // Editing starts from the current value
+        app.perform(Action::Confirm);
+        assert!(app.settings.is_editing);
+        assert_eq!(app.settings.input.as_str(), "25");
+        app.perform(Action::Close);
+        assert!(!app.settings.is_editing);
+        assert_eq!(app.state, AppState::Settings);
+
+        edit_to(&mut app, "50");
+        assert_eq!(app.config.search_result_limit, 50);
+        assert!(!app.settings.is_editing);
+
+        edit_to(&mut app, "lots");
+        assert_eq!(
+            app.settings.message.as_deref(),
+            Some("\"lots\" is not a number of results")
+        );
+        edit_to(&mut app, "0");
+        assert!(app
+            .settings
+            .message
+            .as_ref()
+            .is_some_and(|message| message.starts_with("search_result_limit:")));
+        assert_eq!(app.config.search_result_limit, 50);
+
+        app.perform(Action::Close);
+        assert_eq!(app.state, AppState::Title);
+    }
+
+    #[test]
+    fn saves_settings_but_not_command_line_options() {
+        let dir = std::env::temp_dir().join(format!("wik-config-test-{}", std::process::id()));
+        let mut app = offline_app();
+        app.config_path = dir.join("config.toml");
+        app.config.theme = String::from("light");
+        app.config.language = String::from("de");
+        app.overridden_settings = vec![Setting::Theme, Setting::Language];
+
+        app.open_settings(AppState::Title);
+        assert_eq!(app.settings.selected_setting(), Some(Setting::Theme));
+        app.perform(Action::NextChoice);
+        let theme = app.config.theme.clone();
+        app.settings.selected_index = SettingsState::SAVE_ROW;
+        app.perform(Action::Confirm);
+
+        let saved = Config::load(&app.config_path).unwrap();
+        assert_eq!(saved.theme, theme);
+        assert_eq!(saved.language, Config::default().language);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recalls_past_searches_into_an_empty_search_box() {
+        let mut app = offline_app();
+        app.search_history.record("Kyoto");
+        app.search_history.record("Nara");
+        app.state = AppState::Search;
+        app.search.text_box_is_highlighted = true;
+
+        app.perform(Action::ScrollUp);
+        assert_eq!(app.search.input.as_str(), "Nara");
+        app.perform(Action::ScrollUp);
+        app.perform(Action::ScrollUp);
+        assert_eq!(app.search.input.as_str(), "Kyoto");
+        app.perform(Action::ScrollDown);
+        app.perform(Action::ScrollDown);
+        assert_eq!(app.search.input.as_str(), "");
+
+        // Once a recalled query is changed, Up and Down leave it alone
+        app.perform(Action::ScrollUp);
+        type_text(&mut app, " city");
+        app.perform(Action::ScrollUp);
+        assert_eq!(app.search.input.as_str(), "Nara city");
+
+        app.search.input.set(String::new());
+        app.search.history_recall = None;
+        app.config.search_history = false;
+        app.perform(Action::ScrollUp);
+        assert_eq!(app.search.input.as_str(), "");
+    }
+
+    #[test]
+    fn pages_through_results_without_wrapping_early() {
+        let mut app = App::default();
+        let search = &mut app.search;
+        *search.results.lock().unwrap() = serde_json::from_str(
+            r#"[
+                {"title": "Kyoto", "pageid": 1, "snippet": ""},
+                {"title": "Kyoto Station", "pageid": 2, "snippet": ""},
+                {"title": "Kyoto University", "pageid": 3, "snippet": ""}
+            ]"#,
+        )
+        .unwrap();
+        *search.paging.lock().unwrap() = SearchPaging {
+            query: String::from("Kyoto"),
+            next_offset: Some(3),
+            ..SearchPaging::default()
+        };
+
+        // More results are coming, so neither end wraps around
+        search.scroll_results(ScrollDirection::UP);
+        assert_eq!(search.selected_index, 0);
+        assert_eq!(search.next_page(), None);
+        for _ in 0..4 {
+            search.scroll_results(ScrollDirection::DOWN);
+        }
+        assert_eq!(search.selected_index, 2);
+        assert_eq!(search.next_page(), Some((String::from("Kyoto"), 3)));
+        search.paging.lock().unwrap().is_loading_more = true;
+        assert_eq!(search.next_page(), None);
+
+        // On the last page the list wraps
+        *search.paging.lock().unwrap() = SearchPaging {
+            query: String::from("Kyoto"),
+            ..SearchPaging::default()
+        };
+        assert_eq!(search.next_page(), None);
+        search.scroll_results(ScrollDirection::DOWN);
+        assert_eq!(search.selected_index, 0);
+        search.scroll_results(ScrollDirection::UP);
+        assert_eq!(search.selected_index, 2);
+    }
+
+    #[test]
+    fn keeps_a_split_with_its_tab() {
+        let mut app = offline_app();
+        let article = |title: &str| ArticleState::new(title.to_string(), String::from("en"));
+        app.article = article("Left");
+        app.split = Some(SplitState {
+            direction: SplitDirection::Vertical,
+            other: article("Right"),
+            focus_second: false,
+            sync_scroll: false,
+            synced_scroll: None,
+        });
+        app.tabs.push(BackgroundTab {
+            article: article("Other tab"),
+            split: None,
+        });
+
+        app.switch_tab(1);
+        assert_eq!(app.article.article_name, "Other tab");
+        assert!(app.split.is_none());
+        app.switch_tab(0);
+        assert_eq!(app.article.article_name, "Left");
+        let split = app.split.as_ref().unwrap();
+        assert_eq!(split.other.article_name, "Right");
+
+        app.close_tab();
+        assert_eq!(app.article.article_name, "Other tab");
+        assert!(app.split.is_none());
+        assert!(app.tabs.is_empty());
+    }
+
+    #[test]
+    fn hides_sections_for_the_article_s_own_wiki() {
+        let mut app = offline_app();
+        app.language = String::from("en");
+        app.article = ArticleState::new(String::from("Kyoto"), String::from("de"));
+        *app.article.article_spans.lock().unwrap() = vec![
+            FormattedSpan {
+                text: String::from("Kyoto ist eine Stadt."),
+                ..FormattedSpan::default()
+            },
+            FormattedSpan {
+                text: String::from("Weblinks"),
+                is_heading: true,
+                heading_level: 2,
+                ..FormattedSpan::default()
+            },
+            FormattedSpan {
+                text: String::from("kyoto.jp"),
+                ..FormattedSpan::default()
+            },
+        ];
+
+        let (spans, _) = app.article_layout();
+        let texts: Vec<&str> = spans.iter().map(|span| span.text.as_str()).collect();
+        assert_eq!(texts, ["Kyoto ist eine Stadt."]);
+    }
+
+    #[test]
+    fn command_palette_lists_every_screen() {
+        let lists = |app: &App, action: Action| {
+            app.command_palette
+                .commands
+                .iter()
+                .any(|command| command.label().starts_with(action.description()))
+        };
+        let mut app = offline_app();
+        app.state = AppState::Search;
+        app.open_command_palette();
+        assert!(lists(&app, Action::CycleSort));
+        assert!(lists(&app, Action::SearchHistory));
+        // Without an article open, there's nothing for article commands to act on
+        assert!(!lists(&app, Action::NextLink));
+
+        app.article = ArticleState::new(String::from("Kyoto"), String::from("en"));
+        *app.article.is_loading_article.lock().unwrap() = true;
+        app.state = AppState::Search;
+        app.open_command_palette();
+        assert!(lists(&app, Action::NextLink));
+        assert!(lists(&app, Action::CycleSort));
+    }
 }
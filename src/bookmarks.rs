@@ -0,0 +1,167 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::session;
+use crate::utils::unix_now;
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+/// Bookmarks are kept for good, the reading list is for articles to get round to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BookmarkList {
+    Bookmarks,
+    ReadLater,
+}
+
+impl BookmarkList {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BookmarkList::Bookmarks => "Bookmarks",
+            BookmarkList::ReadLater => "Read later",
+        }
+    }
+
+    pub fn other(&self) -> BookmarkList {
+        match self {
+            BookmarkList::Bookmarks => BookmarkList::ReadLater,
+            BookmarkList::ReadLater => BookmarkList::Bookmarks,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub language: String,
+    /// Where the article was scrolled to when it was bookmarked
+    pub scroll: usize,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub list: BookmarkList,
+    /// Seconds since the Unix epoch
+    pub added_at: u64,
+}
+
+impl Bookmark {
+    pub fn new(title: String, language: String, scroll: usize, list: BookmarkList) -> Self {
+        Self {
+            title,
+            language,
+            scroll,
+            note: String::new(),
+            tags: Vec::new(),
+            list,
+            added_at: unix_now(),
+        }
+    }
+
+    /// Everything the bookmarks screen's filter is matched against
+    pub fn search_text(&self) -> String {
+        let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{tag}")).collect();
+        format!("{} {} {}", self.title, tags.join(" "), self.note)
+    }
+}
+
+/// Saved articles from both lists, latest first
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Bookmarks {
+    pub entries: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn path() -> PathBuf {
+        session::data_dir().join(BOOKMARKS_FILE)
+    }
+
+    /// The saved bookmarks, or none if nothing has been saved yet
+    pub fn load() -> Result<Bookmarks, Box<dyn Error>> {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> Result<Bookmarks, Box<dyn Error>> {
+        Ok(session::load_json(path)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        session::save_json(&Self::path(), self)
+    }
+
+    /// Adds `bookmark` first. An article already in the same list is moved up, keeping its note
+    /// and tags, and picks up the new scroll position.
+    pub fn add(&mut self, mut bookmark: Bookmark) {
+        if let Some(index) = self.entries.iter().position(|entry| {
+            entry.list == bookmark.list
+                && entry.title == bookmark.title
+                && entry.language == bookmark.language
+        }) {
+            let existing = self.entries.remove(index);
+            bookmark.note = existing.note;
+            bookmark.tags = existing.tags;
+        }
+        self.entries.insert(0, bookmark);
+    }
+
+    /// Indices into `entries` of the ones in `list`
+    pub fn in_list(&self, list: BookmarkList) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|&index| self.entries[index].list == list)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn keeps_bookmarks_between_runs() {
+        let dir = std::env::temp_dir().join(format!("wik-bookmarks-test-{}", std::process::id()));
+        let path = dir.join(BOOKMARKS_FILE);
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut bookmarks = Bookmarks::load_from(&path).unwrap();
+        assert!(bookmarks.entries.is_empty());
+        let mut kyoto = Bookmark::new(
+            String::from("Kyōto"),
+            String::from("ja"),
+            12,
+            BookmarkList::Bookmarks,
+        );
+        kyoto.note = String::from("寺の一覧");
+        kyoto.tags = vec![String::from("japan")];
+        bookmarks.add(kyoto);
+        bookmarks.add(Bookmark::new(
+            String::from("Nara"),
+            String::from("en"),
+            0,
+            BookmarkList::ReadLater,
+        ));
+        // Adding it again moves it up with its note, at the new position
+        bookmarks.add(Bookmark::new(
+            String::from("Kyōto"),
+            String::from("ja"),
+            40,
+            BookmarkList::Bookmarks,
+        ));
+        session::save_json(&path, &bookmarks).unwrap();
+
+        let loaded = Bookmarks::load_from(&path).unwrap();
+        assert_eq!(loaded.entries, bookmarks.entries);
+        assert_eq!(loaded.entries[0].scroll, 40);
+        assert_eq!(loaded.entries[0].note, "寺の一覧");
+        assert_eq!(loaded.in_list(BookmarkList::ReadLater), [1]);
+
+        fs::write(&path, "{\"entries\": [{\"title\": 1}]}").unwrap();
+        assert!(Bookmarks::load_from(&path).is_err());
+        assert!(Bookmarks::load_from(&path).unwrap().entries.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -18,6 +18,8 @@ pub type FileName = String;
 pub struct CachingSession {
     pub lookup_table: HashMap<Url, FileName>,
     pub session_name: String,
+    /// wik's cache directory, session caches go in its `caches/` folder
+    pub cache_dir: PathBuf,
 }
 
 impl Default for CachingSession {
@@ -25,14 +27,19 @@ impl Default for CachingSession {
         Self {
             lookup_table: HashMap::new(),
             session_name: String::from("session_name"),
+            cache_dir: Self::default_cache_dir(),
         }
     }
 }
 
 impl CachingSession {
-    const WIK_DIR: &str = ".cache/wik/caches/";
-    pub fn new() -> Self {
+    const WIK_DIR: &str = ".cache/wik/";
+    const CACHES_DIR: &str = "caches/";
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
         let mut session = Self::default();
+        if let Some(cache_dir) = cache_dir {
+            session.cache_dir = cache_dir;
+        }
         session.session_name = rand::thread_rng()
             .sample_iter(&Alphanumeric)
             .take(10)
@@ -42,16 +49,16 @@ impl CachingSession {
         session
     }
 
-    pub fn has_url(&self, url: &Url) -> bool {
-        self.lookup_table.contains_key(url)
+    pub fn default_cache_dir() -> PathBuf {
+        home_dir().unwrap().join(Self::WIK_DIR)
     }
 
-    fn cache_dir() -> PathBuf {
-        home_dir().unwrap().join(Self::WIK_DIR)
+    fn caches_dir(&self) -> PathBuf {
+        self.cache_dir.join(Self::CACHES_DIR)
     }
 
     fn session_cache_dir(&self) -> PathBuf {
-        Self::cache_dir().join(format!("{}/", self.session_name))
+        self.caches_dir().join(format!("{}/", self.session_name))
     }
 
     pub fn get_cache_file_path(&self, file_name: &str) -> PathBuf {
@@ -103,9 +110,18 @@ impl CachingSession {
             None => None,
         }
     }
-    pub fn clear_caches() -> io::Result<()> {
-        match fs::remove_dir_all(Self::cache_dir()) {
-            Ok(_) => match fs::create_dir(Self::cache_dir()) {
+    /// Forgets everything cached by this session, so pages are fetched again
+    pub fn clear_session(&mut self) -> io::Result<()> {
+        self.lookup_table.clear();
+        match fs::remove_dir_all(self.session_cache_dir()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn clear_caches(&self) -> io::Result<()> {
+        match fs::remove_dir_all(self.caches_dir()) {
+            Ok(_) => match fs::create_dir(self.caches_dir()) {
                 Ok(_) => Ok(()),
                 Err(e) => {
                     println!("Error creating files!");
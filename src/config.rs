@@ -0,0 +1,723 @@
+use std::{collections::HashMap, error::Error, fs, path::PathBuf};
+
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::images::GraphicsProtocol;
+use crate::keymap::Keymap;
+use crate::styles::Theme;
+use crate::utils::remainder;
+use crate::wikipedia::{self, SectionFilter};
+
+const CONFIG_FILE: &str = ".config/wik/config.toml";
+
+// The search API refuses to return more than this many results at once
+const MAX_SEARCH_RESULT_LIMIT: u16 = 500;
+const MAX_REFRESH_TIME_MILLIS: u64 = 1000;
+
+pub const USAGE: &str = "\
+Usage: wik [options]
+
+Options:
+  --config <path>          Read settings from <path> instead of ~/.config/wik/config.toml
+  --theme <name>           Colour theme, e.g. dark, light, solarized, high-contrast, monochrome
+  --language <code>        Wikipedia language edition, e.g. en, de, fr
+  --search-limit <n>       Number of search results to fetch (1-500)
+  --refresh-ms <n>         Milliseconds between redraws (1-1000)
+  --images <mode>          auto, half-blocks, kitty, iterm or sixel
+  --cache-dir <path>       Where downloaded pages are cached while wik runs
+  --mouse <on|off>         Whether wik takes the mouse; off keeps the terminal's text selection
+  --preview <on|off>       Show a summary of the selected search result next to the results
+  --history <on|off>       Remember searches between runs
+  --reopen <on|off>        Reopen the tabs from last time on start
+  --keymap <preset>        Key bindings to start from: default, vim or emacs
+  -h, --help               Show this message
+  -V, --version            Show the version
+
+Options given on the command line override the config file.";
+
+/// How article images are drawn. `Auto` picks a protocol from the terminal's environment.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageMode {
+    Auto,
+    HalfBlocks,
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+impl ImageMode {
+    const ALL: [ImageMode; 5] = [
+        ImageMode::Auto,
+        ImageMode::HalfBlocks,
+        ImageMode::Kitty,
+        ImageMode::Iterm,
+        ImageMode::Sixel,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageMode::Auto => "auto",
+            ImageMode::HalfBlocks => "half-blocks",
+            ImageMode::Kitty => "kitty",
+            ImageMode::Iterm => "iterm",
+            ImageMode::Sixel => "sixel",
+        }
+    }
+
+    pub fn protocol(&self) -> GraphicsProtocol {
+        match self {
+            ImageMode::Auto => GraphicsProtocol::detect(),
+            ImageMode::HalfBlocks => GraphicsProtocol::HalfBlocks,
+            ImageMode::Kitty => GraphicsProtocol::Kitty,
+            ImageMode::Iterm => GraphicsProtocol::Iterm,
+            ImageMode::Sixel => GraphicsProtocol::Sixel,
+        }
+    }
+
+    fn from_name(name: &str) -> Result<ImageMode, String> {
+        ImageMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == name)
+            .ok_or(format!(
+                "\"{name}\" is not an image mode, use one of: {}",
+                ImageMode::ALL.map(|mode| mode.name()).join(", ")
+            ))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Config {
+    pub theme: String,
+    pub language: String,
+    pub search_result_limit: u16,
+    pub refresh_time_millis: u64,
+    pub images: ImageMode,
+    /// Capture the mouse for scrolling and clicking. Off leaves it to the terminal.
+    pub mouse: bool,
+    /// Show the selected search result's summary in a pane next to the results
+    pub search_preview: bool,
+    /// Keep searched queries in the search history
+    pub search_history: bool,
+    /// Start with the tabs that were open when wik last quit, instead of the title screen
+    pub reopen_session: bool,
+    /// Defaults to ~/.cache/wik/
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    /// The preset key bindings start from
+    pub keymap: String,
+    /// Section headings stripped from articles, per wiki language
+    pub stripped_sections: HashMap<String, Vec<String>>,
+    /// Changes to the preset: screen name -> action name -> keys
+    pub keys: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default().name,
+            language: String::from(wikipedia::DEFAULT_LANGUAGE),
+            search_result_limit: 25,
+            refresh_time_millis: 16,
+            images: ImageMode::Auto,
+            mouse: true,
+            search_preview: false,
+            search_history: true,
+            reopen_session: false,
+            cache_dir: None,
+            keymap: String::from("default"),
+            stripped_sections: SectionFilter::default().hidden_sections,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+/// The config file: everything is optional and falls back to the defaults. Stripped sections
+/// are merged per language, so setting them for one language keeps the defaults for the rest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    theme: Option<String>,
+    language: Option<String>,
+    search_result_limit: Option<u16>,
+    refresh_time_millis: Option<u64>,
+    images: Option<ImageMode>,
+    mouse: Option<bool>,
+    search_preview: Option<bool>,
+    search_history: Option<bool>,
+    reopen_session: Option<bool>,
+    cache_dir: Option<PathBuf>,
+    stripped_sections: Option<HashMap<String, Vec<String>>>,
+    keymap: Option<String>,
+    keys: Option<HashMap<String, HashMap<String, KeyList>>>,
+}
+
+/// Bindings can be given as a single key or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Config {
+    pub fn default_path() -> PathBuf {
+        home_dir().unwrap_or_default().join(CONFIG_FILE)
+    }
+
+    /// Reads the config file at `path`, or the defaults if there isn't one
+    pub fn load(path: &PathBuf) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(toml_text) => Config::from_toml(&toml_text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn from_toml(toml_text: &str) -> Result<Config, String> {
+        let file: ConfigFile = toml::from_str(toml_text).map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+
+        if let Some(theme) = file.theme {
+            config.theme = theme;
+        }
+        if let Some(language) = file.language {
+            config.language = language;
+        }
+        if let Some(search_result_limit) = file.search_result_limit {
+            config.search_result_limit = search_result_limit;
+        }
+        if let Some(refresh_time_millis) = file.refresh_time_millis {
+            config.refresh_time_millis = refresh_time_millis;
+        }
+        if let Some(images) = file.images {
+            config.images = images;
+        }
+        if let Some(mouse) = file.mouse {
+            config.mouse = mouse;
+        }
+        if let Some(search_preview) = file.search_preview {
+            config.search_preview = search_preview;
+        }
+        if let Some(search_history) = file.search_history {
+            config.search_history = search_history;
+        }
+        if let Some(reopen_session) = file.reopen_session {
+            config.reopen_session = reopen_session;
+        }
+        config.cache_dir = file.cache_dir;
+        if let Some(stripped_sections) = file.stripped_sections {
+            config.stripped_sections.extend(stripped_sections);
+        }
+        if let Some(keymap) = file.keymap {
+            config.keymap = keymap;
+        }
+        for (context, actions) in file.keys.unwrap_or_default() {
+            let actions = actions
+                .into_iter()
+                .map(|(action, keys)| match keys {
+                    KeyList::One(key) => (action, vec![key]),
+                    KeyList::Many(keys) => (action, keys),
+                })
+                .collect();
+            config.keys.insert(context, actions);
+        }
+
+        Ok(config)
+    }
+
+    /// The cache directory with a leading `~` expanded, if one is set
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        let cache_dir = self.cache_dir.as_ref()?;
+        match cache_dir.strip_prefix("~") {
+            Ok(rest) => Some(home_dir().unwrap_or_default().join(rest)),
+            Err(_) => Some(cache_dir.clone()),
+        }
+    }
+
+    /// Sets each setting given on the command line, naming the flag of any that is wrong
+    pub fn apply_overrides(&mut self, overrides: &[(Setting, String)]) -> Result<(), String> {
+        for (setting, value) in overrides {
+            setting
+                .set(self, value)
+                .map_err(|e| format!("{}: {e}", setting.flag().unwrap_or_default()))?;
+        }
+        Ok(())
+    }
+
+    /// A copy with `settings` put back to their values in `file_config`, so that saving
+    /// doesn't write options that were only given on the command line into the file
+    pub fn with_file_values(&self, file_config: &Config, settings: &[Setting]) -> Config {
+        let mut config = self.clone();
+        for setting in settings {
+            // A value read back from a config always parses
+            let _ = setting.set(&mut config, &setting.value(file_config));
+        }
+        config
+    }
+
+    pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Checks the values are usable, describing every one that isn't
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let (themes, _) = Theme::available_themes();
+        if !themes.iter().any(|theme| theme.name == self.theme) {
+            errors.push(format!(
+                "theme: there is no theme called \"{}\", available themes are: {}",
+                self.theme,
+                themes
+                    .iter()
+                    .map(|theme| theme.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+        }
+        if let Err(e) = validate_language(&self.language) {
+            errors.push(format!("language: {e}"));
+        }
+        for language in self.stripped_sections.keys() {
+            if let Err(e) = validate_language(language) {
+                errors.push(format!("stripped_sections: {e}"));
+            }
+        }
+        if !(1..=MAX_SEARCH_RESULT_LIMIT).contains(&self.search_result_limit) {
+            errors.push(format!(
+                "search_result_limit: must be between 1 and {MAX_SEARCH_RESULT_LIMIT}, got {}",
+                self.search_result_limit
+            ));
+        }
+        if !(1..=MAX_REFRESH_TIME_MILLIS).contains(&self.refresh_time_millis) {
+            errors.push(format!(
+                "refresh_time_millis: must be between 1 and {MAX_REFRESH_TIME_MILLIS}, got {}",
+                self.refresh_time_millis
+            ));
+        }
+        if let Err(keymap_errors) = Keymap::build(&self.keymap, &self.keys) {
+            errors.extend(keymap_errors);
+        }
+        if self
+            .cache_dir
+            .as_ref()
+            .is_some_and(|cache_dir| cache_dir.as_os_str().is_empty())
+        {
+            errors.push(String::from("cache_dir: can't be empty"));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_language(language: &str) -> Result<(), String> {
+    let is_valid = !language.is_empty()
+        && language.len() <= 12
+        && language.chars().all(|c| c.is_ascii_lowercase() || c == '-');
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "\"{language}\" is not a Wikipedia language code, use something like \"en\" or \"simple\""
+        ))
+    }
+}
+
+fn parse_switch(text: &str) -> Result<bool, String> {
+    match text {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("\"{text}\" should be \"on\" or \"off\"")),
+    }
+}
+
+/// The settings that can be changed from the settings screen and the command line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Setting {
+    Theme,
+    Language,
+    StrippedSections,
+    SearchResultLimit,
+    RefreshTime,
+    Images,
+    Mouse,
+    SearchPreview,
+    SearchHistory,
+    ReopenSession,
+    CacheDir,
+    Keymap,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 12] = [
+        Setting::Theme,
+        Setting::Language,
+        Setting::StrippedSections,
+        Setting::SearchResultLimit,
+        Setting::RefreshTime,
+        Setting::Images,
+        Setting::Mouse,
+        Setting::SearchPreview,
+        Setting::SearchHistory,
+        Setting::ReopenSession,
+        Setting::CacheDir,
+        Setting::Keymap,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Setting::Theme => "Theme",
+            Setting::Language => "Language",
+            Setting::StrippedSections => "Stripped sections",
+            Setting::SearchResultLimit => "Search results",
+            Setting::RefreshTime => "Refresh time (ms)",
+            Setting::Images => "Images",
+            Setting::Mouse => "Mouse",
+            Setting::SearchPreview => "Search preview",
+            Setting::SearchHistory => "Search history",
+            Setting::ReopenSession => "Reopen last session",
+            Setting::CacheDir => "Cache directory",
+            Setting::Keymap => "Key bindings",
+        }
+    }
+
+    pub fn flag(&self) -> Option<&'static str> {
+        match self {
+            Setting::Theme => Some("--theme"),
+            Setting::Language => Some("--language"),
+            Setting::SearchResultLimit => Some("--search-limit"),
+            Setting::RefreshTime => Some("--refresh-ms"),
+            Setting::Images => Some("--images"),
+            Setting::Mouse => Some("--mouse"),
+            Setting::SearchPreview => Some("--preview"),
+            Setting::SearchHistory => Some("--history"),
+            Setting::ReopenSession => Some("--reopen"),
+            Setting::CacheDir => Some("--cache-dir"),
+            Setting::Keymap => Some("--keymap"),
+            Setting::StrippedSections => None,
+        }
+    }
+
+    /// The setting's value as it is shown and edited. Stripped sections are the ones for
+    /// the current language, separated by commas.
+    pub fn value(&self, config: &Config) -> String {
+        match self {
+            Setting::Theme => config.theme.clone(),
+            Setting::Language => config.language.clone(),
+            Setting::StrippedSections => config
+                .stripped_sections
+                .get(&config.language)
+                .map(|sections| sections.join(", "))
+                .unwrap_or_default(),
+            Setting::SearchResultLimit => config.search_result_limit.to_string(),
+            Setting::RefreshTime => config.refresh_time_millis.to_string(),
+            Setting::Images => config.images.name().to_string(),
+            Setting::Mouse => String::from(if config.mouse { "on" } else { "off" }),
+            Setting::SearchPreview => {
+                String::from(if config.search_preview { "on" } else { "off" })
+            }
+            Setting::SearchHistory => {
+                String::from(if config.search_history { "on" } else { "off" })
+            }
+            Setting::ReopenSession => {
+                String::from(if config.reopen_session { "on" } else { "off" })
+            }
+            Setting::CacheDir => match &config.cache_dir {
+                Some(cache_dir) => cache_dir.to_string_lossy().to_string(),
+                None => String::from("default"),
+            },
+            Setting::Keymap => config.keymap.clone(),
+        }
+    }
+
+    /// Parses `text` into the setting. The result still needs to be validated.
+    pub fn set(&self, config: &mut Config, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        match self {
+            Setting::Theme => config.theme = text.to_string(),
+            Setting::Language => config.language = text.to_string(),
+            Setting::StrippedSections => {
+                let sections = text
+                    .split(',')
+                    .map(|section| section.trim().to_string())
+                    .filter(|section| !section.is_empty())
+                    .collect();
+                config
+                    .stripped_sections
+                    .insert(config.language.clone(), sections);
+            }
+            Setting::SearchResultLimit => {
+                config.search_result_limit = text
+                    .parse()
+                    .map_err(|_| format!("\"{text}\" is not a number of results"))?
+            }
+            Setting::RefreshTime => {
+                config.refresh_time_millis = text
+                    .parse()
+                    .map_err(|_| format!("\"{text}\" is not a number of milliseconds"))?
+            }
+            Setting::Images => config.images = ImageMode::from_name(text)?,
+            Setting::Mouse => config.mouse = parse_switch(text)?,
+            Setting::SearchPreview => config.search_preview = parse_switch(text)?,
+            Setting::SearchHistory => config.search_history = parse_switch(text)?,
+            Setting::ReopenSession => config.reopen_session = parse_switch(text)?,
+            Setting::CacheDir => {
+                config.cache_dir = match text {
+                    "" | "default" => None,
+                    _ => Some(PathBuf::from(text)),
+                }
+            }
+            Setting::Keymap => config.keymap = text.to_string(),
+        }
+        Ok(())
+    }
+
+    /// Steps through the choices for settings that have a fixed set of them, or a number
+    pub fn cycle(&self, config: &mut Config, forwards: bool) {
+        let step = |index: usize, total: usize| match forwards {
+            true => remainder(index + 1, total),
+            false => remainder(index + total - 1, total),
+        };
+        match self {
+            Setting::Theme => {
+                let (themes, _) = Theme::available_themes();
+                let index = themes
+                    .iter()
+                    .position(|theme| theme.name == config.theme)
+                    .unwrap_or(0);
+                config.theme = themes[step(index, themes.len())].name.clone();
+            }
+            Setting::Images => {
+                let index = ImageMode::ALL
+                    .iter()
+                    .position(|mode| *mode == config.images)
+                    .unwrap_or(0);
+                config.images = ImageMode::ALL[step(index, ImageMode::ALL.len())];
+            }
+            Setting::SearchResultLimit => {
+                config.search_result_limit = match forwards {
+                    true => config.search_result_limit.saturating_add(5),
+                    false => config.search_result_limit.saturating_sub(5),
+                }
+                .clamp(1, MAX_SEARCH_RESULT_LIMIT)
+            }
+            Setting::RefreshTime => {
+                config.refresh_time_millis = match forwards {
+                    true => config.refresh_time_millis.saturating_add(1),
+                    false => config.refresh_time_millis.saturating_sub(1),
+                }
+                .clamp(1, MAX_REFRESH_TIME_MILLIS)
+            }
+            Setting::Mouse => config.mouse = !config.mouse,
+            Setting::SearchPreview => config.search_preview = !config.search_preview,
+            Setting::SearchHistory => config.search_history = !config.search_history,
+            Setting::ReopenSession => config.reopen_session = !config.reopen_session,
+            Setting::Keymap => {
+                let index = Keymap::PRESETS
+                    .iter()
+                    .position(|preset| *preset == config.keymap)
+                    .unwrap_or(0);
+                config.keymap = Keymap::PRESETS[step(index, Keymap::PRESETS.len())].to_string();
+            }
+            Setting::Language | Setting::StrippedSections | Setting::CacheDir => {}
+        }
+    }
+}
+
+/// What was asked for on the command line
+pub enum CliCommand {
+    Run {
+        config_path: PathBuf,
+        overrides: Vec<(Setting, String)>,
+    },
+    Help,
+    Version,
+}
+
+/// Reads the command line arguments (without the program name). Flags take their value
+/// either as the next argument or after an `=`.
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, String> {
+    let mut config_path = Config::default_path();
+    let mut overrides = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "-V" | "--version" => return Ok(CliCommand::Version),
+            _ => {}
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        let setting = Setting::ALL
+            .into_iter()
+            .find(|setting| setting.flag() == Some(flag.as_str()));
+        if setting.is_none() && flag != "--config" {
+            return Err(format!("unknown option \"{arg}\""));
+        }
+
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("{flag} needs a value")),
+        };
+        match setting {
+            Some(setting) => overrides.push((setting, value)),
+            None => config_path = PathBuf::from(value),
+        }
+    }
+
+    Ok(CliCommand::Run {
+        config_path,
+        overrides,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> impl Iterator<Item = String> + '_ {
+        text.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn parses_flags_with_and_without_an_equals_sign() {
+        let Ok(CliCommand::Run {
+            config_path,
+            overrides,
+        }) = parse_args(args("--theme light --language=de --config /tmp/wik.toml"))
+        else {
+            panic!("the arguments should parse");
+        };
+        assert_eq!(config_path, PathBuf::from("/tmp/wik.toml"));
+        assert_eq!(
+            overrides,
+            vec![
+                (Setting::Theme, String::from("light")),
+                (Setting::Language, String::from("de")),
+            ]
+        );
+
+        assert!(matches!(
+            parse_args(args("--theme x -h")),
+            Ok(CliCommand::Help)
+        ));
+        assert!(matches!(parse_args(args("-V")), Ok(CliCommand::Version)));
+        assert_eq!(
+            parse_args(args("--colour red")).err(),
+            Some(String::from("unknown option \"--colour\""))
+        );
+        assert_eq!(
+            parse_args(args("--theme")).err(),
+            Some(String::from("--theme needs a value"))
+        );
+    }
+
+    #[test]
+    fn reads_a_config_file_over_the_defaults() {
+        let config = Config::from_toml(
+            r#"
+            theme = "light"
+            search_result_limit = 50
+            images = "half-blocks"
+            stripped_sections = { de = ["Literatur"] }
+
+            [keys.article]
+            scroll_down = "ctrl-j"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.theme, "light");
+        assert_eq!(config.search_result_limit, 50);
+        assert_eq!(config.images, ImageMode::HalfBlocks);
+        assert_eq!(config.language, Config::default().language);
+        assert_eq!(config.stripped_sections["de"], vec!["Literatur"]);
+        // The other languages keep their default sections
+        assert_eq!(
+            config.stripped_sections["en"],
+            Config::default().stripped_sections["en"]
+        );
+        assert_eq!(config.keys["article"]["scroll_down"], vec!["ctrl-j"]);
+
+        assert!(Config::from_toml("colour = \"red\"").is_err());
+        assert!(Config::from_toml("search_result_limit = \"many\"").is_err());
+    }
+
+    #[test]
+    fn the_command_line_wins_over_the_file() {
+        let mut config = Config::from_toml("theme = \"light\"\nlanguage = \"fr\"").unwrap();
+        config
+            .apply_overrides(&[(Setting::Language, String::from("de"))])
+            .unwrap();
+        assert_eq!(config.theme, "light");
+        assert_eq!(config.language, "de");
+
+        assert_eq!(
+            config.apply_overrides(&[(Setting::Mouse, String::from("maybe"))]),
+            Err(String::from(
+                "--mouse: \"maybe\" should be \"on\" or \"off\""
+            ))
+        );
+    }
+
+    #[test]
+    fn saves_the_file_values_of_command_line_options() {
+        let file_config = Config::from_toml("theme = \"light\"").unwrap();
+        let mut config = file_config.clone();
+        config
+            .apply_overrides(&[
+                (Setting::Theme, String::from("solarized")),
+                (Setting::Language, String::from("de")),
+            ])
+            .unwrap();
+        config.search_result_limit = 10;
+
+        let saved = config.with_file_values(&file_config, &[Setting::Theme, Setting::Language]);
+        assert_eq!(saved.theme, "light");
+        assert_eq!(saved.language, file_config.language);
+        assert_eq!(saved.search_result_limit, 10);
+    }
+
+    #[test]
+    fn describes_every_bad_value() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let config = Config {
+            theme: String::from("neon"),
+            language: String::from("EN"),
+            search_result_limit: 0,
+            refresh_time_millis: 5000,
+            keymap: String::from("nano"),
+            cache_dir: Some(PathBuf::new()),
+            ..Config::default()
+        };
+
+        let errors = config.validate().unwrap_err();
+        for field in [
+            "theme:",
+            "language:",
+            "search_result_limit:",
+            "refresh_time_millis:",
+            "keymap:",
+            "cache_dir:",
+        ] {
+            assert!(
+                errors.iter().any(|error| error.starts_with(field)),
+                "no error for {field} in {errors:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::session;
+use crate::utils::unix_now;
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Older queries are dropped past this many
+const MAX_ENTRIES: usize = 500;
+
+/// A query that was searched for, and when
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub query: String,
+    /// Seconds since the Unix epoch
+    pub searched_at: u64,
+}
+
+/// Every query searched for, latest first, kept between runs
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SearchHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl SearchHistory {
+    pub fn path() -> PathBuf {
+        session::data_dir().join(HISTORY_FILE)
+    }
+
+    /// The saved history, or an empty one if nothing has been saved yet
+    pub fn load() -> Result<SearchHistory, Box<dyn Error>> {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> Result<SearchHistory, Box<dyn Error>> {
+        Ok(session::load_json(path)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        session::save_json(&Self::path(), self)
+    }
+
+    /// Puts `query` first. A query searched for before moves up rather than appearing twice.
+    pub fn record(&mut self, query: &str) {
+        self.entries.retain(|entry| entry.query != query);
+        self.entries.insert(
+            0,
+            HistoryEntry {
+                query: query.to_string(),
+                searched_at: unix_now(),
+            },
+        );
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// The latest `count` queries
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        self.entries
+            .iter()
+            .take(count)
+            .map(|entry| entry.query.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn keeps_searches_between_runs() {
+        let dir = std::env::temp_dir().join(format!("wik-history-test-{}", std::process::id()));
+        let path = dir.join(HISTORY_FILE);
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut history = SearchHistory::load_from(&path).unwrap();
+        assert!(history.entries.is_empty());
+        history.record("Kyoto");
+        history.record("Nara");
+        history.record("Kyoto");
+        session::save_json(&path, &history).unwrap();
+
+        let history = SearchHistory::load_from(&path).unwrap();
+        assert_eq!(history.recent(5), ["Kyoto", "Nara"]);
+        assert_eq!(history.recent(1), ["Kyoto"]);
+
+        fs::write(&path, "[\"Kyoto\"]").unwrap();
+        assert!(SearchHistory::load_from(&path).is_err());
+        assert!(SearchHistory::load_from(&path).unwrap().entries.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
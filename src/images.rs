@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    io::{self, Cursor, Write},
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{cursor::MoveTo, queue, terminal};
+use image::{imageops::FilterType, ImageFormat, RgbImage, RgbaImage};
+use reqwest::blocking::Client;
+use tui::layout::Rect;
+
+use crate::parsing::FormattedSpan;
+use crate::utils::Shared;
+
+pub const USER_AGENT: &str = concat!("wik/", env!("CARGO_PKG_VERSION"));
+
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+pub enum ImageState {
+    Loading,
+    Loaded(RgbaImage),
+    Failed,
+}
+
+/// Downloaded article images, keyed by URL
+pub type ImageStore = Shared<HashMap<String, ImageState>>;
+
+/// How images get onto the screen. Half-blocks work everywhere,
+/// the others are used when the terminal says it understands them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+    HalfBlocks,
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    pub fn detect() -> Self {
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var("KITTY_WINDOW_ID").is_ok() || term == "xterm-kitty" || term_program == "ghostty"
+        {
+            GraphicsProtocol::Kitty
+        } else if term_program == "iTerm.app" || term_program == "WezTerm" {
+            GraphicsProtocol::Iterm
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            GraphicsProtocol::Sixel
+        } else {
+            GraphicsProtocol::HalfBlocks
+        }
+    }
+}
+
+/// Where `ui` left room for an image that should be drawn with a terminal graphics protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePlacement {
+    pub url: String,
+    pub area: Rect,
+}
+
+/// Fetches every image in the article that isn't already in the store
+pub fn load_images_to_app(spans: &[FormattedSpan], image_store: ImageStore) {
+    let urls = spans
+        .iter()
+        .filter_map(|span| span.image.as_ref())
+        .map(|image| image.url.clone())
+        .collect();
+    fetch_images_to_store(urls, image_store);
+}
+
+/// Fetches a single image, like a search preview's thumbnail, unless it's already in the store
+pub fn load_image_to_app(url: String, image_store: ImageStore) {
+    fetch_images_to_store(vec![url], image_store);
+}
+
+fn fetch_images_to_store(all_urls: Vec<String>, image_store: ImageStore) {
+    let mut urls: Vec<String> = Vec::new();
+    {
+        let mut store = image_store.lock().unwrap();
+        for url in all_urls {
+            if !store.contains_key(&url) {
+                store.insert(url.clone(), ImageState::Loading);
+                urls.push(url);
+            }
+        }
+    }
+    if urls.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let client = Client::builder().user_agent(USER_AGENT).build();
+        for url in urls {
+            let image_state = match client.as_ref().ok().map(|client| fetch_image(client, &url)) {
+                Some(Ok(image)) => ImageState::Loaded(image),
+                _ => ImageState::Failed,
+            };
+            image_store.lock().unwrap().insert(url, image_state);
+        }
+    });
+}
+
+fn fetch_image(client: &Client, url: &str) -> Result<RgbaImage, Box<dyn Error>> {
+    let bytes = client.get(url).send()?.error_for_status()?.bytes()?;
+    Ok(image::load_from_memory(&bytes)?.to_rgba8())
+}
+
+/// Scales the image to exactly `width` x `height` pixels, laying transparent parts over `background`
+pub fn flatten(image: &RgbaImage, width: u32, height: u32, background: (u8, u8, u8)) -> RgbImage {
+    let resized = image::imageops::resize(image, width.max(1), height.max(1), FilterType::Triangle);
+    RgbImage::from_fn(resized.width(), resized.height(), |x, y| {
+        let [red, green, blue, alpha] = resized.get_pixel(x, y).0;
+        let blend = |channel: u8, background_channel: u8| {
+            ((channel as u32 * alpha as u32 + background_channel as u32 * (255 - alpha as u32))
+                / 255) as u8
+        };
+        image::Rgb([
+            blend(red, background.0),
+            blend(green, background.1),
+            blend(blue, background.2),
+        ])
+    })
+}
+
+/// Writes images over the blank areas `ui` reserved for them, for the non half-block protocols.
+/// Only redraws when the placements change, as the escape sequences are big.
+pub struct GraphicsOutput {
+    pub protocol: GraphicsProtocol,
+    last_placements: Vec<ImagePlacement>,
+}
+
+impl GraphicsOutput {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol,
+            last_placements: Vec::new(),
+        }
+    }
+
+    pub fn needs_update(&self, placements: &[ImagePlacement]) -> bool {
+        self.protocol != GraphicsProtocol::HalfBlocks && placements != self.last_placements
+    }
+
+    /// iTerm and Sixel images are painted into the cells themselves, so the screen has to be
+    /// redrawn from scratch to get rid of the old ones. Kitty images can just be deleted.
+    pub fn needs_full_redraw(&self) -> bool {
+        matches!(
+            self.protocol,
+            GraphicsProtocol::Iterm | GraphicsProtocol::Sixel
+        )
+    }
+
+    pub fn write_placements<W: Write>(
+        &mut self,
+        out: &mut W,
+        placements: Vec<ImagePlacement>,
+        image_store: &ImageStore,
+        background: (u8, u8, u8),
+    ) -> io::Result<()> {
+        if self.protocol == GraphicsProtocol::Kitty {
+            write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+        }
+
+        let (cell_width, cell_height) = cell_size_in_pixels();
+        let store = image_store.lock().unwrap();
+        for placement in placements.iter() {
+            let Some(ImageState::Loaded(image)) = store.get(&placement.url) else {
+                continue;
+            };
+            let area = placement.area;
+            let pixels = flatten(
+                image,
+                area.width as u32 * cell_width,
+                area.height as u32 * cell_height,
+                background,
+            );
+
+            queue!(out, MoveTo(area.x, area.y))?;
+            match self.protocol {
+                GraphicsProtocol::Kitty => write_kitty(out, &pixels, area)?,
+                GraphicsProtocol::Iterm => write_iterm(out, &pixels, area)?,
+                GraphicsProtocol::Sixel => write_sixel(out, &pixels)?,
+                GraphicsProtocol::HalfBlocks => {}
+            }
+        }
+        out.flush()?;
+
+        self.last_placements = placements;
+        Ok(())
+    }
+}
+
+fn cell_size_in_pixels() -> (u32, u32) {
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => (10, 20),
+    }
+}
+
+fn png_base64(pixels: &RgbImage) -> io::Result<String> {
+    let mut png_bytes = Vec::new();
+    pixels
+        .write_to(&mut Cursor::new(&mut png_bytes), ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(STANDARD.encode(png_bytes))
+}
+
+fn write_kitty<W: Write>(out: &mut W, pixels: &RgbImage, area: Rect) -> io::Result<()> {
+    let encoded = png_base64(pixels)?;
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more_chunks = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=100,q=2,c={},r={},m={more_chunks};",
+                area.width, area.height
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={more_chunks};")?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    Ok(())
+}
+
+fn write_iterm<W: Write>(out: &mut W, pixels: &RgbImage, area: Rect) -> io::Result<()> {
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=0:{}\x07",
+        area.width,
+        area.height,
+        png_base64(pixels)?
+    )
+}
+
+/// Sixel output on a fixed 6x6x6 colour cube, which is plenty for thumbnails
+fn write_sixel<W: Write>(out: &mut W, pixels: &RgbImage) -> io::Result<()> {
+    let (width, height) = pixels.dimensions();
+    let to_level = |channel: u8| (channel as usize * 5 + 127) / 255;
+    let palette_index = |x: u32, y: u32| {
+        let [red, green, blue] = pixels.get_pixel(x, y).0;
+        to_level(red) * 36 + to_level(green) * 6 + to_level(blue)
+    };
+
+    write!(out, "\x1bPq\"1;1;{width};{height}")?;
+    for index in 0..216 {
+        let to_percent = |level: usize| level * 100 / 5;
+        write!(
+            out,
+            "#{index};2;{};{};{}",
+            to_percent(index / 36),
+            to_percent(index / 6 % 6),
+            to_percent(index % 6)
+        )?;
+    }
+
+    for band_top in (0..height).step_by(6) {
+        let band_height = (height - band_top).min(6);
+        let mut colour_rows: HashMap<usize, Vec<u8>> = HashMap::new();
+        for x in 0..width {
+            for dy in 0..band_height {
+                let row = colour_rows
+                    .entry(palette_index(x, band_top + dy))
+                    .or_insert_with(|| vec![0; width as usize]);
+                row[x as usize] |= 1 << dy;
+            }
+        }
+
+        for (index, row) in colour_rows {
+            write!(out, "#{index}")?;
+            let mut x = 0;
+            while x < row.len() {
+                let run = row[x..].iter().take_while(|&&bits| bits == row[x]).count();
+                let sixel = (row[x] + 63) as char;
+                if run > 3 {
+                    write!(out, "!{run}{sixel}")?;
+                } else {
+                    write!(out, "{}", sixel.to_string().repeat(run))?;
+                }
+                x += run;
+            }
+            write!(out, "$")?;
+        }
+        write!(out, "-")?;
+    }
+    write!(out, "\x1b\\")
+}
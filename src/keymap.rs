@@ -0,0 +1,984 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::AppState;
+
+/// Everything a key can be bound to. What an action does depends on the screen it's used on,
+/// e.g. `Confirm` runs the selected menu item in a menu and submits the query when searching.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Help,
+    Confirm,
+    OpenMenu,
+    Close,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    NextLink,
+    PreviousLink,
+    FollowLink,
+    Back,
+    ToggleMath,
+    OpenLinkInNewTab,
+    NextTab,
+    PreviousTab,
+    CloseTab,
+    MoveTabLeft,
+    MoveTabRight,
+    SplitVertical,
+    SplitHorizontal,
+    SwitchPane,
+    ClosePane,
+    ToggleSyncScroll,
+    ViewResult,
+    CycleSort,
+    AdvancedSearch,
+    TogglePreview,
+    SearchHistory,
+    AddBookmark,
+    ReadLater,
+    OpenBookmarks,
+    DeleteBookmark,
+    EditBookmarkNote,
+    EditBookmarkTags,
+    CommandPalette,
+    PreviousChoice,
+    NextChoice,
+    CursorLeft,
+    CursorRight,
+    CursorToStart,
+    CursorToEnd,
+    DeleteBackward,
+}
+
+impl Action {
+    const ALL: [Action; 48] = [
+        Action::Quit,
+        Action::Help,
+        Action::Confirm,
+        Action::OpenMenu,
+        Action::Close,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::HalfPageUp,
+        Action::HalfPageDown,
+        Action::ScrollToTop,
+        Action::ScrollToBottom,
+        Action::NextLink,
+        Action::PreviousLink,
+        Action::FollowLink,
+        Action::Back,
+        Action::ToggleMath,
+        Action::OpenLinkInNewTab,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::CloseTab,
+        Action::MoveTabLeft,
+        Action::MoveTabRight,
+        Action::SplitVertical,
+        Action::SplitHorizontal,
+        Action::SwitchPane,
+        Action::ClosePane,
+        Action::ToggleSyncScroll,
+        Action::ViewResult,
+        Action::CycleSort,
+        Action::AdvancedSearch,
+        Action::TogglePreview,
+        Action::SearchHistory,
+        Action::AddBookmark,
+        Action::ReadLater,
+        Action::OpenBookmarks,
+        Action::DeleteBookmark,
+        Action::EditBookmarkNote,
+        Action::EditBookmarkTags,
+        Action::CommandPalette,
+        Action::PreviousChoice,
+        Action::NextChoice,
+        Action::CursorLeft,
+        Action::CursorRight,
+        Action::CursorToStart,
+        Action::CursorToEnd,
+        Action::DeleteBackward,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::Confirm => "confirm",
+            Action::OpenMenu => "open-menu",
+            Action::Close => "close",
+            Action::ScrollUp => "scroll-up",
+            Action::ScrollDown => "scroll-down",
+            Action::PageUp => "page-up",
+            Action::PageDown => "page-down",
+            Action::HalfPageUp => "half-page-up",
+            Action::HalfPageDown => "half-page-down",
+            Action::ScrollToTop => "scroll-to-top",
+            Action::ScrollToBottom => "scroll-to-bottom",
+            Action::NextLink => "next-link",
+            Action::PreviousLink => "previous-link",
+            Action::FollowLink => "follow-link",
+            Action::Back => "back",
+            Action::ToggleMath => "toggle-math",
+            Action::OpenLinkInNewTab => "open-link-in-new-tab",
+            Action::NextTab => "next-tab",
+            Action::PreviousTab => "previous-tab",
+            Action::CloseTab => "close-tab",
+            Action::MoveTabLeft => "move-tab-left",
+            Action::MoveTabRight => "move-tab-right",
+            Action::SplitVertical => "split-vertical",
+            Action::SplitHorizontal => "split-horizontal",
+            Action::SwitchPane => "switch-pane",
+            Action::ClosePane => "close-pane",
+            Action::ToggleSyncScroll => "toggle-sync-scroll",
+            Action::ViewResult => "view-result",
+            Action::CycleSort => "cycle-sort",
+            Action::AdvancedSearch => "advanced-search",
+            Action::TogglePreview => "toggle-preview",
+            Action::SearchHistory => "search-history",
+            Action::AddBookmark => "add-bookmark",
+            Action::ReadLater => "read-later",
+            Action::OpenBookmarks => "open-bookmarks",
+            Action::DeleteBookmark => "delete-bookmark",
+            Action::EditBookmarkNote => "edit-bookmark-note",
+            Action::EditBookmarkTags => "edit-bookmark-tags",
+            Action::CommandPalette => "command-palette",
+            Action::PreviousChoice => "previous-choice",
+            Action::NextChoice => "next-choice",
+            Action::CursorLeft => "cursor-left",
+            Action::CursorRight => "cursor-right",
+            Action::CursorToStart => "cursor-to-start",
+            Action::CursorToEnd => "cursor-to-end",
+            Action::DeleteBackward => "delete-backward",
+        }
+    }
+
+    /// What the action does, as shown in the help overlay
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit wik",
+            Action::Help => "Show this help",
+            Action::Confirm => "Select",
+            Action::OpenMenu => "Open the menu",
+            Action::Close => "Close",
+            Action::ScrollUp => "Move up",
+            Action::ScrollDown => "Move down",
+            Action::PageUp => "Scroll up a page",
+            Action::PageDown => "Scroll down a page",
+            Action::HalfPageUp => "Scroll up half a page",
+            Action::HalfPageDown => "Scroll down half a page",
+            Action::ScrollToTop => "Go to the top",
+            Action::ScrollToBottom => "Go to the bottom",
+            Action::NextLink => "Select the next link",
+            Action::PreviousLink => "Select the previous link",
+            Action::FollowLink => "Follow the selected link",
+            Action::Back => "Go back to the previous article",
+            Action::ToggleMath => "Show formulas as LaTeX or Unicode",
+            Action::OpenLinkInNewTab => "Open the selected link in a new tab",
+            Action::NextTab => "Switch to the next tab",
+            Action::PreviousTab => "Switch to the previous tab",
+            Action::CloseTab => "Close the tab",
+            Action::MoveTabLeft => "Move the tab left",
+            Action::MoveTabRight => "Move the tab right",
+            Action::SplitVertical => "Split the view side by side",
+            Action::SplitHorizontal => "Split the view top and bottom",
+            Action::SwitchPane => "Move focus to the other pane",
+            Action::ClosePane => "Close the focused pane",
+            Action::ToggleSyncScroll => "Scroll both panes together by section",
+            Action::ViewResult => "Open the selected result",
+            Action::CycleSort => "Change how results are sorted",
+            Action::AdvancedSearch => "Open or close the advanced search form",
+            Action::TogglePreview => "Show or hide the preview of the selected result",
+            Action::SearchHistory => "Search past queries",
+            Action::AddBookmark => "Bookmark the article",
+            Action::ReadLater => "Save the article to read later",
+            Action::OpenBookmarks => "Show bookmarks and the reading list",
+            Action::DeleteBookmark => "Delete the selected bookmark",
+            Action::EditBookmarkNote => "Edit the bookmark's note",
+            Action::EditBookmarkTags => "Edit the bookmark's tags",
+            Action::CommandPalette => "Open the command palette",
+            Action::PreviousChoice => "Previous choice",
+            Action::NextChoice => "Next choice",
+            Action::CursorLeft => "Move the cursor left",
+            Action::CursorRight => "Move the cursor right",
+            Action::CursorToStart => "Move the cursor to the start",
+            Action::CursorToEnd => "Move the cursor to the end",
+            Action::DeleteBackward => "Delete the character before the cursor",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Action, String> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name() == name)
+            .ok_or(format!("there is no action called \"{name}\""))
+    }
+}
+
+/// Where a binding applies: one of the screens, or any focused text box
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Screen(AppState),
+    TextInput,
+}
+
+impl KeyContext {
+    pub const ALL: [KeyContext; 11] = [
+        KeyContext::Screen(AppState::Title),
+        KeyContext::Screen(AppState::Search),
+        KeyContext::Screen(AppState::SearchMenu),
+        KeyContext::Screen(AppState::Article),
+        KeyContext::Screen(AppState::ArticleMenu),
+        KeyContext::Screen(AppState::Credit),
+        KeyContext::Screen(AppState::ThemePicker),
+        KeyContext::Screen(AppState::Settings),
+        KeyContext::Screen(AppState::CommandPalette),
+        KeyContext::Screen(AppState::Bookmarks),
+        KeyContext::TextInput,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyContext::Screen(AppState::Title) => "title",
+            KeyContext::Screen(AppState::Search) => "search",
+            KeyContext::Screen(AppState::SearchMenu) => "search-menu",
+            KeyContext::Screen(AppState::Article) => "article",
+            KeyContext::Screen(AppState::ArticleMenu) => "article-menu",
+            KeyContext::Screen(AppState::Credit) => "credit",
+            KeyContext::Screen(AppState::ThemePicker) => "theme-picker",
+            KeyContext::Screen(AppState::Settings) => "settings",
+            KeyContext::Screen(AppState::CommandPalette) => "command-palette",
+            KeyContext::Screen(AppState::Bookmarks) => "bookmarks",
+            KeyContext::TextInput => "text-input",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<KeyContext, String> {
+        KeyContext::ALL
+            .into_iter()
+            .find(|context| context.name() == name)
+            .ok_or(format!(
+                "there are no key bindings called \"{name}\", use one of: {}",
+                KeyContext::ALL.map(|context| context.name()).join(", ")
+            ))
+    }
+}
+
+/// One key with its modifiers. Shift is folded into the character for letters and symbols,
+/// so "G" is shift-g.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    pub fn from_event(event: &KeyEvent) -> KeyPress {
+        let modifiers = match event.code {
+            KeyCode::Char(_) | KeyCode::BackTab => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        KeyPress {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    /// Reads keys written like "j", "G", "ctrl-d", "alt-<", "shift-tab", "pagedown" or "f1"
+    pub fn parse(text: &str) -> Result<KeyPress, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = text;
+        // Split modifiers off the front, leaving a lone "-" as the key itself
+        while let Some((modifier, rest)) = key.split_once('-') {
+            if rest.is_empty() {
+                break;
+            }
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("\"{modifier}\" in \"{text}\" is not a modifier")),
+            };
+            key = rest;
+        }
+
+        let code = match key.to_lowercase().as_str() {
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap_or(' ')),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name
+                .strip_prefix('f')
+                .and_then(|number| number.parse().ok())
+            {
+                Some(number @ 1..=24) => KeyCode::F(number),
+                _ => return Err(format!("\"{text}\" is not a key")),
+            },
+        };
+
+        Ok(KeyPress::from_event(&KeyEvent::new(code, modifiers)))
+    }
+
+    /// A character that should be typed into a text box rather than looked up
+    pub fn typed_char(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(c)
+                if !self
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Written the same way `KeyPress::parse` reads it
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(number) => write!(f, "f{number}"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "shift-tab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// A key, or a sequence of keys separated by spaces like "g g"
+pub type KeySequence = Vec<KeyPress>;
+
+fn sequence_to_string(sequence: &KeySequence) -> String {
+    sequence
+        .iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_sequence(text: &str) -> Result<KeySequence, String> {
+    let sequence = text
+        .split_whitespace()
+        .map(KeyPress::parse)
+        .collect::<Result<KeySequence, String>>()?;
+    if sequence.is_empty() {
+        return Err(String::from("a key binding can't be empty"));
+    }
+    Ok(sequence)
+}
+
+const ALL_SCREENS: [AppState; 10] = [
+    AppState::Title,
+    AppState::Search,
+    AppState::SearchMenu,
+    AppState::Article,
+    AppState::ArticleMenu,
+    AppState::Credit,
+    AppState::ThemePicker,
+    AppState::Settings,
+    AppState::CommandPalette,
+    AppState::Bookmarks,
+];
+
+/// Screens that are a list to pick from
+const MENUS: [AppState; 7] = [
+    AppState::SearchMenu,
+    AppState::ArticleMenu,
+    AppState::Credit,
+    AppState::ThemePicker,
+    AppState::Settings,
+    AppState::CommandPalette,
+    AppState::Bookmarks,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum KeyResult {
+    Action(Action),
+    /// The keys so far are the start of a longer binding
+    Pending,
+    Unbound,
+}
+
+/// How long the start of a binding like "g g" waits for the rest of it
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The keys pressed so far towards a binding of more than one key
+#[derive(Default)]
+pub struct PendingKeys {
+    keys: KeySequence,
+    last_pressed: Option<Instant>,
+}
+
+impl PendingKeys {
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.last_pressed = None;
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<KeyContext, Vec<(KeySequence, Action)>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::default_preset()
+    }
+}
+
+impl Keymap {
+    pub const PRESETS: [&'static str; 3] = ["default", "vim", "emacs"];
+
+    /// The named preset, with `overrides` (context name -> action name -> keys) on top.
+    /// Overriding an action replaces all of its keys in that context.
+    pub fn build(
+        preset: &str,
+        overrides: &HashMap<String, HashMap<String, Vec<String>>>,
+    ) -> Result<Keymap, Vec<String>> {
+        let mut keymap = match preset {
+            "default" => Keymap::default_preset(),
+            "vim" => Keymap::vim_preset(),
+            "emacs" => Keymap::emacs_preset(),
+            _ => {
+                return Err(vec![format!(
+                    "keymap: \"{preset}\" is not a keymap, use one of: {}",
+                    Keymap::PRESETS.join(", ")
+                )])
+            }
+        };
+
+        let mut errors = Vec::new();
+        for (context_name, actions) in overrides {
+            let context = match KeyContext::from_name(context_name) {
+                Ok(context) => context,
+                Err(e) => {
+                    errors.push(format!("keys: {e}"));
+                    continue;
+                }
+            };
+            for (action_name, keys) in actions {
+                let action = match Action::from_name(action_name) {
+                    Ok(action) => action,
+                    Err(e) => {
+                        errors.push(format!("keys.{context_name}: {e}"));
+                        continue;
+                    }
+                };
+                let bindings = keymap.bindings.entry(context).or_default();
+                bindings.retain(|(_, bound_action)| *bound_action != action);
+                for key in keys {
+                    match parse_sequence(key) {
+                        Ok(sequence) => bindings.push((sequence, action)),
+                        Err(e) => errors.push(format!("keys.{context_name}.{action_name}: {e}")),
+                    }
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(keymap),
+            false => Err(errors),
+        }
+    }
+
+    /// Adds `key`, pressed at `now`, to the binding so far and looks it up. The keys so far are
+    /// dropped if they were pressed too long ago. A text box gets first go at keys, and plain
+    /// characters are left for it to type.
+    pub fn resolve(
+        &self,
+        state: AppState,
+        text_input_focused: bool,
+        pending: &mut PendingKeys,
+        key: KeyPress,
+        now: Instant,
+    ) -> KeyResult {
+        if pending
+            .last_pressed
+            .is_some_and(|last_pressed| now.duration_since(last_pressed) > SEQUENCE_TIMEOUT)
+        {
+            pending.clear();
+        }
+        pending.last_pressed = Some(now);
+        self.resolve_sequence(state, text_input_focused, &mut pending.keys, key)
+    }
+
+    fn resolve_sequence(
+        &self,
+        state: AppState,
+        text_input_focused: bool,
+        pending_keys: &mut KeySequence,
+        key: KeyPress,
+    ) -> KeyResult {
+        pending_keys.push(key);
+        let mut contexts = vec![];
+        if text_input_focused {
+            contexts.push(KeyContext::TextInput);
+        }
+        contexts.push(KeyContext::Screen(state));
+
+        let candidates: Vec<&(KeySequence, Action)> = contexts
+            .iter()
+            .flat_map(|context| {
+                self.bindings
+                    .get(context)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |(sequence, _)| {
+                        *context == KeyContext::TextInput
+                            || !text_input_focused
+                            || sequence[0].typed_char().is_none()
+                    })
+            })
+            .collect();
+
+        if let Some((_, action)) = candidates
+            .iter()
+            .find(|(sequence, _)| sequence == pending_keys)
+        {
+            pending_keys.clear();
+            return KeyResult::Action(*action);
+        }
+        if candidates
+            .iter()
+            .any(|(sequence, _)| sequence.starts_with(pending_keys))
+        {
+            return KeyResult::Pending;
+        }
+
+        // The earlier keys didn't lead anywhere, so start again from this one
+        let was_pending = pending_keys.len() > 1;
+        pending_keys.clear();
+        if was_pending {
+            self.resolve_sequence(state, text_input_focused, pending_keys, key)
+        } else {
+            KeyResult::Unbound
+        }
+    }
+
+    /// The bindings in `context` grouped by action, in the order they were bound
+    pub fn bindings_in(&self, context: KeyContext) -> Vec<(Action, Vec<String>)> {
+        let mut grouped: Vec<(Action, Vec<String>)> = Vec::new();
+        for (sequence, action) in self.bindings.get(&context).into_iter().flatten() {
+            let key = sequence_to_string(sequence);
+            match grouped.iter_mut().find(|(other, _)| other == action) {
+                Some((_, keys)) => keys.push(key),
+                None => grouped.push((*action, vec![key])),
+            }
+        }
+        grouped
+    }
+
+    /// The first key that does `action` on the screen, skipping ones a text box would take
+    pub fn key_for(
+        &self,
+        state: AppState,
+        text_input_focused: bool,
+        action: Action,
+    ) -> Option<String> {
+        let mut contexts = vec![];
+        if text_input_focused {
+            contexts.push(KeyContext::TextInput);
+        }
+        contexts.push(KeyContext::Screen(state));
+
+        contexts.iter().find_map(|context| {
+            self.bindings
+                .get(context)?
+                .iter()
+                .find(|(sequence, bound_action)| {
+                    *bound_action == action
+                        && (*context == KeyContext::TextInput
+                            || !text_input_focused
+                            || sequence[0].typed_char().is_none())
+                })
+                .map(|(sequence, _)| sequence_to_string(sequence))
+        })
+    }
+
+    fn bind(&mut self, context: KeyContext, keys: &[&str], action: Action) {
+        let bindings = self.bindings.entry(context).or_default();
+        for key in keys {
+            if let Ok(sequence) = parse_sequence(key) {
+                bindings.push((sequence, action));
+            }
+        }
+    }
+
+    /// Removes every binding for `key`, in all contexts
+    fn unbind_key(&mut self, key: &str) {
+        if let Ok(sequence) = parse_sequence(key) {
+            for bindings in self.bindings.values_mut() {
+                bindings.retain(|(bound_sequence, _)| *bound_sequence != sequence);
+            }
+        }
+    }
+
+    fn bind_screens(&mut self, states: &[AppState], keys: &[&str], action: Action) {
+        for state in states {
+            self.bind(KeyContext::Screen(*state), keys, action);
+        }
+    }
+
+    fn default_preset() -> Keymap {
+        use AppState::*;
+        let mut keymap = Keymap {
+            bindings: HashMap::new(),
+        };
+
+        keymap.bind(KeyContext::TextInput, &["left"], Action::CursorLeft);
+        keymap.bind(KeyContext::TextInput, &["right"], Action::CursorRight);
+        keymap.bind(KeyContext::TextInput, &["ctrl-left"], Action::CursorToStart);
+        keymap.bind(KeyContext::TextInput, &["ctrl-right"], Action::CursorToEnd);
+        keymap.bind(
+            KeyContext::TextInput,
+            &["backspace"],
+            Action::DeleteBackward,
+        );
+
+        keymap.bind_screens(&ALL_SCREENS, &["?", "f3"], Action::Help);
+        keymap.bind_screens(&ALL_SCREENS, &[":", "ctrl-p"], Action::CommandPalette);
+        // "?" and ":" are typed into text boxes, so the other keys work while typing too
+        keymap.bind(KeyContext::TextInput, &["f3"], Action::Help);
+        keymap.bind(KeyContext::TextInput, &["ctrl-p"], Action::CommandPalette);
+        keymap.bind_screens(&[Title], &["enter"], Action::Confirm);
+        keymap.bind_screens(&[Title], &["up"], Action::ScrollUp);
+        keymap.bind_screens(&[Title], &["down"], Action::ScrollDown);
+        keymap.bind_screens(&[Title, Search], &["ctrl-r"], Action::SearchHistory);
+        keymap.bind_screens(&[Title, Search], &["f7"], Action::OpenBookmarks);
+
+        keymap.bind_screens(&[Search, Article], &["esc"], Action::OpenMenu);
+        // Just-in-case exit
+        keymap.bind_screens(&[Search, SearchMenu], &["f1"], Action::Quit);
+        keymap.bind_screens(&[Search], &["enter"], Action::Confirm);
+        keymap.bind_screens(&[Search], &["f2"], Action::ViewResult);
+        keymap.bind_screens(&[Search], &["f4"], Action::CycleSort);
+        keymap.bind_screens(&[Search], &["f5"], Action::AdvancedSearch);
+        keymap.bind_screens(&[Search], &["f6"], Action::TogglePreview);
+        keymap.bind_screens(&[Search], &["up"], Action::ScrollUp);
+        keymap.bind_screens(&[Search], &["down"], Action::ScrollDown);
+
+        keymap.bind_screens(&MENUS, &["esc"], Action::Close);
+        keymap.bind_screens(&MENUS, &["up"], Action::ScrollUp);
+        keymap.bind_screens(&MENUS, &["down"], Action::ScrollDown);
+        keymap.bind_screens(&MENUS, &["enter"], Action::Confirm);
+        keymap.bind_screens(&[Settings, Search], &["left"], Action::PreviousChoice);
+        keymap.bind_screens(&[Settings, Search], &["right"], Action::NextChoice);
+        keymap.bind_screens(&[Bookmarks], &["shift-tab"], Action::PreviousChoice);
+        keymap.bind_screens(&[Bookmarks], &["tab"], Action::NextChoice);
+        keymap.bind_screens(&[Bookmarks], &["delete"], Action::DeleteBookmark);
+        keymap.bind_screens(&[Bookmarks], &["f2"], Action::EditBookmarkNote);
+        keymap.bind_screens(&[Bookmarks], &["f4"], Action::EditBookmarkTags);
+
+        keymap.bind_screens(&[Article], &["up"], Action::ScrollUp);
+        keymap.bind_screens(&[Article], &["down"], Action::ScrollDown);
+        keymap.bind_screens(&[Article], &["pageup"], Action::PageUp);
+        keymap.bind_screens(&[Article], &["pagedown"], Action::PageDown);
+        keymap.bind_screens(&[Article], &["home"], Action::ScrollToTop);
+        keymap.bind_screens(&[Article], &["end"], Action::ScrollToBottom);
+        keymap.bind_screens(&[Article], &["tab"], Action::NextLink);
+        keymap.bind_screens(&[Article], &["shift-tab"], Action::PreviousLink);
+        keymap.bind_screens(&[Article], &["enter"], Action::FollowLink);
+        keymap.bind_screens(&[Article], &["backspace"], Action::Back);
+        keymap.bind_screens(&[Article], &["m"], Action::ToggleMath);
+        keymap.bind_screens(&[Article], &["t"], Action::OpenLinkInNewTab);
+        keymap.bind_screens(&[Article], &["]", "ctrl-pagedown"], Action::NextTab);
+        keymap.bind_screens(&[Article], &["[", "ctrl-pageup"], Action::PreviousTab);
+        keymap.bind_screens(&[Article], &["w"], Action::CloseTab);
+        keymap.bind_screens(&[Article], &["{"], Action::MoveTabLeft);
+        keymap.bind_screens(&[Article], &["}"], Action::MoveTabRight);
+        keymap.bind_screens(&[Article], &["|"], Action::SplitVertical);
+        keymap.bind_screens(&[Article], &["-"], Action::SplitHorizontal);
+        keymap.bind_screens(&[Article], &["o"], Action::SwitchPane);
+        keymap.bind_screens(&[Article], &["x"], Action::ClosePane);
+        keymap.bind_screens(&[Article], &["s"], Action::ToggleSyncScroll);
+        keymap.bind_screens(&[Article], &["b"], Action::AddBookmark);
+        keymap.bind_screens(&[Article], &["r"], Action::ReadLater);
+        keymap.bind_screens(&[Article], &["B"], Action::OpenBookmarks);
+
+        keymap
+    }
+
+    fn vim_preset() -> Keymap {
+        use AppState::*;
+        let mut keymap = Keymap::default_preset();
+
+        keymap.bind_screens(&MENUS, &["k"], Action::ScrollUp);
+        keymap.bind_screens(&MENUS, &["j"], Action::ScrollDown);
+        keymap.bind_screens(&MENUS, &["q"], Action::Close);
+        keymap.bind_screens(&[Settings, Search], &["h"], Action::PreviousChoice);
+        keymap.bind_screens(&[Settings, Search], &["l"], Action::NextChoice);
+        // Ctrl-P is left opening the command palette, so results only get Ctrl-N
+        keymap.bind_screens(&[Search], &["ctrl-n"], Action::ScrollDown);
+
+        keymap.bind_screens(&[Article], &["k"], Action::ScrollUp);
+        keymap.bind_screens(&[Article], &["j"], Action::ScrollDown);
+        keymap.bind_screens(&[Article], &["ctrl-b"], Action::PageUp);
+        keymap.bind_screens(&[Article], &["ctrl-f"], Action::PageDown);
+        keymap.bind_screens(&[Article], &["ctrl-u"], Action::HalfPageUp);
+        keymap.bind_screens(&[Article], &["ctrl-d"], Action::HalfPageDown);
+        keymap.bind_screens(&[Article], &["g g"], Action::ScrollToTop);
+        keymap.bind_screens(&[Article], &["G"], Action::ScrollToBottom);
+        keymap.bind_screens(&[Article], &["n"], Action::NextLink);
+        keymap.bind_screens(&[Article], &["N"], Action::PreviousLink);
+        keymap.bind_screens(&[Article], &["ctrl-o"], Action::Back);
+        keymap.bind_screens(&[Article], &["q"], Action::OpenMenu);
+        keymap.bind_screens(&[Article], &["g t"], Action::NextTab);
+        keymap.bind_screens(&[Article], &["g T"], Action::PreviousTab);
+        keymap.bind_screens(&[Article], &["ctrl-w v"], Action::SplitVertical);
+        keymap.bind_screens(&[Article], &["ctrl-w s"], Action::SplitHorizontal);
+        keymap.bind_screens(&[Article], &["ctrl-w w"], Action::SwitchPane);
+        keymap.bind_screens(&[Article], &["ctrl-w q"], Action::ClosePane);
+
+        keymap
+    }
+
+    fn emacs_preset() -> Keymap {
+        use AppState::*;
+        let mut keymap = Keymap::default_preset();
+
+        // Ctrl-P moves up in emacs, the palette is on M-x instead
+        keymap.unbind_key("ctrl-p");
+        keymap.bind_screens(&ALL_SCREENS, &["alt-x"], Action::CommandPalette);
+        keymap.bind(KeyContext::TextInput, &["alt-x"], Action::CommandPalette);
+
+        keymap.bind(KeyContext::TextInput, &["ctrl-b"], Action::CursorLeft);
+        keymap.bind(KeyContext::TextInput, &["ctrl-f"], Action::CursorRight);
+        keymap.bind(KeyContext::TextInput, &["ctrl-a"], Action::CursorToStart);
+        keymap.bind(KeyContext::TextInput, &["ctrl-e"], Action::CursorToEnd);
+        keymap.bind(KeyContext::TextInput, &["ctrl-h"], Action::DeleteBackward);
+
+        keymap.bind_screens(&ALL_SCREENS, &["ctrl-x ctrl-c"], Action::Quit);
+        keymap.bind_screens(&[Search, Article], &["ctrl-g"], Action::OpenMenu);
+        keymap.bind_screens(&MENUS, &["ctrl-g"], Action::Close);
+        keymap.bind_screens(&[Title, Search, Article], &["ctrl-p"], Action::ScrollUp);
+        keymap.bind_screens(&[Title, Search, Article], &["ctrl-n"], Action::ScrollDown);
+        keymap.bind_screens(&MENUS, &["ctrl-p"], Action::ScrollUp);
+        keymap.bind_screens(&MENUS, &["ctrl-n"], Action::ScrollDown);
+        keymap.bind_screens(&[Settings, Search], &["ctrl-b"], Action::PreviousChoice);
+        keymap.bind_screens(&[Settings, Search], &["ctrl-f"], Action::NextChoice);
+
+        keymap.bind_screens(&[Article], &["alt-v"], Action::PageUp);
+        keymap.bind_screens(&[Article], &["ctrl-v"], Action::PageDown);
+        keymap.bind_screens(&[Article], &["alt-<"], Action::ScrollToTop);
+        keymap.bind_screens(&[Article], &["alt->"], Action::ScrollToBottom);
+        keymap.bind_screens(&[Article], &["alt-n"], Action::NextLink);
+        keymap.bind_screens(&[Article], &["alt-p"], Action::PreviousLink);
+        keymap.bind_screens(&[Article], &["alt-left"], Action::Back);
+        keymap.bind_screens(&[Article], &["ctrl-x k"], Action::CloseTab);
+        keymap.bind_screens(&[Article], &["ctrl-x 3"], Action::SplitVertical);
+        keymap.bind_screens(&[Article], &["ctrl-x 2"], Action::SplitHorizontal);
+        keymap.bind_screens(&[Article], &["ctrl-x o"], Action::SwitchPane);
+        keymap.bind_screens(&[Article], &["ctrl-x 0"], Action::ClosePane);
+
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str) -> KeyPress {
+        KeyPress::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(key("G").code, KeyCode::Char('G'));
+        assert_eq!(key("G").modifiers, KeyModifiers::NONE);
+        assert_eq!(
+            key("ctrl-d"),
+            KeyPress {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+            }
+        );
+        assert_eq!(key("alt-<").modifiers, KeyModifiers::ALT);
+        assert_eq!(key("ctrl--").code, KeyCode::Char('-'));
+        assert_eq!(key("shift-tab").code, KeyCode::BackTab);
+        assert_eq!(key("PageDown").code, KeyCode::PageDown);
+        assert_eq!(key("f12").code, KeyCode::F(12));
+        assert_eq!(key("space").typed_char(), Some(' '));
+        assert_eq!(key("ctrl-p").typed_char(), None);
+
+        for text in ["ctrl-alt-x", "shift-tab", "f3", "alt-<", "space"] {
+            assert_eq!(key(text).to_string(), text);
+        }
+
+        assert!(KeyPress::parse("hyper-x").is_err());
+        assert!(KeyPress::parse("f25").is_err());
+        assert!(KeyPress::parse("enterr").is_err());
+        assert!(parse_sequence("  ").is_err());
+    }
+
+    #[test]
+    fn resolves_a_sequence_of_keys() {
+        let keymap = Keymap::build("vim", &HashMap::new()).unwrap();
+        let mut pending = PendingKeys::default();
+        let now = Instant::now();
+        let mut press =
+            |text: &str| keymap.resolve(AppState::Article, false, &mut pending, key(text), now);
+
+        assert_eq!(press("g"), KeyResult::Pending);
+        assert_eq!(press("g"), KeyResult::Action(Action::ScrollToTop));
+        assert_eq!(press("g"), KeyResult::Pending);
+        assert_eq!(press("t"), KeyResult::Action(Action::NextTab));
+        // "g j" isn't bound, so "j" is looked up by itself
+        assert_eq!(press("g"), KeyResult::Pending);
+        assert_eq!(press("j"), KeyResult::Action(Action::ScrollDown));
+        assert_eq!(press("z"), KeyResult::Unbound);
+    }
+
+    #[test]
+    fn drops_the_start_of_a_sequence_after_a_pause() {
+        let keymap = Keymap::build("vim", &HashMap::new()).unwrap();
+        let mut pending = PendingKeys::default();
+        let start = Instant::now();
+        let mut press = |text: &str, at: Instant| {
+            keymap.resolve(AppState::Article, false, &mut pending, key(text), at)
+        };
+
+        assert_eq!(press("g", start), KeyResult::Pending);
+        let later = start + SEQUENCE_TIMEOUT + Duration::from_millis(1);
+        assert_eq!(press("g", later), KeyResult::Pending);
+        assert_eq!(press("g", later), KeyResult::Action(Action::ScrollToTop));
+    }
+
+    #[test]
+    fn leaves_plain_characters_to_a_text_box() {
+        let keymap = Keymap::default();
+        let mut pending = PendingKeys::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            keymap.resolve(AppState::Search, true, &mut pending, key("?"), now),
+            KeyResult::Unbound
+        );
+        assert_eq!(
+            keymap.resolve(AppState::Search, true, &mut pending, key("f3"), now),
+            KeyResult::Action(Action::Help)
+        );
+        assert_eq!(
+            keymap.resolve(AppState::Search, false, &mut pending, key("?"), now),
+            KeyResult::Action(Action::Help)
+        );
+    }
+
+    #[test]
+    fn opens_help_and_the_palette_while_typing() {
+        for preset in Keymap::PRESETS {
+            let keymap = Keymap::build(preset, &HashMap::new()).unwrap();
+            for action in [Action::Help, Action::CommandPalette] {
+                for state in [AppState::Title, AppState::Search] {
+                    let text = keymap.key_for(state, true, action).unwrap();
+                    let mut pending = PendingKeys::default();
+                    assert_eq!(
+                        keymap.resolve(state, true, &mut pending, key(&text), Instant::now()),
+                        KeyResult::Action(action),
+                        "{text} in the {preset} keymap"
+                    );
+                }
+                let typing = keymap.bindings_in(KeyContext::TextInput);
+                assert!(typing.iter().any(|(bound, _)| *bound == action));
+            }
+        }
+    }
+
+    #[test]
+    fn applies_overrides_on_top_of_a_preset() {
+        let overrides = HashMap::from([(
+            String::from("article"),
+            HashMap::from([(String::from("scroll-down"), vec![String::from("ctrl-j")])]),
+        )]);
+        let keymap = Keymap::build("vim", &overrides).unwrap();
+        let mut pending = PendingKeys::default();
+        let now = Instant::now();
+        let mut press =
+            |text: &str| keymap.resolve(AppState::Article, false, &mut pending, key(text), now);
+
+        assert_eq!(press("ctrl-j"), KeyResult::Action(Action::ScrollDown));
+        // Overriding an action replaces its keys from the preset
+        assert_eq!(press("j"), KeyResult::Unbound);
+        assert_eq!(press("down"), KeyResult::Unbound);
+        // and leaves the rest of the preset alone
+        assert_eq!(press("k"), KeyResult::Action(Action::ScrollUp));
+    }
+
+    #[test]
+    fn rejects_unknown_names_and_keys() {
+        assert!(Keymap::build("nano", &HashMap::new()).is_err());
+
+        let overrides = HashMap::from([
+            (
+                String::from("articel"),
+                HashMap::from([(String::from("back"), vec![String::from("h")])]),
+            ),
+            (
+                String::from("article"),
+                HashMap::from([
+                    (String::from("go-back"), vec![String::from("h")]),
+                    (String::from("back"), vec![String::from("ctrl-")]),
+                ]),
+            ),
+        ]);
+        let Err(mut errors) = Keymap::build("default", &overrides) else {
+            panic!("the overrides should be rejected");
+        };
+        errors.sort();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("keys.article.back: "));
+        assert!(errors[1].starts_with("keys.article: there is no action called \"go-back\""));
+        assert!(errors[2].starts_with("keys: there are no key bindings called \"articel\""));
+    }
+}
@@ -1,7 +1,15 @@
 pub mod app;
+pub mod bookmarks;
 pub mod caching;
+pub mod config;
+pub mod history;
+pub mod images;
+pub mod keymap;
 pub mod parsing;
+pub mod positions;
+pub mod session;
 pub mod styles;
+pub mod text_input;
 pub mod ui;
 pub mod utils;
 pub mod widgets;
@@ -1,173 +1,145 @@
 mod app;
+mod bookmarks;
 mod caching;
+mod config;
+mod history;
+mod images;
+mod keymap;
 mod parsing;
+mod positions;
+mod session;
 mod styles;
+mod text_input;
 mod ui;
 mod utils;
 mod widgets;
 mod wikipedia;
 
 use crate::app::App;
-use app::{ActionMenu, AppState, CursorDirection, ScrollDirection, TypeableState};
-use caching::CachingSession;
+use config::{CliCommand, Config, Setting};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use htmd::HtmlToMarkdown;
-use std::{
-    error::Error,
-    io::{Read, Write},
-    time::{Duration, Instant},
-};
-use std::{fs::File, io};
+use images::GraphicsOutput;
+use std::{env, error::Error, io, path::PathBuf, process, time::Duration};
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
 
-const APP_REFRESH_TIME_MILLIS: u64 = 16;
+/// The settings wik starts with, and where they came from
+struct LoadedConfig {
+    config: Config,
+    /// The config file alone, without the command line options
+    file_config: Config,
+    overridden_settings: Vec<Setting>,
+    path: PathBuf,
+}
+
+/// Reads the config file and applies the command line on top of it.
+/// `--help` and `--version` are answered here and exit straight away.
+fn load_config() -> Result<LoadedConfig, String> {
+    let (config_path, overrides) = match config::parse_args(env::args().skip(1))? {
+        CliCommand::Help => {
+            println!("{}", config::USAGE);
+            process::exit(0);
+        }
+        CliCommand::Version => {
+            println!("wik {}", env!("CARGO_PKG_VERSION"));
+            process::exit(0);
+        }
+        CliCommand::Run {
+            config_path,
+            overrides,
+        } => (config_path, overrides),
+    };
+
+    let file_config = Config::load(&config_path)
+        .map_err(|e| format!("couldn't read {}: {e}", config_path.display()))?;
+    let mut config = file_config.clone();
+    config.apply_overrides(&overrides)?;
+    config.validate().map_err(|errors| {
+        format!(
+            "invalid settings (from {} and the command line):\n  {}",
+            config_path.display(),
+            errors.join("\n  ")
+        )
+    })?;
+
+    Ok(LoadedConfig {
+        config,
+        file_config,
+        overridden_settings: overrides.into_iter().map(|(setting, _)| setting).collect(),
+        path: config_path,
+    })
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // Bad settings are reported before the terminal is taken over
+    let loaded = match load_config() {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("wik: {message}");
+            eprintln!("Run `wik --help` for the available options.");
+            process::exit(2);
+        }
+    };
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut mouse_captured = loaded.config.mouse;
+    if mouse_captured {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
+    let mut app = App::new(loaded.config, loaded.path);
+    app.file_config = loaded.file_config;
+    app.overridden_settings = loaded.overridden_settings;
     app.is_running = true;
+    let mut graphics_output = GraphicsOutput::new(app.graphics_protocol);
 
     // Main loop
     loop {
         if !app.is_running {
             break;
         }
+        app.update();
+        graphics_output.protocol = app.graphics_protocol;
         terminal.draw(|f| ui::draw(f, &app))?;
 
-        if event::poll(Duration::from_millis(APP_REFRESH_TIME_MILLIS))? {
-            if let Event::Key(key) = event::read()? {
-                match app.state {
-                    AppState::Title => match key.code {
-                        KeyCode::Enter => {
-                            app.state = AppState::Search;
-                        }
-                        _ => {}
-                    },
-                    AppState::Search => match key.code {
-                        KeyCode::Esc => {
-                            // Enter Escape menu, from where one can exit normally
-                            app.state = AppState::SearchMenu;
-                        }
-                        KeyCode::F(1) => {
-                            // Just-in-case exit
-                            app.is_running = false
-                        }
-                        KeyCode::Char(c) => {
-                            // Append character to input
-                            app.search.type_char(c);
-                        }
-                        KeyCode::Backspace => {
-                            app.search.backspace();
-                        }
-                        KeyCode::Left if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            app.search.move_cursor_to_start();
-                        }
-                        KeyCode::Left => {
-                            app.search.move_cursor_one_step(CursorDirection::LEFT);
-                        }
-                        KeyCode::Right if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            app.search.move_cursor_to_end();
-                        }
-                        KeyCode::Right => {
-                            app.search.move_cursor_one_step(CursorDirection::RIGHT);
-                        }
-                        KeyCode::Enter => {
-                            if app.search.text_box_is_highlighted {
-                                app.load_wikipedia_search_query();
-                                app.search.text_box_is_highlighted = false;
-                            } else {
-                                app.view_selected_article();
-                            }
-                        }
-                        KeyCode::F(2) => {
-                            app.view_selected_article();
-                        }
-                        KeyCode::Up => {
-                            app.search.scroll_results(ScrollDirection::UP);
-                        }
-                        KeyCode::Down => {
-                            app.search.scroll_results(ScrollDirection::DOWN);
-                        }
-
-                        _ => {}
-                    },
-                    AppState::SearchMenu => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Search;
-                        }
-
-                        KeyCode::Up => {
-                            app.search_menu.scroll(ScrollDirection::UP);
-                        }
-
-                        KeyCode::Down => {
-                            app.search_menu.scroll(ScrollDirection::DOWN);
-                        }
-
-                        KeyCode::Enter => {
-                            app.search_menu.get_selected_action()(&mut app);
-                        }
-
-                        KeyCode::F(1) => {
-                            // Just-in-case exit
-                            app.is_running = false;
-                        }
-                        _ => {}
-                    },
-                    AppState::Credit => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::SearchMenu;
-                        }
-
-                        KeyCode::Up => {
-                            app.credit.scroll(ScrollDirection::UP);
-                        }
-                        KeyCode::Down => {
-                            app.credit.scroll(ScrollDirection::DOWN);
-                        }
-
-                        KeyCode::Enter => {
-                            app.credit.get_selected_action()(&mut app);
-                        }
-
-                        _ => {}
-                    },
-                    AppState::Article => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::ArticleMenu;
-                        }
-                        _ => {}
-                    },
-                    AppState::ArticleMenu => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Article;
-                        }
-                        KeyCode::Up => {
-                            app.article_menu.scroll(ScrollDirection::UP);
-                        }
+        let image_placements = app.image_placements.borrow().clone();
+        if graphics_output.needs_update(&image_placements) {
+            if graphics_output.needs_full_redraw() {
+                terminal.clear()?;
+                terminal.draw(|f| ui::draw(f, &app))?;
+            }
+            graphics_output.write_placements(
+                terminal.backend_mut(),
+                image_placements,
+                &app.images,
+                app.theme.background_rgb(),
+            )?;
+        }
 
-                        KeyCode::Down => {
-                            app.article_menu.scroll(ScrollDirection::DOWN);
-                        }
+        if event::poll(Duration::from_millis(app.config.refresh_time_millis))? {
+            match event::read()? {
+                Event::Key(key) => app.handle_key(key),
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                _ => {}
+            }
+        }
 
-                        KeyCode::Enter => {
-                            app.article_menu.get_selected_action()(&mut app);
-                        }
-                        _ => {}
-                    },
-                    _ => app.is_running = false,
-                }
+        // The mouse setting can be changed from the settings screen
+        if app.config.mouse != mouse_captured {
+            mouse_captured = app.config.mouse;
+            if mouse_captured {
+                execute!(terminal.backend_mut(), EnableMouseCapture)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)?;
             }
         }
     }
@@ -180,61 +152,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         DisableMouseCapture
     )?;
 
-    CachingSession::clear_caches()?;
-
-    /*
-    println!("Getting your page...");
-    let start_time = Instant::now();
-
-    let url = "https://en.wikipedia.org/w/rest.php/v1/page/First 100 days of the first Donald Trump presidency/html";
-
-    // "https://en.wikipedia.org/w/api.php?action=parse&page=Presidency_of_Donald_Trump&prop=text&format=json";
-    // let response = reqwest::blocking::get(url)?.json::<WikiPageResponse>()?;
-    let response = reqwest::blocking::get(url)?;
-    let html_content = response.text()?;
-    println!(
-        "Download done in {} seconds",
-        start_time.elapsed().as_millis()
-    );
-
-    // Print the extracted text content
-    // println!("Title: {}", response.parse.title);
-    // println!("Page ID: {}", response.parse.pageid);
-
-    let converter = HtmlToMarkdown::builder()
-        .skip_tags(vec!["script", "style", "table", "sup"])
-        .build();
-
-    match converter.convert(&html_content) {
-        Ok(content) => {
-            // println!("Content:\n{}", content);
-            let mut file = File::create("./earth.md")?;
-            file.write_all(content.as_bytes())?;
-        }
-        Err(e) => {
-            println!("uhoh!!!\n{}", e);
-        }
+    if let Err(e) = app.save_reading_positions() {
+        eprintln!("wik: couldn't save reading positions: {e}");
     }
-    println!(
-        "Conversion done in {} seconds",
-        start_time.elapsed().as_millis()
-    );
-
-    let mut markdown_text: String = String::new();
-    File::open("./earth.md")?.read_to_string(&mut markdown_text)?;
-    let mut spans = parsing::parse_markdown(&markdown_text);
-
-    println!(
-        "Parsing done in {} seconds",
-        start_time.elapsed().as_millis()
-    );
-
-    spans = wikipedia::remove_unnecessary_spans(spans);
-
-    for span in spans.iter() {
-        println!("{}", span);
+    if let Err(e) = app.save_session() {
+        eprintln!("wik: couldn't save the open tabs: {e}");
     }
-    */
+    app.cache.lock().unwrap().clear_caches()?;
 
     Ok(())
 }
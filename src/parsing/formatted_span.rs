@@ -2,14 +2,32 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+use crate::parsing::Link;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FormattedSpan {
     pub index: usize,
     pub text: String,
     pub is_heading: bool,
     pub heading_level: usize,
-    pub link: Option<String>,
+    pub link: Option<Link>,
     pub is_break: bool,
+    pub is_bold: bool,
+    pub is_italic: bool,
+    pub is_citation: bool,
+    pub section_id: Option<String>,
+    pub image: Option<ArticleImage>,
+    pub is_caption: bool,
+    /// The LaTeX a formula was rendered from; `text` holds the Unicode rendering
+    pub math_source: Option<String>,
+}
+
+/// An image in the article, with the size it is displayed at on Wikipedia
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ArticleImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Display for FormattedSpan {
@@ -20,14 +38,24 @@ impl Display for FormattedSpan {
                 "index: {}, text: {}, {} heading",
                 self.index, self.text, self.heading_level
             )
-        } else if let Some(link_text) = &self.link {
+        } else if let Some(link) = &self.link {
             write!(
                 f,
-                "index: {}, text: {}, link: {}",
-                self.index, self.text, link_text
+                "index: {}, text: {}, link: {:?} {}",
+                self.index, self.text, link.kind, link.target
             )
         } else if self.is_break {
             write!(f, "index: {}, line break", self.index)
+        } else if let Some(image) = &self.image {
+            write!(f, "index: {}, image: {}", self.index, image.url)
+        } else if let Some(math_source) = &self.math_source {
+            write!(
+                f,
+                "index: {}, math: {} ({})",
+                self.index, self.text, math_source
+            )
+        } else if self.is_citation {
+            write!(f, "index: {}, citation: {}", self.index, self.text)
         } else {
             write!(f, "index: {}, text: {}", self.index, self.text)
         }
@@ -43,6 +71,13 @@ impl Default for FormattedSpan {
             heading_level: 0,
             link: None,
             is_break: false,
+            is_bold: false,
+            is_italic: false,
+            is_citation: false,
+            section_id: None,
+            image: None,
+            is_caption: false,
+            math_source: None,
         }
     }
 }
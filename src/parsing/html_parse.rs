@@ -0,0 +1,601 @@
+use crate::parsing::math::{latex_to_unicode, strip_style_wrapper};
+use crate::parsing::{ArticleImage, FormattedSpan, Link};
+use html5ever::tendril::TendrilSink;
+use html5ever::{parse_document, Attribute};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use std::ops::Range;
+
+// Elements that never hold readable article text
+const SKIPPED_TAGS: [&str; 8] = [
+    "head", "script", "style", "link", "meta", "noscript", "audio", "video",
+];
+
+// Parsoid/MediaWiki classes for navigation boxes, edit links and other page furniture
+const SKIPPED_CLASSES: [&str; 7] = [
+    "navbox",
+    "navbox-styles",
+    "mw-editsection",
+    "mw-empty-elt",
+    "noprint",
+    "metadata",
+    "shortdescription",
+];
+
+const BLOCK_TAGS: [&str; 13] = [
+    "p",
+    "div",
+    "section",
+    "blockquote",
+    "ul",
+    "ol",
+    "dl",
+    "table",
+    "caption",
+    "pre",
+    "center",
+    "hr",
+    "figure",
+];
+
+const CELL_SEPARATOR: &str = " │ ";
+
+// Images smaller than this (flags, icons) are left out rather than drawn as a smudge
+const MIN_IMAGE_SIZE: u32 = 50;
+
+/// Converts the Parsoid HTML served by the REST API straight into the spans `draw_article` renders.
+pub fn parse_html(html: &str) -> Vec<FormattedSpan> {
+    let dom = parse_document(RcDom::default(), Default::default()).one(html);
+    let mut converter = HtmlConverter::default();
+    converter.walk(&dom.document);
+    converter.finish()
+}
+
+/// A search snippet as plain text, with the byte ranges of `text` that matched the query
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Parses the HTML of a search snippet, where the API marks matches with the `searchmatch` class.
+/// Entities are decoded and any other markup is dropped.
+pub fn parse_snippet(html: &str) -> Snippet {
+    let dom = parse_document(RcDom::default(), Default::default()).one(html);
+    let mut snippet = Snippet::default();
+    collect_snippet(&dom.document, false, &mut snippet);
+    snippet
+}
+
+fn collect_snippet(node: &Handle, highlighted: bool, snippet: &mut Snippet) {
+    let highlighted = match &node.data {
+        NodeData::Text { contents } => {
+            let start = snippet.text.len();
+            snippet
+                .text
+                .push_str(&collapse_whitespace(&contents.borrow()));
+            let end = snippet.text.len();
+            if highlighted && end > start {
+                // Neighbouring matches read as one
+                match snippet.highlights.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => snippet.highlights.push(start..end),
+                }
+            }
+            return;
+        }
+        NodeData::Element { attrs, .. } => highlighted || has_class(&attrs.borrow(), "searchmatch"),
+        NodeData::Document => highlighted,
+        _ => return,
+    };
+    for child in node.children.borrow().iter() {
+        collect_snippet(child, highlighted, snippet);
+    }
+}
+
+#[derive(Default)]
+struct HtmlConverter {
+    spans: Vec<FormattedSpan>,
+    bold_depth: usize,
+    italic_depth: usize,
+    citation_depth: usize,
+    caption_depth: usize,
+    cell_depth: usize,
+    link: Option<Link>,
+    // One entry per open list; `Some(n)` is an ordered list on item n
+    lists: Vec<Option<usize>>,
+    at_line_start: bool,
+}
+
+impl HtmlConverter {
+    fn walk(&mut self, node: &Handle) {
+        match &node.data {
+            NodeData::Document => self.walk_children(node),
+            NodeData::Text { contents } => self.push_text(&contents.borrow()),
+            NodeData::Element { name, attrs, .. } => {
+                let tag = name.local.as_ref().to_string();
+                self.walk_element(node, &tag, &attrs.borrow());
+            }
+            _ => {}
+        }
+    }
+
+    fn walk_children(&mut self, node: &Handle) {
+        for child in node.children.borrow().iter() {
+            self.walk(child);
+        }
+    }
+
+    fn walk_element(&mut self, node: &Handle, tag: &str, attrs: &[Attribute]) {
+        // Checked before skipping, as the MathML inside is hidden with `display: none`
+        if is_math(tag, attrs) {
+            if let Some(latex) = math_source(node) {
+                self.push_math(latex, is_display_math(node));
+            }
+            return;
+        }
+        if SKIPPED_TAGS.contains(&tag) || is_skipped(attrs) {
+            return;
+        }
+
+        match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let heading_level = tag[1..].parse().unwrap_or(2);
+                let text = collapse_whitespace(&text_content(node));
+                if !text.trim().is_empty() {
+                    self.paragraph_gap();
+                    self.push_span(FormattedSpan {
+                        text: text.trim().to_string(),
+                        is_heading: true,
+                        heading_level,
+                        section_id: attribute(attrs, "id").map(str::to_string),
+                        ..Default::default()
+                    });
+                    self.paragraph_gap();
+                }
+            }
+            "br" => self.end_line(),
+            "img" => self.push_image(attrs),
+            "figcaption" => {
+                self.force_line_break();
+                self.caption_depth += 1;
+                self.walk_children(node);
+                self.caption_depth -= 1;
+                self.force_line_break();
+            }
+            "th" => {
+                self.start_cell();
+                self.bold_depth += 1;
+                self.walk_children(node);
+                self.bold_depth -= 1;
+                self.cell_depth -= 1;
+            }
+            "b" | "strong" => {
+                self.bold_depth += 1;
+                self.walk_children(node);
+                self.bold_depth -= 1;
+            }
+            "i" | "em" | "cite" | "var" => {
+                self.italic_depth += 1;
+                self.walk_children(node);
+                self.italic_depth -= 1;
+            }
+            "sup" if is_citation(attrs) => {
+                self.citation_depth += 1;
+                self.walk_children(node);
+                self.citation_depth -= 1;
+            }
+            "a" => {
+                let previous_link = self.link.take();
+                self.link = link_target(attrs).or(previous_link.clone());
+                self.walk_children(node);
+                self.link = previous_link;
+            }
+            "li" => {
+                self.end_line();
+                let depth = self.lists.len().max(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(item_number)) => {
+                        *item_number += 1;
+                        format!("{item_number}. ")
+                    }
+                    _ => String::from("• "),
+                };
+                self.push_plain(format!("{}{}", "  ".repeat(depth - 1), marker));
+                self.walk_children(node);
+                self.end_line();
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.paragraph_gap();
+                } else {
+                    self.end_line();
+                }
+                self.lists.push(if tag == "ol" { Some(0) } else { None });
+                self.walk_children(node);
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.paragraph_gap();
+                }
+            }
+            "dt" => {
+                self.end_line();
+                self.bold_depth += 1;
+                self.walk_children(node);
+                self.bold_depth -= 1;
+                self.end_line();
+            }
+            "dd" => {
+                self.end_line();
+                self.push_plain(String::from("    "));
+                self.walk_children(node);
+                self.end_line();
+            }
+            "tr" => {
+                self.end_line();
+                self.walk_children(node);
+                self.end_line();
+            }
+            "td" => {
+                self.start_cell();
+                self.walk_children(node);
+                self.cell_depth -= 1;
+            }
+            _ if BLOCK_TAGS.contains(&tag) => {
+                if self.cell_depth > 0 {
+                    self.push_text(" ");
+                    self.walk_children(node);
+                    self.push_text(" ");
+                } else {
+                    self.paragraph_gap();
+                    self.walk_children(node);
+                    self.paragraph_gap();
+                }
+            }
+            _ => self.walk_children(node),
+        }
+    }
+
+    fn start_cell(&mut self) {
+        if self.cell_depth == 0 && !self.at_line_start && !self.spans.is_empty() {
+            self.push_plain(String::from(CELL_SEPARATOR));
+        }
+        self.cell_depth += 1;
+    }
+
+    fn push_text(&mut self, raw_text: &str) {
+        let mut text = collapse_whitespace(raw_text);
+        if self.at_line_start || self.ends_with_space() {
+            text = text.trim_start().to_string();
+        }
+        if text.is_empty() {
+            return;
+        }
+        self.push_span(FormattedSpan {
+            text,
+            is_bold: self.bold_depth > 0,
+            is_italic: self.italic_depth > 0,
+            is_citation: self.citation_depth > 0,
+            is_caption: self.caption_depth > 0,
+            link: self.link.clone(),
+            ..Default::default()
+        });
+    }
+
+    fn push_image(&mut self, attrs: &[Attribute]) {
+        let size = |name| attribute(attrs, name).and_then(|value| value.parse::<u32>().ok());
+        let (Some(src), Some(width), Some(height)) =
+            (attribute(attrs, "src"), size("width"), size("height"))
+        else {
+            return;
+        };
+        if width < MIN_IMAGE_SIZE || height < MIN_IMAGE_SIZE {
+            return;
+        }
+
+        let url = if src.starts_with("//") {
+            format!("https:{src}")
+        } else {
+            src.to_string()
+        };
+        self.force_line_break();
+        self.push_span(FormattedSpan {
+            image: Some(ArticleImage { url, width, height }),
+            ..Default::default()
+        });
+        self.force_line_break();
+    }
+
+    fn push_math(&mut self, latex: String, is_display: bool) {
+        let span = FormattedSpan {
+            text: latex_to_unicode(&latex),
+            math_source: Some(strip_style_wrapper(&latex).to_string()),
+            link: self.link.clone(),
+            ..Default::default()
+        };
+        if is_display && self.cell_depth == 0 {
+            // Display formulas get a line of their own, indented unless something (like a
+            // `<dd>`) already started an indented line for them
+            if !self.current_line_is_blank() {
+                self.end_line();
+                self.push_plain(String::from("    "));
+            }
+            self.push_span(span);
+            self.end_line();
+        } else {
+            self.push_span(span);
+        }
+    }
+
+    /// Like `end_line`, but also breaks inside table cells, for images and their captions
+    fn force_line_break(&mut self) {
+        if !self.at_line_start && !self.spans.is_empty() {
+            self.push_span(FormattedSpan {
+                is_break: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn push_plain(&mut self, text: String) {
+        self.push_span(FormattedSpan {
+            text,
+            ..Default::default()
+        });
+    }
+
+    fn push_span(&mut self, mut span: FormattedSpan) {
+        span.index = self.spans.len();
+        self.at_line_start = span.is_break;
+        self.spans.push(span);
+    }
+
+    fn current_line_is_blank(&self) -> bool {
+        self.spans
+            .iter()
+            .rev()
+            .take_while(|span| !span.is_break)
+            .all(|span| span.text.trim().is_empty() && span.image.is_none())
+    }
+
+    fn ends_with_space(&self) -> bool {
+        match self.spans.last() {
+            Some(span) => span.text.ends_with(' '),
+            None => true,
+        }
+    }
+
+    /// Ends the current line, unless it is already empty
+    fn end_line(&mut self) {
+        if self.cell_depth > 0 {
+            self.push_text(" ");
+        } else if !self.at_line_start && !self.spans.is_empty() {
+            self.push_span(FormattedSpan {
+                is_break: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Ends the current line and leaves one blank line after it
+    fn paragraph_gap(&mut self) {
+        if self.cell_depth > 0 {
+            self.push_text(" ");
+            return;
+        }
+        self.end_line();
+        let ends_with_blank_line = self.spans.len() >= 2
+            && self.spans[self.spans.len() - 2..]
+                .iter()
+                .all(|span| span.is_break);
+        if !self.spans.is_empty() && !ends_with_blank_line {
+            self.push_span(FormattedSpan {
+                is_break: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn finish(mut self) -> Vec<FormattedSpan> {
+        while self.spans.last().is_some_and(|span| span.is_break) {
+            self.spans.pop();
+        }
+        self.spans
+    }
+}
+
+fn attribute<'a>(attrs: &'a [Attribute], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|attr| attr.name.local.as_ref() == name)
+        .map(|attr| attr.value.as_ref())
+}
+
+fn has_class(attrs: &[Attribute], class_name: &str) -> bool {
+    attribute(attrs, "class")
+        .is_some_and(|classes| classes.split_whitespace().any(|class| class == class_name))
+}
+
+fn is_skipped(attrs: &[Attribute]) -> bool {
+    let hidden_by_style = attribute(attrs, "style")
+        .is_some_and(|style| style.replace(' ', "").contains("display:none"));
+    hidden_by_style
+        || SKIPPED_CLASSES
+            .iter()
+            .any(|class_name| has_class(attrs, class_name))
+}
+
+fn is_citation(attrs: &[Attribute]) -> bool {
+    has_class(attrs, "reference")
+        || attribute(attrs, "typeof").is_some_and(|typeof_| typeof_.contains("mw:Extension/ref"))
+}
+
+fn is_math(tag: &str, attrs: &[Attribute]) -> bool {
+    tag == "math"
+        || has_class(attrs, "mwe-math-element")
+        || attribute(attrs, "typeof").is_some_and(|typeof_| typeof_.contains("mw:Extension/math"))
+}
+
+/// The LaTeX behind a formula: Parsoid keeps it in the `alttext` of the `<math>` element and
+/// in the `alt` of the fallback image
+fn math_source(node: &Handle) -> Option<String> {
+    if let NodeData::Element { name, attrs, .. } = &node.data {
+        let attrs = attrs.borrow();
+        let source = match name.local.as_ref() {
+            "math" => attribute(&attrs, "alttext"),
+            "img" => attribute(&attrs, "alt"),
+            _ => None,
+        };
+        if let Some(source) = source {
+            return Some(source.to_string());
+        }
+    }
+    node.children.borrow().iter().find_map(math_source)
+}
+
+fn is_display_math(node: &Handle) -> bool {
+    match &node.data {
+        NodeData::Element { name, attrs, .. } => {
+            let attrs = attrs.borrow();
+            attribute(&attrs, "display") == Some("block")
+                || (name.local.as_ref() != "math"
+                    && attribute(&attrs, "class").is_some_and(|classes| {
+                        classes.contains("mwe-math-mathml-display")
+                            || classes.contains("mwe-math-fallback-image-display")
+                    }))
+                || node.children.borrow().iter().any(is_display_math)
+        }
+        _ => false,
+    }
+}
+
+fn link_target(attrs: &[Attribute]) -> Option<Link> {
+    let href = attribute(attrs, "href")?;
+    Some(Link::from_attributes(
+        href,
+        attribute(attrs, "rel"),
+        attribute(attrs, "class"),
+        attribute(attrs, "title"),
+    ))
+}
+
+fn text_content(node: &Handle) -> String {
+    match &node.data {
+        NodeData::Text { contents } => contents.borrow().to_string(),
+        NodeData::Element { attrs, .. } if is_skipped(&attrs.borrow()) => String::new(),
+        _ => node
+            .children
+            .borrow()
+            .iter()
+            .map(text_content)
+            .collect::<String>(),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut last_was_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_was_space {
+                collapsed.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            collapsed.push(c);
+            last_was_space = false;
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::LinkKind;
+
+    /// The text of each line, as `draw_article` would break them
+    fn lines(spans: &[FormattedSpan]) -> Vec<String> {
+        let mut lines = vec![String::new()];
+        for span in spans {
+            match span.is_break {
+                true => lines.push(String::new()),
+                false => lines.last_mut().unwrap().push_str(&span.text),
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn joins_adjacent_matches() {
+        let snippet = parse_snippet(
+            r#"<span class="searchmatch">foo</span><span class="searchmatch">bar</span> baz"#,
+        );
+        assert_eq!(snippet.text, "foobar baz");
+        assert_eq!(snippet.highlights, vec![0..6]);
+
+        let snippet = parse_snippet(
+            r#"<span class="searchmatch">a</span> <span class="searchmatch">b</span>"#,
+        );
+        assert_eq!(snippet.highlights, vec![0..1, 2..3]);
+    }
+
+    #[test]
+    fn decodes_entities_in_snippets() {
+        let snippet = parse_snippet(
+            r#"say &quot;<span class="searchmatch">hi</span>&quot; it&#039;s &amp; <b>bold</b>"#,
+        );
+        assert_eq!(snippet.text, r#"say "hi" it's & bold"#);
+        assert_eq!(snippet.highlights, vec![5..7]);
+    }
+
+    #[test]
+    fn highlights_byte_ranges_around_non_ascii_text() {
+        let snippet = parse_snippet(r#"Café <span class="searchmatch">Müller</span> in 日本"#);
+        assert_eq!(snippet.text, "Café Müller in 日本");
+        let highlighted: Vec<&str> = snippet
+            .highlights
+            .iter()
+            .map(|range| &snippet.text[range.clone()])
+            .collect();
+        assert_eq!(highlighted, vec!["Müller"]);
+    }
+
+    #[test]
+    fn converts_an_article() {
+        let spans = parse_html(
+            r#"<html><head><style>p { color: red }</style></head><body>
+            <h2 id="History">History</h2>
+            <p>Some <b>bold</b> and <i>it</i> <a href="./Kyoto" rel="mw:WikiLink">Kyoto</a><sup class="reference">[1]</sup>.</p>
+            <div class="navbox">Navigation</div>
+            <ul><li>one</li><li>two</li></ul>
+            <p>x <span class="mwe-math-element"><math alttext="{\displaystyle x^{2}}"></math></span></p>
+            </body></html>"#,
+        );
+        assert_eq!(
+            lines(&spans),
+            vec![
+                "History",
+                "",
+                "Some bold and it Kyoto[1].",
+                "",
+                "• one",
+                "• two",
+                "",
+                "x x²"
+            ]
+        );
+
+        let span = |text: &str| spans.iter().find(|span| span.text == text).unwrap();
+        assert!(span("History").is_heading);
+        assert_eq!(span("History").section_id.as_deref(), Some("History"));
+        assert!(span("bold").is_bold);
+        assert!(span("it").is_italic);
+        assert!(span("[1]").is_citation);
+        let link = span("Kyoto").link.as_ref().unwrap();
+        assert_eq!(
+            (link.target.as_str(), &link.kind),
+            ("Kyoto", &LinkKind::Internal)
+        );
+        assert_eq!(span("x²").math_source.as_deref(), Some("x^{2}"));
+    }
+}
@@ -0,0 +1,129 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::parsing::{ArticleImage, FormattedSpan};
+
+// Images are drawn at most this many columns wide, and never taller than MAX_IMAGE_ROWS
+const MAX_IMAGE_COLUMNS: u32 = 48;
+const MAX_IMAGE_ROWS: u32 = 24;
+
+/// A piece of one span that landed on a wrapped line
+#[derive(Clone, Debug)]
+pub struct LineFragment {
+    /// Position of the source span in the slice given to `wrap_spans`
+    pub span_index: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WrappedLine {
+    pub fragments: Vec<LineFragment>,
+    /// Set on the lines an image is drawn over, to which row of the image this line shows
+    pub image_row: Option<usize>,
+}
+
+impl WrappedLine {
+    fn width(&self) -> usize {
+        self.fragments
+            .iter()
+            .map(|fragment| fragment.text.width())
+            .sum()
+    }
+
+    fn push(&mut self, span_index: usize, text: &str) {
+        match self.fragments.last_mut() {
+            Some(fragment) if fragment.span_index == span_index => fragment.text.push_str(text),
+            _ => self.fragments.push(LineFragment {
+                span_index,
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    pub fn contains_span(&self, span_index: usize) -> bool {
+        self.fragments
+            .iter()
+            .any(|fragment| fragment.span_index == span_index)
+    }
+}
+
+/// How many columns and rows an image takes up in a view `width` columns wide.
+/// Every cell holds two vertical pixels, so the rows are half the scaled height.
+pub fn image_size_in_cells(image: &ArticleImage, width: usize) -> (u16, u16) {
+    let columns = (width as u32)
+        .clamp(1, MAX_IMAGE_COLUMNS)
+        .min(image.width.max(1));
+    let rows = (columns * image.height / image.width.max(1)).div_ceil(2);
+    (columns as u16, rows.clamp(1, MAX_IMAGE_ROWS) as u16)
+}
+
+/// Word-wraps the spans into lines at most `width` columns wide, keeping track of which span
+/// every piece of text came from so the caller can style it and map lines back to spans.
+pub fn wrap_spans(spans: &[FormattedSpan], width: usize) -> Vec<WrappedLine> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current_line = WrappedLine::default();
+    // The image rows already ended the line, so the break that follows an image is dropped
+    let mut follows_image = false;
+
+    for (span_index, span) in spans.iter().enumerate() {
+        if span.is_break {
+            if !follows_image {
+                lines.push(std::mem::take(&mut current_line));
+            }
+            follows_image = false;
+            continue;
+        }
+        follows_image = span.image.is_some();
+
+        if let Some(image) = &span.image {
+            if !current_line.fragments.is_empty() {
+                lines.push(std::mem::take(&mut current_line));
+            }
+            let (_, rows) = image_size_in_cells(image, width);
+            for image_row in 0..rows as usize {
+                lines.push(WrappedLine {
+                    fragments: vec![LineFragment {
+                        span_index,
+                        text: String::new(),
+                    }],
+                    image_row: Some(image_row),
+                });
+            }
+            continue;
+        }
+
+        for word in span.text.split_inclusive(' ') {
+            let word_width = word.trim_end().width();
+            let line_width = current_line.width();
+            if line_width > 0 && line_width + word_width > width {
+                lines.push(std::mem::take(&mut current_line));
+            }
+
+            let word = if current_line.fragments.is_empty() {
+                word.trim_start()
+            } else {
+                word
+            };
+            if word.width() <= width {
+                current_line.push(span_index, word);
+                continue;
+            }
+
+            // A single word wider than the whole line gets split wherever it overflows
+            for c in word.chars() {
+                let mut buffer = [0; 4];
+                let c = c.encode_utf8(&mut buffer);
+                if current_line.width() + c.width() > width {
+                    lines.push(std::mem::take(&mut current_line));
+                }
+                current_line.push(span_index, c);
+            }
+        }
+    }
+
+    if !current_line.fragments.is_empty() {
+        lines.push(current_line);
+    }
+
+    lines
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::percent_decode;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum LinkKind {
+    /// An existing article on the same wiki
+    Internal,
+    /// A section of the current article
+    Anchor,
+    /// An article that has not been written yet (a "red link")
+    Red,
+    /// An article on another wiki or language edition
+    Interwiki,
+    /// Anything outside Wikipedia
+    External,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// Article title for internal and red links, the URL for interwiki and external ones,
+    /// and the section id for anchors
+    pub target: String,
+    /// The `#fragment` of an internal link, if it points into a section
+    pub section: Option<String>,
+}
+
+impl Link {
+    /// Classifies an `<a>` element from Parsoid HTML by its `href`, `rel`, `class` and `title`
+    pub fn from_attributes(
+        href: &str,
+        rel: Option<&str>,
+        class: Option<&str>,
+        title: Option<&str>,
+    ) -> Link {
+        let rel = rel.unwrap_or("");
+        let is_red = class.is_some_and(|classes| classes.split_whitespace().any(|c| c == "new"))
+            || href.contains("redlink=1");
+
+        if let Some(anchor) = href.strip_prefix('#') {
+            return Link::new(LinkKind::Anchor, percent_decode(anchor), None);
+        }
+        if rel.contains("mw:WikiLink/Interwiki") {
+            return Link::new(LinkKind::Interwiki, absolute_url(href), None);
+        }
+        if rel.contains("mw:ExtLink")
+            || href.starts_with("http://")
+            || href.starts_with("https://")
+            || href.starts_with("//")
+        {
+            return Link::new(LinkKind::External, absolute_url(href), None);
+        }
+
+        let path = href.trim_start_matches("./");
+        let (page, section) = match path.split_once('#') {
+            Some((page, section)) => (page, Some(percent_decode(section))),
+            None => (path, None),
+        };
+        let page = page.split('?').next().unwrap_or(page);
+        let article_title = match title {
+            Some(title) => title.to_string(),
+            None => percent_decode(page).replace('_', " "),
+        };
+
+        if is_red {
+            Link::new(LinkKind::Red, article_title, None)
+        } else {
+            Link::new(LinkKind::Internal, article_title, section)
+        }
+    }
+
+    fn new(kind: LinkKind, target: String, section: Option<String>) -> Link {
+        Link {
+            kind,
+            target,
+            section,
+        }
+    }
+}
+
+fn absolute_url(href: &str) -> String {
+    if href.starts_with("//") {
+        format!("https:{href}")
+    } else {
+        href.to_string()
+    }
+}
@@ -0,0 +1,435 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Characters with a Unicode superscript form, and that form at the same position
+const SUPERSCRIPT_FROM: &str = "0123456789+-−=()abcdefghijklmnoprstuvwxyzABDEGHIJKLMNOPRTUW′";
+const SUPERSCRIPT_TO: &str = "⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻⁻⁼⁽⁾ᵃᵇᶜᵈᵉᶠᵍʰⁱʲᵏˡᵐⁿᵒᵖʳˢᵗᵘᵛʷˣʸᶻᴬᴮᴰᴱᴳᴴᴵᴶᴷᴸᴹᴺᴼᴾᴿᵀᵁᵂ′";
+
+const SUBSCRIPT_FROM: &str = "0123456789+-−=()aehijklmnoprstuvxβγρφ";
+const SUBSCRIPT_TO: &str = "₀₁₂₃₄₅₆₇₈₉₊₋₋₌₍₎ₐₑₕᵢⱼₖₗₘₙₒₚᵣₛₜᵤᵥₓᵦᵧᵨᵩ";
+
+const SYMBOLS: [(&str, &str); 134] = [
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("varpi", "ϖ"),
+    ("rho", "ρ"),
+    ("varrho", "ϱ"),
+    ("sigma", "σ"),
+    ("varsigma", "ς"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("coprod", "∐"),
+    ("int", "∫"),
+    ("iint", "∬"),
+    ("iiint", "∭"),
+    ("oint", "∮"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("infty", "∞"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("times", "×"),
+    ("cdot", "⋅"),
+    ("div", "÷"),
+    ("ast", "∗"),
+    ("star", "⋆"),
+    ("circ", "∘"),
+    ("bullet", "•"),
+    ("oplus", "⊕"),
+    ("otimes", "⊗"),
+    ("leq", "≤"),
+    ("le", "≤"),
+    ("geq", "≥"),
+    ("ge", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("ll", "≪"),
+    ("gg", "≫"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("cong", "≅"),
+    ("propto", "∝"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("supseteq", "⊇"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("emptyset", "∅"),
+    ("varnothing", "∅"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("lnot", "¬"),
+    ("land", "∧"),
+    ("wedge", "∧"),
+    ("lor", "∨"),
+    ("vee", "∨"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("gets", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("leftrightarrow", "↔"),
+    ("Leftrightarrow", "⇔"),
+    ("iff", "⇔"),
+    ("implies", "⇒"),
+    ("mapsto", "↦"),
+    ("uparrow", "↑"),
+    ("downarrow", "↓"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("cdots", "⋯"),
+    ("vdots", "⋮"),
+    ("ddots", "⋱"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("hbar", "ℏ"),
+    ("ell", "ℓ"),
+    ("Re", "ℜ"),
+    ("Im", "ℑ"),
+    ("aleph", "ℵ"),
+    ("prime", "′"),
+    ("angle", "∠"),
+    ("perp", "⊥"),
+    ("parallel", "∥"),
+    ("mid", "∣"),
+    ("vert", "|"),
+    ("Vert", "‖"),
+    ("|", "‖"),
+    ("{", "{"),
+    ("}", "}"),
+];
+
+// Relations get a space either side so `a=b` reads as `a = b`
+const RELATIONS: [&str; 16] = [
+    "=", "<", ">", "≤", "≥", "≠", "≈", "≡", "∼", "≃", "≅", "∝", "→", "⇒", "⇔", "↦",
+];
+
+// Written out upright, as names rather than symbols
+const FUNCTION_NAMES: [&str; 24] = [
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "deg",
+];
+
+const DOUBLE_STRUCK_FROM: &str = "NZQRCPH";
+const DOUBLE_STRUCK_TO: &str = "ℕℤℚℝℂℙℍ";
+
+/// Turns the LaTeX Wikipedia keeps for every formula into a line of plain Unicode:
+/// Greek letters and operators become symbols, simple super/subscripts become ²/₂ and
+/// fractions are written inline as `(a)/(b)`.
+pub fn latex_to_unicode(latex: &str) -> String {
+    let latex = strip_style_wrapper(latex);
+    let mut chars = latex.chars().peekable();
+    let converted = convert(&mut chars, false);
+    tidy_spacing(&converted)
+}
+
+/// Wikipedia wraps formulas in `{\displaystyle ...}`, which says nothing about the formula itself
+pub fn strip_style_wrapper(latex: &str) -> &str {
+    let trimmed = latex.trim();
+    for wrapper in ["{\\displaystyle", "{\\textstyle"] {
+        if let Some(inner) = trimmed.strip_prefix(wrapper) {
+            if let Some(inner) = inner.strip_suffix('}') {
+                return inner.trim();
+            }
+        }
+    }
+    trimmed
+}
+
+fn convert(chars: &mut Peekable<Chars>, inside_group: bool) -> String {
+    let mut output = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '}' if inside_group => break,
+            '{' => output.push_str(&convert(chars, true)),
+            '\\' => output.push_str(&convert_command(chars)),
+            '^' => output.push_str(&scripted(
+                &next_argument(chars),
+                SUPERSCRIPT_FROM,
+                SUPERSCRIPT_TO,
+                '^',
+            )),
+            '_' => output.push_str(&scripted(
+                &next_argument(chars),
+                SUBSCRIPT_FROM,
+                SUBSCRIPT_TO,
+                '_',
+            )),
+            '&' => output.push(' '),
+            '~' => output.push(' '),
+            '\'' => output.push('′'),
+            '-' => output.push('−'),
+            // Spacing in math mode comes from the symbols, not from the source
+            c if c.is_whitespace() => {}
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+fn convert_command(chars: &mut Peekable<Chars>) -> String {
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_alphabetic() {
+            name.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    if name.is_empty() {
+        // A single-character command like `\,`, `\{` or `\\`
+        return match chars.next() {
+            Some(',') | Some(':') | Some(';') | Some(' ') => String::from(" "),
+            Some('!') => String::new(),
+            Some('\\') => String::from("; "),
+            Some(c) => lookup_symbol(&c.to_string()).unwrap_or(c.to_string()),
+            None => String::new(),
+        };
+    }
+
+    match name.as_str() {
+        "frac" | "dfrac" | "tfrac" => {
+            let numerator = next_argument(chars);
+            let denominator = next_argument(chars);
+            format!(
+                "{}/{}",
+                parenthesise(&numerator),
+                parenthesise(&denominator)
+            )
+        }
+        "binom" => {
+            let top = next_argument(chars);
+            let bottom = next_argument(chars);
+            format!("C({top}, {bottom})")
+        }
+        "sqrt" => {
+            let degree = optional_argument(chars);
+            let radicand = next_argument(chars);
+            let root = match degree.as_deref() {
+                None | Some("2") => String::from("√"),
+                Some("3") => String::from("∛"),
+                Some("4") => String::from("∜"),
+                Some(degree) => format!(
+                    "{}√",
+                    scripted(degree, SUPERSCRIPT_FROM, SUPERSCRIPT_TO, '^')
+                ),
+            };
+            format!("{root}{}", parenthesise(&radicand))
+        }
+        "mathbb" => next_argument(chars)
+            .chars()
+            .map(|c| lookup_char(c, DOUBLE_STRUCK_FROM, DOUBLE_STRUCK_TO).unwrap_or(c))
+            .collect(),
+        "overline" | "bar" => combine(&next_argument(chars), '\u{0305}'),
+        "vec" => combine(&next_argument(chars), '\u{20D7}'),
+        "hat" | "widehat" => combine(&next_argument(chars), '\u{0302}'),
+        "tilde" | "widetilde" => combine(&next_argument(chars), '\u{0303}'),
+        "dot" => combine(&next_argument(chars), '\u{0307}'),
+        "ddot" => combine(&next_argument(chars), '\u{0308}'),
+        "mathrm" | "mathbf" | "mathit" | "mathsf" | "mathtt" | "mathcal" | "mathfrak"
+        | "boldsymbol" | "operatorname" => next_argument(chars),
+        "text" | "textrm" | "textbf" | "textit" | "mbox" => {
+            format!(" {} ", raw_group(chars).trim())
+        }
+        "begin" | "end" => {
+            next_argument(chars);
+            String::new()
+        }
+        "left" | "right" | "bigl" | "bigr" | "Bigl" | "Bigr" | "big" | "Big" | "displaystyle"
+        | "textstyle" | "scriptstyle" | "limits" | "nolimits" => {
+            if chars.peek() == Some(&'.') {
+                chars.next();
+            }
+            String::new()
+        }
+        "quad" => String::from("  "),
+        "qquad" => String::from("    "),
+        name if FUNCTION_NAMES.contains(&name) => {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            match chars.peek() {
+                Some('_') | Some('^') | Some('(') | None => name.to_string(),
+                _ => format!("{name} "),
+            }
+        }
+        name => lookup_symbol(name).unwrap_or(format!("\\{name}")),
+    }
+}
+
+/// Reads the next `{group}`, `\command` or single character
+fn next_argument(chars: &mut Peekable<Chars>) -> String {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    match chars.next() {
+        Some('{') => convert(chars, true),
+        Some('\\') => convert_command(chars),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Reads a `{group}` as it is written, for text whose spaces matter
+fn raw_group(chars: &mut Peekable<Chars>) -> String {
+    if chars.peek() != Some(&'{') {
+        return next_argument(chars);
+    }
+    chars.next();
+    let mut depth = 1;
+    let mut group = String::new();
+    for c in chars.by_ref() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            break;
+        }
+        group.push(c);
+    }
+    group
+}
+
+fn optional_argument(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.peek() != Some(&'[') {
+        return None;
+    }
+    chars.next();
+    let mut argument = String::new();
+    for c in chars.by_ref() {
+        if c == ']' {
+            break;
+        }
+        argument.push(c);
+    }
+    Some(argument)
+}
+
+/// Uses Unicode super/subscript characters when every character has one, otherwise falls back
+/// to `^(...)` / `_(...)`
+fn scripted(text: &str, from: &str, to: &str, marker: char) -> String {
+    let text = text.trim();
+    let converted: Option<String> = text.chars().map(|c| lookup_char(c, from, to)).collect();
+    match converted {
+        Some(converted) => converted,
+        None if text.chars().count() == 1 => format!("{marker}{text}"),
+        None => format!("{marker}({text})"),
+    }
+}
+
+fn parenthesise(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= 1 || text.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        text.to_string()
+    } else {
+        format!("({text})")
+    }
+}
+
+fn combine(text: &str, combining_mark: char) -> String {
+    text.chars().flat_map(|c| [c, combining_mark]).collect()
+}
+
+fn lookup_char(c: char, from: &str, to: &str) -> Option<char> {
+    let position = from.chars().position(|plain| plain == c)?;
+    to.chars().nth(position)
+}
+
+fn lookup_symbol(name: &str) -> Option<String> {
+    SYMBOLS
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, symbol)| symbol.to_string())
+}
+
+fn tidy_spacing(text: &str) -> String {
+    let mut spaced = String::with_capacity(text.len());
+    for c in text.chars() {
+        if RELATIONS.contains(&c.to_string().as_str()) {
+            spaced.push(' ');
+            spaced.push(c);
+            spaced.push(' ');
+        } else {
+            spaced.push(c);
+        }
+    }
+    spaced.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_latex_to_unicode() {
+        let cases = [
+            (r"{\displaystyle E=mc^{2}}", "E = mc²"),
+            (r"\frac{a+b}{2}", "(a+b)/2"),
+            (r"\sqrt[3]{x}", "∛x"),
+            (r"\alpha_i \leq \beta", "αᵢ ≤ β"),
+            (r"x^{n+1}", "xⁿ⁺¹"),
+            (r"\mathbb{R}", "ℝ"),
+            (r"\sin x", "sin x"),
+            (r"\text{if } x", "if x"),
+        ];
+        for (latex, expected) in cases {
+            assert_eq!(latex_to_unicode(latex), expected, "{latex}");
+        }
+    }
+
+    #[test]
+    fn strips_only_style_wrappers() {
+        assert_eq!(strip_style_wrapper(r" {\displaystyle x^{2}} "), "x^{2}");
+        assert_eq!(strip_style_wrapper(r"{\textstyle a}"), "a");
+        assert_eq!(strip_style_wrapper(r"{x}"), "{x}");
+    }
+}
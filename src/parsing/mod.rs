@@ -1,4 +1,9 @@
 pub mod formatted_span;
-pub mod markdown_parse;
-pub use formatted_span::FormattedSpan;
-pub use markdown_parse::parse_markdown;
+pub mod html_parse;
+pub mod line_wrap;
+pub mod link;
+pub mod math;
+pub use formatted_span::{ArticleImage, FormattedSpan};
+pub use html_parse::{parse_html, parse_snippet, Snippet};
+pub use line_wrap::{image_size_in_cells, wrap_spans, WrappedLine};
+pub use link::{Link, LinkKind};
@@ -0,0 +1,123 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::session;
+use crate::utils::unix_now;
+
+const POSITIONS_FILE: &str = "positions.json";
+
+/// Articles read longest ago are forgotten past this many
+const MAX_ENTRIES: usize = 1000;
+
+/// How far down an article was read
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReadingPosition {
+    pub language: String,
+    pub title: String,
+    /// First wrapped line shown when the article was left
+    pub scroll: usize,
+    /// Seconds since the Unix epoch
+    pub read_at: u64,
+}
+
+impl ReadingPosition {
+    fn is_for(&self, language: &str, title: &str) -> bool {
+        self.language == language && self.title == normalize_title(title)
+    }
+}
+
+/// Titles from links use underscores where the page title has spaces
+fn normalize_title(title: &str) -> String {
+    title.replace('_', " ")
+}
+
+/// Where each article was left, latest first, kept between runs
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ReadingPositions {
+    pub entries: Vec<ReadingPosition>,
+}
+
+impl ReadingPositions {
+    pub fn path() -> PathBuf {
+        session::data_dir().join(POSITIONS_FILE)
+    }
+
+    /// The saved positions, or none if nothing has been saved yet
+    pub fn load() -> Result<ReadingPositions, Box<dyn Error>> {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> Result<ReadingPositions, Box<dyn Error>> {
+        Ok(session::load_json(path)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        session::save_json(&Self::path(), self)
+    }
+
+    /// Remembers `scroll` for the article. Going back to the top forgets it instead.
+    pub fn record(&mut self, language: &str, title: &str, scroll: usize) {
+        self.entries.retain(|entry| !entry.is_for(language, title));
+        if scroll == 0 {
+            return;
+        }
+        self.entries.insert(
+            0,
+            ReadingPosition {
+                language: language.to_string(),
+                title: normalize_title(title),
+                scroll,
+                read_at: unix_now(),
+            },
+        );
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// Where the article was left, if it was read before
+    pub fn get(&self, language: &str, title: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|entry| entry.is_for(language, title))
+            .map(|entry| entry.scroll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn keeps_reading_positions_between_runs() {
+        let dir = std::env::temp_dir().join(format!("wik-positions-test-{}", std::process::id()));
+        let path = dir.join(POSITIONS_FILE);
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut positions = ReadingPositions::load_from(&path).unwrap();
+        assert_eq!(positions.get("en", "Kyoto"), None);
+        positions.record("en", "Kyoto_Station", 30);
+        positions.record("de", "Kyoto", 12);
+        positions.record("en", "Nara", 8);
+        // Scrolling back to the top forgets the article
+        positions.record("en", "Nara", 0);
+        session::save_json(&path, &positions).unwrap();
+
+        let positions = ReadingPositions::load_from(&path).unwrap();
+        assert_eq!(positions.get("en", "Kyoto Station"), Some(30));
+        assert_eq!(positions.get("de", "Kyoto"), Some(12));
+        assert_eq!(positions.get("en", "Kyoto"), None);
+        assert_eq!(positions.get("en", "Nara"), None);
+
+        fs::write(&path, "").unwrap();
+        assert!(ReadingPositions::load_from(&path).is_err());
+        assert!(ReadingPositions::load_from(&path)
+            .unwrap()
+            .entries
+            .is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
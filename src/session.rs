@@ -0,0 +1,183 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use dirs::home_dir;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+const DATA_DIR: &str = ".local/share/wik/";
+const SESSION_FILE: &str = "session.json";
+
+/// An article read before the current one in a tab or pane, to go back to
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub title: String,
+    /// The wiki it was read on, which links and Back stay on
+    pub language: String,
+    pub scroll: usize,
+}
+
+/// An open article tab, as saved when wik quits
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SavedTab {
+    pub title: String,
+    pub language: String,
+    pub scroll: usize,
+    /// Previously read articles in the tab
+    pub history: Vec<HistoryEntry>,
+    /// The second pane, if the tab was split
+    #[serde(default)]
+    pub split: Option<SavedSplit>,
+}
+
+/// The pane a tab was split with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SavedSplit {
+    pub title: String,
+    pub language: String,
+    pub scroll: usize,
+    pub history: Vec<HistoryEntry>,
+    /// Side by side rather than one above the other
+    pub vertical: bool,
+    /// Whether the tab's own article is the right or bottom pane
+    pub focus_second: bool,
+    pub sync_scroll: bool,
+}
+
+/// The tabs that were open last time, so they can be picked up again
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Session {
+    pub tabs: Vec<SavedTab>,
+    pub active_tab: usize,
+}
+
+/// Where wik keeps what it remembers between runs
+pub fn data_dir() -> PathBuf {
+    home_dir().unwrap_or_default().join(DATA_DIR)
+}
+
+/// Reads a JSON file, `None` if there isn't one yet. A file that can't be parsed is moved aside
+/// to `<name>.bad`, so that saving afterwards doesn't overwrite it, and reported as an error.
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Box<dyn Error>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("couldn't read {}: {e}", path.display()).into()),
+    };
+    match serde_json::from_reader(BufReader::new(file)) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            let moved_to = path.with_extension("json.bad");
+            fs::rename(path, &moved_to)?;
+            Err(format!(
+                "couldn't read {} ({e}), moved it to {}",
+                path.display(),
+                moved_to.display()
+            )
+            .into())
+        }
+    }
+}
+
+/// Writes `value` as JSON through a temporary file, so the file is never left half written
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+impl Session {
+    pub fn path() -> PathBuf {
+        data_dir().join(SESSION_FILE)
+    }
+
+    /// The last saved session, if there is one
+    pub fn load() -> Result<Option<Session>, Box<dyn Error>> {
+        load_json(&Self::path())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        save_json(&Self::path(), self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_a_file_that_cant_be_parsed() {
+        let dir = std::env::temp_dir().join(format!("wik-session-test-{}", std::process::id()));
+        let path = dir.join("store.json");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(load_json::<Vec<String>>(&path).unwrap().is_none());
+        save_json(&path, &vec![String::from("saved")]).unwrap();
+        assert_eq!(
+            load_json::<Vec<String>>(&path).unwrap(),
+            Some(vec![String::from("saved")])
+        );
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(load_json::<Vec<String>>(&path).is_err());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("store.json.bad")).unwrap(),
+            "{ not json"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reopens_tabs_with_their_history() {
+        let dir = std::env::temp_dir().join(format!("wik-tabs-test-{}", std::process::id()));
+        let path = dir.join(SESSION_FILE);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(load_json::<Session>(&path).unwrap().is_none());
+        let back = HistoryEntry {
+            title: String::from("Japan"),
+            language: String::from("en"),
+            scroll: 5,
+        };
+        let session = Session {
+            tabs: vec![SavedTab {
+                title: String::from("京都市"),
+                language: String::from("ja"),
+                scroll: 42,
+                history: vec![back.clone()],
+                split: Some(SavedSplit {
+                    title: String::from("Nara"),
+                    language: String::from("en"),
+                    scroll: 0,
+                    history: Vec::new(),
+                    vertical: true,
+                    focus_second: false,
+                    sync_scroll: true,
+                }),
+            }],
+            active_tab: 0,
+        };
+        save_json(&path, &session).unwrap();
+
+        let loaded = load_json::<Session>(&path).unwrap().unwrap();
+        let tab = &loaded.tabs[0];
+        assert_eq!(
+            (tab.title.as_str(), tab.language.as_str()),
+            ("京都市", "ja")
+        );
+        assert_eq!(tab.scroll, 42);
+        assert_eq!(tab.history, [back]);
+        let split = tab.split.as_ref().unwrap();
+        assert_eq!(split.title, "Nara");
+        assert!(split.vertical && split.sync_scroll && !split.focus_second);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
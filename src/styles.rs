@@ -1,28 +1,189 @@
+use std::{fs, path::PathBuf};
+
+use dirs::home_dir;
+use serde::Deserialize;
 use tui::style::{Color, Modifier, Style};
 
+use crate::parsing::LinkKind;
+use crate::utils::hex_to_rgb;
+
+const THEMES_DIR: &str = ".config/wik/themes/";
+
+// Shipped with wik, in the same format as user theme files
+const BUNDLED_THEMES: [&str; 4] = [
+    include_str!("../themes/light.toml"),
+    include_str!("../themes/solarized.toml"),
+    include_str!("../themes/high-contrast.toml"),
+    include_str!("../themes/monochrome.toml"),
+];
+
+#[derive(Clone)]
 pub struct Theme {
+    pub name: String,
     pub background: Color,
     pub text: Color,
     pub secondary: Color,
     pub tertiary: Color,
     pub highlight: Color,
     pub negative_text: Color,
+    pub link: Color,
+    pub external_link: Color,
+    pub red_link: Color,
 }
 
 impl Default for Theme {
     fn default() -> Self {
         Self {
+            name: String::from("dark"),
             background: Color::Rgb(42, 49, 56),
             text: Color::White,
             secondary: Color::Yellow,
             tertiary: Color::Green,
             highlight: Color::LightBlue,
             negative_text: Color::Black,
+            link: Color::LightCyan,
+            external_link: Color::LightMagenta,
+            red_link: Color::LightRed,
         }
     }
 }
 
+/// A theme file: every colour is optional and falls back to the default (dark) theme
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    background: Option<String>,
+    text: Option<String>,
+    secondary: Option<String>,
+    tertiary: Option<String>,
+    highlight: Option<String>,
+    negative_text: Option<String>,
+    link: Option<String>,
+    external_link: Option<String>,
+    red_link: Option<String>,
+}
+
+/// Reads a colour as `#rrggbb`, or as the name of one of the 16 basic terminal colours
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    if value.starts_with('#') {
+        let (red, green, blue) = hex_to_rgb(value)?;
+        return Ok(Color::Rgb(red, green, blue));
+    }
+    match value.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "reset" | "default" => Ok(Color::Reset),
+        "black" => Ok(Color::Black),
+        "red" => Ok(Color::Red),
+        "green" => Ok(Color::Green),
+        "yellow" => Ok(Color::Yellow),
+        "blue" => Ok(Color::Blue),
+        "magenta" => Ok(Color::Magenta),
+        "cyan" => Ok(Color::Cyan),
+        "gray" | "grey" => Ok(Color::Gray),
+        "darkgray" | "darkgrey" => Ok(Color::DarkGray),
+        "lightred" => Ok(Color::LightRed),
+        "lightgreen" => Ok(Color::LightGreen),
+        "lightyellow" => Ok(Color::LightYellow),
+        "lightblue" => Ok(Color::LightBlue),
+        "lightmagenta" => Ok(Color::LightMagenta),
+        "lightcyan" => Ok(Color::LightCyan),
+        "white" => Ok(Color::White),
+        _ => Err(format!(
+            "\"{value}\" is not a colour, use a hex code like \"#ffaa00\" or a name like \"yellow\""
+        )),
+    }
+}
+
 impl Theme {
+    pub fn from_toml(default_name: &str, toml_text: &str) -> Result<Theme, String> {
+        let file: ThemeFile = toml::from_str(toml_text).map_err(|e| e.to_string())?;
+        let mut theme = Theme {
+            name: file.name.unwrap_or(default_name.to_string()),
+            ..Theme::default()
+        };
+
+        let colours = [
+            ("background", file.background, &mut theme.background),
+            ("text", file.text, &mut theme.text),
+            ("secondary", file.secondary, &mut theme.secondary),
+            ("tertiary", file.tertiary, &mut theme.tertiary),
+            ("highlight", file.highlight, &mut theme.highlight),
+            (
+                "negative_text",
+                file.negative_text,
+                &mut theme.negative_text,
+            ),
+            ("link", file.link, &mut theme.link),
+            (
+                "external_link",
+                file.external_link,
+                &mut theme.external_link,
+            ),
+            ("red_link", file.red_link, &mut theme.red_link),
+        ];
+        for (key, value, colour) in colours {
+            if let Some(value) = value {
+                *colour = parse_color(&value).map_err(|e| format!("{key}: {e}"))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn themes_dir() -> PathBuf {
+        home_dir().unwrap_or_default().join(THEMES_DIR)
+    }
+
+    /// The bundled themes followed by any in the themes directory. Theme files that can't be
+    /// read come back as error messages instead.
+    pub fn available_themes() -> (Vec<Theme>, Vec<String>) {
+        let mut themes = vec![Theme::default()];
+        themes.extend(
+            BUNDLED_THEMES
+                .iter()
+                .filter_map(|toml_text| Theme::from_toml("bundled", toml_text).ok()),
+        );
+
+        let mut errors = Vec::new();
+        let mut theme_files: Vec<PathBuf> = match fs::read_dir(Self::themes_dir()) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "toml")
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        theme_files.sort();
+
+        for path in theme_files {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let default_name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let theme = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|toml_text| Theme::from_toml(&default_name, &toml_text));
+            match theme {
+                // A user theme with a bundled theme's name replaces it
+                Ok(theme) => match themes.iter().position(|other| other.name == theme.name) {
+                    Some(index) => themes[index] = theme,
+                    None => themes.push(theme),
+                },
+                Err(e) => errors.push(format!("{file_name}: {e}")),
+            }
+        }
+
+        (themes, errors)
+    }
+
+    /// The background as RGB, for blending images into it
+    pub fn background_rgb(&self) -> (u8, u8, u8) {
+        match self.background {
+            Color::Rgb(red, green, blue) => (red, green, blue),
+            Color::White => (255, 255, 255),
+            _ => (0, 0, 0),
+        }
+    }
+
     pub fn highlighted_snippet_style(&self) -> Style {
         Style::default().bg(self.highlight).fg(self.negative_text)
     }
@@ -75,4 +236,92 @@ impl Theme {
     pub fn block_border_focus(&self) -> Style {
         Style::default().fg(self.secondary)
     }
+
+    pub fn citation(&self) -> Style {
+        Style::default()
+            .fg(self.tertiary)
+            .add_modifier(Modifier::DIM)
+    }
+
+    pub fn link(&self, link_kind: &LinkKind) -> Style {
+        match link_kind {
+            LinkKind::Internal => Style::default()
+                .fg(self.link)
+                .add_modifier(Modifier::UNDERLINED),
+            LinkKind::Anchor => Style::default().fg(self.link),
+            LinkKind::Red => Style::default()
+                .fg(self.red_link)
+                .add_modifier(Modifier::UNDERLINED),
+            LinkKind::Interwiki => Style::default()
+                .fg(self.external_link)
+                .add_modifier(Modifier::UNDERLINED | Modifier::ITALIC),
+            LinkKind::External => Style::default()
+                .fg(self.external_link)
+                .add_modifier(Modifier::UNDERLINED),
+        }
+    }
+
+    pub fn selected_link(&self) -> Style {
+        Style::default()
+            .bg(self.secondary)
+            .fg(self.negative_text)
+            .add_modifier(Modifier::UNDERLINED)
+    }
+
+    pub fn caption(&self) -> Style {
+        Style::default()
+            .fg(self.text)
+            .add_modifier(Modifier::ITALIC | Modifier::DIM)
+    }
+
+    pub fn math(&self) -> Style {
+        Style::default()
+            .fg(self.highlight)
+            .add_modifier(Modifier::ITALIC)
+    }
+
+    pub fn hint_key(&self) -> Style {
+        Style::default()
+            .fg(self.secondary)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn hint_text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn landing_heading(&self) -> Style {
+        Style::default()
+            .fg(self.tertiary)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn status_bar(&self) -> Style {
+        Style::default().bg(self.secondary).fg(self.negative_text)
+    }
+
+    pub fn status_mode(&self) -> Style {
+        Style::default()
+            .bg(self.tertiary)
+            .fg(self.negative_text)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn notification(&self) -> Style {
+        self.status_bar()
+            .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_non_hex_colours() {
+        assert_eq!(parse_color("#1a2B3c"), Ok(Color::Rgb(0x1a, 0x2b, 0x3c)));
+        for value in ["#ééé", "#aééb", "#12345", "#12345g"] {
+            assert!(parse_color(value).is_err(), "{value}");
+        }
+    }
 }
@@ -0,0 +1,111 @@
+/// Text typed into a box, with a cursor that always sits on a character boundary
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextInput {
+    text: String,
+    /// Byte index into `text`
+    cursor_pos: usize,
+}
+
+impl TextInput {
+    /// `text`, with the cursor at its end
+    pub fn new(text: String) -> Self {
+        Self {
+            cursor_pos: text.len(),
+            text,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, putting the cursor at its end
+    pub fn set(&mut self, text: String) {
+        *self = Self::new(text);
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor_pos, c);
+        self.cursor_pos += c.len_utf8();
+    }
+
+    /// Deletes the character before the cursor
+    pub fn delete_backward(&mut self) {
+        let previous = self.previous_boundary();
+        self.text.replace_range(previous..self.cursor_pos, "");
+        self.cursor_pos = previous;
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor_pos = self.previous_boundary();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor_pos = self.next_boundary();
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor_pos = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor_pos = self.text.len();
+    }
+
+    /// The text before the cursor, the character under it (if it isn't at the end), and the
+    /// text after that
+    pub fn split_at_cursor(&self) -> (&str, Option<char>, &str) {
+        let (before, after) = self.text.split_at(self.cursor_pos);
+        let mut rest = after.chars();
+        let under = rest.next();
+        (before, under, rest.as_str())
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor_pos]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor_pos..]
+            .chars()
+            .next()
+            .map_or(self.cursor_pos, |c| self.cursor_pos + c.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_and_deletes_whole_characters() {
+        let mut input = TextInput::new(String::from("café"));
+        input.delete_backward();
+        assert_eq!(input.as_str(), "caf");
+
+        input.set(String::from("日本語"));
+        input.move_left();
+        assert_eq!(input.split_at_cursor(), ("日本", Some('語'), ""));
+        input.delete_backward();
+        assert_eq!(input.as_str(), "日語");
+        input.insert('é');
+        assert_eq!(input.as_str(), "日é語");
+        assert_eq!(input.split_at_cursor(), ("日é", Some('語'), ""));
+        input.move_right();
+        input.move_right();
+        assert_eq!(input.split_at_cursor(), ("日é語", None, ""));
+    }
+
+    #[test]
+    fn stops_at_the_ends() {
+        let mut input = TextInput::default();
+        input.delete_backward();
+        input.move_left();
+        input.move_right();
+        assert_eq!(input, TextInput::default());
+        assert_eq!(input.split_at_cursor(), ("", None, ""));
+    }
+}
@@ -1,43 +1,310 @@
-use std::ops::Deref;
 use std::sync::{MutexGuard, TryLockError, TryLockResult};
 
-use crate::app::{ActionItem, ActionMenu, App, AppState, MenuState, TypeableState};
-use crate::parsing::FormattedSpan;
+use crate::app::{
+    ActionItem, ActionMenu, AdvancedSearchState, App, AppState, ArticleState, BookmarkField,
+    LandingItem, MenuState, MouseTarget, PaletteKind, SettingsState, SplitDirection,
+};
+use crate::bookmarks::BookmarkList;
+use crate::config::Setting;
+use crate::images::{GraphicsProtocol, ImagePlacement, ImageState};
+use crate::keymap::{Action, KeyContext};
+use crate::parsing::{image_size_in_cells, wrap_spans, ArticleImage, FormattedSpan};
 use crate::styles::Theme;
-use crate::utils::{wik_title, wrapped_iter_enumerate};
-use crate::widgets::ScrollBar;
-use crate::wikipedia::SearchResult;
+use crate::text_input::TextInput;
+use crate::utils::{format_timestamp, wik_title, wrapped_iter_enumerate};
+use crate::widgets::{HalfBlockImage, ScrollBar};
+use crate::wikipedia::{
+    PageSource, SearchOperator, SearchResult, SearchSort, SummaryState, NAMESPACES,
+};
 use digest::typenum::Mod;
 use tui::layout::Rect;
 use tui::style::Modifier;
 use tui::widgets::{BorderType, Widget};
-// use crate::widgets::ScrollBar;
+// use crate::widgets::{HalfBlockImage, ScrollBar};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout},
     style::Style,
     text::{Span, Spans},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 
 use substring::Substring;
+use unicode_width::UnicodeWidthStr;
 
 pub fn draw<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
+    app.image_placements.borrow_mut().clear();
+    app.mouse_areas.borrow_mut().clear();
     let window_area = frame.size();
     frame.render_widget(
         Block::default().style(app.theme.window_background()),
         window_area,
     );
+    let window_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(window_area);
+    let screen_area = window_chunks[0];
+
     match app.state {
-        AppState::Title => draw_title(frame, app),
-        AppState::Search => draw_search(frame, app),
-        AppState::SearchMenu => draw_menu(frame, app, &app.search_menu),
-        AppState::Credit => draw_credit(frame, app),
-        AppState::Article => draw_article(frame, app),
-        AppState::ArticleMenu => draw_menu(frame, app, &app.article_menu),
-        _ => draw_search(frame, app),
+        AppState::Title => draw_title(frame, app, screen_area),
+        AppState::Search => draw_search(frame, app, screen_area),
+        AppState::SearchMenu => draw_menu(frame, app, &app.search_menu, screen_area),
+        AppState::Credit => draw_credit(frame, app, screen_area),
+        AppState::Article => draw_article(frame, app, screen_area),
+        AppState::ArticleMenu => draw_menu(frame, app, &app.article_menu, screen_area),
+        AppState::ThemePicker => draw_theme_picker(frame, app, screen_area),
+        AppState::Settings => draw_settings(frame, app, screen_area),
+        AppState::CommandPalette => draw_command_palette(frame, app, screen_area),
+        AppState::Bookmarks => draw_bookmarks(frame, app, screen_area),
+    }
+    draw_status_bar(frame, app, window_chunks[1]);
+    draw_hint_bar(frame, app, window_chunks[2]);
+
+    if app.show_help {
+        draw_help(frame, app, screen_area);
+    }
+}
+
+/// The actions worth reminding people of on each screen, with a short label
+fn hint_actions(app: &App) -> Vec<(Action, &'static str)> {
+    match app.state {
+        AppState::Title => vec![
+            (Action::Confirm, "search"),
+            (Action::ScrollDown, "recent & featured"),
+            (Action::SearchHistory, "history"),
+            (Action::OpenBookmarks, "bookmarks"),
+        ],
+        AppState::Search if app.search.advanced.is_open => vec![
+            (Action::ScrollDown, "next field"),
+            (Action::NextChoice, "namespace"),
+            (Action::Confirm, "search"),
+            (Action::AdvancedSearch, "close"),
+        ],
+        AppState::Search if app.search.showing_suggestions() => vec![
+            (Action::ScrollDown, "pick suggestion"),
+            (Action::Confirm, "open/search"),
+            (Action::OpenMenu, "menu"),
+        ],
+        AppState::Search => vec![
+            (Action::Confirm, "search/open"),
+            (Action::ScrollDown, "next result"),
+            (Action::ViewResult, "open result"),
+            (Action::CycleSort, "sort"),
+            (Action::TogglePreview, "preview"),
+            (Action::OpenMenu, "menu"),
+        ],
+        AppState::SearchMenu | AppState::ArticleMenu | AppState::Credit => vec![
+            (Action::ScrollDown, "down"),
+            (Action::Confirm, "select"),
+            (Action::Close, "close"),
+        ],
+        AppState::Article if app.split.is_some() => vec![
+            (Action::ScrollDown, "scroll"),
+            (Action::FollowLink, "follow"),
+            (Action::SwitchPane, "other pane"),
+            (Action::ToggleSyncScroll, "sync"),
+            (Action::ClosePane, "close pane"),
+            (Action::OpenMenu, "menu"),
+        ],
+        AppState::Article if app.total_tabs() > 1 => vec![
+            (Action::NextLink, "next link"),
+            (Action::FollowLink, "follow"),
+            (Action::OpenLinkInNewTab, "new tab"),
+            (Action::NextTab, "next tab"),
+            (Action::CloseTab, "close tab"),
+            (Action::OpenMenu, "menu"),
+        ],
+        AppState::Article => vec![
+            (Action::ScrollDown, "scroll"),
+            (Action::NextLink, "next link"),
+            (Action::FollowLink, "follow"),
+            (Action::OpenLinkInNewTab, "new tab"),
+            (Action::Back, "back"),
+            (Action::AddBookmark, "bookmark"),
+            (Action::OpenMenu, "menu"),
+        ],
+        AppState::ThemePicker => vec![
+            (Action::ScrollDown, "preview next"),
+            (Action::Confirm, "keep"),
+            (Action::Close, "cancel"),
+        ],
+        AppState::Settings if app.settings.is_editing => {
+            vec![(Action::Confirm, "apply"), (Action::Close, "cancel")]
+        }
+        AppState::Settings => vec![
+            (Action::Confirm, "edit"),
+            (Action::NextChoice, "change"),
+            (Action::Close, "back"),
+        ],
+        AppState::CommandPalette => vec![
+            (Action::Confirm, "run"),
+            (Action::ScrollDown, "next"),
+            (Action::Close, "close"),
+        ],
+        AppState::Bookmarks if app.bookmarks_screen.edit.is_some() => {
+            vec![(Action::Confirm, "save"), (Action::Close, "cancel")]
+        }
+        AppState::Bookmarks => vec![
+            (Action::Confirm, "open"),
+            (Action::NextChoice, "other list"),
+            (Action::EditBookmarkNote, "note"),
+            (Action::EditBookmarkTags, "tags"),
+            (Action::DeleteBookmark, "remove"),
+            (Action::Close, "close"),
+        ],
+    }
+}
+
+/// The screen, the wiki, how the page was loaded, where you are in it, and any notification
+fn draw_status_bar<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let (is_loading, load_info, language) = match app.state {
+        AppState::Search | AppState::SearchMenu => (
+            app.search.currently_loading(),
+            app.search.load_info.try_lock().ok().and_then(|info| *info),
+            &app.language,
+        ),
+        // An article stays on the wiki it was opened from when the language setting changes
+        AppState::Article | AppState::ArticleMenu => (
+            app.article.currently_loading(),
+            app.article.load_info.try_lock().ok().and_then(|info| *info),
+            &app.article.language,
+        ),
+        _ => (false, None, &app.language),
+    };
+
+    let mut left = vec![
+        Span::styled(format!(" {} ", app.state.label()), app.theme.status_mode()),
+        Span::raw(format!(" {language}.wikipedia.org ")),
+    ];
+    if is_loading {
+        left.push(Span::raw("│ Loading... "));
+    } else if let Some(load_info) = load_info {
+        let source = match load_info.source {
+            PageSource::Cache => "cache",
+            PageSource::Network => "network",
+        };
+        left.push(Span::raw(format!(
+            "│ {source} {}ms ",
+            load_info.duration.as_millis()
+        )));
+    }
+    if let Some((message, _)) = &app.notification {
+        left.push(Span::styled(
+            format!("│ {message} "),
+            app.theme.notification(),
+        ));
+    }
+
+    let right = match app.state {
+        AppState::Article if !is_loading => {
+            let max_scroll = app.article.max_scroll.get();
+            let percent = match max_scroll {
+                0 => 100,
+                _ => app.article.scroll.min(max_scroll) * 100 / max_scroll,
+            };
+            format!("{} words  {percent:>3}% ", app.article.word_count.get())
+        }
+        _ => String::new(),
+    };
+
+    let left_width: usize = left.iter().map(|span| span.content.width()).sum();
+    let padding = (area.width as usize).saturating_sub(left_width + right.width());
+    left.push(Span::raw(" ".repeat(padding)));
+    left.push(Span::raw(right));
+    frame.render_widget(
+        Paragraph::new(Spans::from(left)).style(app.theme.status_bar()),
+        area,
+    );
+}
+
+/// One line of the most useful keys for the current screen, read from the keymap
+fn draw_hint_bar<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let text_input_focused = app.text_input_focused();
+    let mut hints = hint_actions(app);
+    hints.push((Action::Help, "help"));
+
+    let hint_spans: Vec<Span> = hints
+        .into_iter()
+        .filter_map(|(action, label)| {
+            let key = app.keymap.key_for(app.state, text_input_focused, action)?;
+            Some(vec![
+                Span::styled(format!(" {key}"), app.theme.hint_key()),
+                Span::styled(format!(" {label} "), app.theme.hint_text()),
+            ])
+        })
+        .flatten()
+        .collect();
+    frame.render_widget(Paragraph::new(Spans::from(hint_spans)), area);
+}
+
+/// Every binding for the current screen, as a modal over it
+fn draw_help<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let mut sections = vec![("", app.keymap.bindings_in(KeyContext::Screen(app.state)))];
+    if matches!(
+        app.state,
+        AppState::Title
+            | AppState::Search
+            | AppState::Settings
+            | AppState::CommandPalette
+            | AppState::Bookmarks
+    ) {
+        sections.push((
+            "While typing",
+            app.keymap.bindings_in(KeyContext::TextInput),
+        ));
+    }
+
+    let key_width = sections
+        .iter()
+        .flat_map(|(_, bindings)| bindings.iter())
+        .map(|(_, keys)| keys.join(", ").width())
+        .max()
+        .unwrap_or(0);
+    let mut help_text = Vec::new();
+    for (heading, bindings) in sections {
+        if !heading.is_empty() {
+            help_text.push(Spans::from(""));
+            help_text.push(Spans::from(Span::styled(heading, app.theme.loading())));
+        }
+        for (action, keys) in bindings {
+            help_text.push(Spans::from(vec![
+                Span::styled(
+                    format!("{:<key_width$}  ", keys.join(", ")),
+                    app.theme.hint_key(),
+                ),
+                Span::styled(action.description(), app.theme.hint_text()),
+            ]));
+        }
     }
+    help_text.push(Spans::from(""));
+    help_text.push(Spans::from(Span::styled(
+        "Press any key to close",
+        app.theme.loading(),
+    )));
+
+    let area = centered_rect(70, 80, area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(app.theme.window_background())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Keys")
+                    .style(app.theme.block_border_focus()),
+            )
+            .wrap(Wrap { trim: false }),
+        area,
+    );
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -103,18 +370,40 @@ fn centered_rect_by_lengths(length_x: u16, length_y: u16, r: Rect) -> Rect {
         .split(vertical_layout[1])[1]
 }
 
-fn search_box_widget<'a>(
-    app: &'a App,
-    typeable: &'a impl TypeableState,
-    title: String,
-) -> Paragraph<'a> {
-    let mut input_text = typeable.get_input().to_owned();
-    input_text.push(' ');
-    let pre_highlight = input_text.substring(0, typeable.get_cursor_pos());
-    let highlight_char =
-        input_text.substring(typeable.get_cursor_pos(), typeable.get_cursor_pos() + 1);
-    let post_highlight =
-        input_text.substring(typeable.get_cursor_pos() + 1, typeable.get_input().len());
+/// Records that a click in `area` should act on `target`
+fn add_mouse_area(app: &App, area: Rect, target: MouseTarget) {
+    app.mouse_areas.borrow_mut().push((area, target));
+}
+
+/// Makes each of the first `total_rows` rows of `area` a clickable menu item, numbered
+/// from `first_index`
+fn add_menu_rows(app: &App, area: Rect, first_index: usize, total_rows: usize) {
+    for row in 0..total_rows.min(area.height as usize) {
+        let row_area = Rect::new(area.x, area.y + row as u16, area.width, 1);
+        add_mouse_area(app, row_area, MouseTarget::MenuItem(first_index + row));
+    }
+}
+
+/// Rows `text` takes up when word-wrapped to `width`, the way `Paragraph` wraps it
+fn wrapped_height(text: &str, width: usize) -> usize {
+    let mut rows = 1;
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = word.width();
+        if line_width > 0 && line_width + 1 + word_width > width {
+            rows += 1;
+            line_width = 0;
+        }
+        line_width += word_width + if line_width > 0 { 1 } else { 0 };
+        while line_width > width {
+            rows += 1;
+            line_width -= width;
+        }
+    }
+    rows
+}
+
+fn search_box_widget<'a>(app: &'a App, input: &'a TextInput, title: String) -> Paragraph<'a> {
     let text_block = if title.len() > 0 {
         Block::default()
             .borders(Borders::ALL)
@@ -122,20 +411,137 @@ fn search_box_widget<'a>(
     } else {
         Block::default().borders(Borders::ALL)
     };
-    Paragraph::new(vec![Spans::from(vec![
+    Paragraph::new(vec![Spans::from(cursor_spans(app, input))]).block(text_block)
+}
+
+/// The typed text with the character under the cursor highlighted
+fn cursor_spans<'a>(app: &App, input: &TextInput) -> Vec<Span<'a>> {
+    let (pre_highlight, highlight_char, post_highlight) = input.split_at_cursor();
+    vec![
         Span::raw(pre_highlight.to_owned()),
-        Span::styled(highlight_char.to_owned(), app.theme.cursor_style()),
+        Span::styled(
+            highlight_char.unwrap_or(' ').to_string(),
+            app.theme.cursor_style(),
+        ),
         Span::raw(post_highlight.to_owned()),
-    ])])
-    .block(text_block)
+    ]
 }
 
-fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
+fn draw_advanced_search<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let form = &app.search.advanced;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Advanced search")
+        .style(app.theme.block_border_focus());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let label_width = SearchOperator::ALL
+        .iter()
+        .map(|operator| operator.label().len())
+        .chain(["Namespace".len()])
+        .max()
+        .unwrap_or(0);
+    let keyword_width = SearchOperator::ALL
+        .iter()
+        .filter_map(|operator| operator.keyword())
+        .map(|keyword| keyword.len() + 1)
+        .max()
+        .unwrap_or(0);
+    let row_style = |row: usize| match row == form.selected_row {
+        true => app.theme.selected_option(),
+        false => app.theme.unselected_option(),
+    };
+
+    let mut rows: Vec<Spans> = SearchOperator::ALL
+        .iter()
+        .enumerate()
+        .map(|(row, operator)| {
+            let keyword = operator
+                .keyword()
+                .map(|keyword| format!("{keyword}:"))
+                .unwrap_or_default();
+            let mut spans = vec![
+                Span::styled(
+                    format!("{:<label_width$}  ", operator.label()),
+                    row_style(row),
+                ),
+                Span::styled(format!("{keyword:<keyword_width$}  "), app.theme.caption()),
+            ];
+            match row == form.selected_row {
+                true => spans.extend(cursor_spans(app, &form.values[row])),
+                false => spans.push(Span::raw(form.values[row].as_str().to_string())),
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    rows.push(Spans::from(vec![
+        Span::styled(
+            format!("{:<label_width$}  ", "Namespace"),
+            row_style(AdvancedSearchState::NAMESPACE_ROW),
+        ),
+        Span::raw(format!(
+            "{:<keyword_width$}  < {} >",
+            "", NAMESPACES[form.namespace].0
+        )),
+    ]));
+    add_menu_rows(app, inner, 0, rows.len());
+
+    // The compiled query, so the syntax can be picked up and typed straight into the search box
+    rows.push(Spans::from(""));
+    rows.push(Spans::from(vec![
+        Span::styled("srsearch=", app.theme.caption()),
+        Span::styled(form.compiled_query(), app.theme.highlighted_snippet_style()),
+    ]));
+    rows.push(Spans::from(vec![
+        Span::styled("srnamespace=", app.theme.caption()),
+        Span::styled(
+            NAMESPACES[form.namespace].1,
+            app.theme.highlighted_snippet_style(),
+        ),
+    ]));
+    frame.render_widget(Paragraph::new(rows).wrap(Wrap { trim: false }), inner);
+}
+
+/// Narrower than this, the results area is left whole instead of making room for the preview
+const MIN_PREVIEW_SPLIT_WIDTH: u16 = 70;
+
+fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
-        .split(frame.size());
+        .split(area);
+
+    // "Did you mean" takes the first row of the results area
+    let mut results_box_area = chunks[1];
+    if let Some(suggestion) = app
+        .search
+        .did_you_mean()
+        .filter(|_| !app.search.advanced.is_open)
+    {
+        let suggestion_area = Rect::new(
+            results_box_area.x,
+            results_box_area.y,
+            results_box_area.width,
+            1,
+        );
+        results_box_area.y += 1;
+        results_box_area.height = results_box_area.height.saturating_sub(1);
+        let suggestion_style = match app.search.suggestion_selected {
+            true => app.theme.selected_option(),
+            false => app.theme.highlighted_snippet_style(),
+        };
+        add_mouse_area(app, suggestion_area, MouseTarget::DidYouMean);
+        frame.render_widget(
+            Paragraph::new(Spans::from(vec![
+                Span::styled(" Did you mean: ", app.theme.unselected_option()),
+                Span::styled(suggestion, suggestion_style),
+                Span::styled("?", app.theme.unselected_option()),
+            ])),
+            suggestion_area,
+        );
+    }
 
     // Search input box
     let text_box_is_highlighted = app.search.text_box_is_highlighted;
@@ -168,10 +574,15 @@ fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
     );
     */
 
-    let input_widget = search_box_widget(&app, &app.search, String::from("Search Wikipedia"))
+    let input_widget = search_box_widget(&app, &app.search.input, String::from("Search Wikipedia"))
         .style(text_block_style);
     frame.render_widget(input_widget, chunks[0]);
 
+    if app.search.advanced.is_open {
+        draw_advanced_search(frame, app, chunks[1]);
+        return;
+    }
+
     let mut is_loading = false;
     if let Ok(is_loading_guard) = app.search.is_loading_query.try_lock() {
         if *is_loading_guard {
@@ -179,6 +590,16 @@ fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
         }
     }
 
+    // The preview takes the right of the results area when there's room for both
+    if app.config.search_preview && results_box_area.width >= MIN_PREVIEW_SPLIT_WIDTH {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(results_box_area);
+        results_box_area = panes[0];
+        draw_search_preview(frame, app, panes[1]);
+    }
+
     let mut available_results: TryLockResult<MutexGuard<'_, Vec<SearchResult>>> =
         Err(TryLockError::WouldBlock);
 
@@ -191,7 +612,14 @@ fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
             // Collect spans into a Vec<Spans>
             // let results = result_guard;
             let selected_index = app.search.selected_index;
-            let all_spans: Vec<Spans> = wrapped_iter_enumerate(&results, app.search.selected_index)
+            let paging = app.search.paging.lock().unwrap().clone();
+            // Results after the last one aren't loaded yet, so the list doesn't wrap around
+            let shown_results = match paging.next_offset {
+                Some(_) => results.len().saturating_sub(selected_index),
+                None => results.len(),
+            };
+            let mut all_spans: Vec<Spans> = wrapped_iter_enumerate(&results, selected_index)
+                .take(shown_results)
                 .flat_map(|(index, search_result)| -> Vec<Spans<'_>> {
                     let title_style = if index == selected_index {
                         app.theme.highlighted_title_style()
@@ -206,32 +634,112 @@ fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
                         ),
                         title_style,
                     );
+                    let mut title_spans = vec![title_span];
+                    if let Some(hint) = search_result.match_hint() {
+                        title_spans.push(Span::styled(format!("  ({hint})"), app.theme.caption()));
+                    }
                     if index == selected_index {
-                        vec![
-                            Spans::from(vec![title_span]),
-                            SearchResult::highlighted_snippets(&search_result, &app.theme),
-                            Spans::from(vec![Span::raw("")]),
-                        ]
+                        let mut lines = vec![Spans::from(title_spans)];
+                        let metadata = search_result.metadata();
+                        if !metadata.is_empty() {
+                            lines.push(Spans::from(Span::styled(metadata, app.theme.caption())));
+                        }
+                        lines.push(SearchResult::highlighted_snippets(
+                            search_result,
+                            &app.theme,
+                        ));
+                        lines.push(Spans::from(vec![Span::raw("")]));
+                        lines
                     } else {
-                        vec![Spans::from(vec![title_span])]
+                        vec![Spans::from(title_spans)]
                     }
                 })
                 .collect(); // Collect spans into a Vec<Spans>
+            if paging.is_loading_more {
+                all_spans.push(Spans::from(Span::styled(
+                    "Loading more results...",
+                    app.theme.loading(),
+                )));
+            }
 
             let result_chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
-                .split(chunks[1]);
+                .split(results_box_area);
 
             // Render the results
+            let mut title_details = Vec::new();
+            if let Some(total_hits) = paging.total_hits {
+                title_details.push(format!("{} of {total_hits}", results.len()));
+            }
+            let options = app.search.options;
+            if options.sort.sorts_each_page() {
+                title_details.push(format!(
+                    "{} by {}",
+                    options.sort.scope(),
+                    options.sort.label()
+                ));
+            } else if options.sort != SearchSort::Relevance {
+                title_details.push(format!("by {}", options.sort.label()));
+            }
+            if options.namespace != 0 {
+                title_details.push(format!("in {}", options.namespace_label()));
+            }
+            let results_title = match title_details.is_empty() {
+                true => String::from("Results"),
+                false => format!("Results ({})", title_details.join(", ")),
+            };
+            let results_block = Block::default().borders(Borders::ALL).title(results_title);
+            let results_area = results_block.inner(result_chunks[0]);
             frame.render_widget(
                 Paragraph::new(all_spans)
                     .style(result_block_style)
-                    .block(Block::default().borders(Borders::ALL).title("Results"))
+                    .block(results_block)
                     .wrap(Wrap { trim: true }),
                 result_chunks[0],
             );
 
+            let mut row = 0;
+            for (index, search_result) in
+                wrapped_iter_enumerate(&results, selected_index).take(shown_results)
+            {
+                let mut title = format!("{} - {}", search_result.title, search_result.pageid);
+                if let Some(hint) = search_result.match_hint() {
+                    title.push_str(&format!("  ({hint})"));
+                }
+                let mut result_height = wrapped_height(&title, results_area.width as usize);
+                if index == selected_index {
+                    let snippet: String =
+                        SearchResult::highlighted_snippets(search_result, &app.theme)
+                            .0
+                            .iter()
+                            .map(|span| span.content.as_ref())
+                            .collect();
+                    result_height += wrapped_height(&snippet, results_area.width as usize) + 1;
+                    let metadata = search_result.metadata();
+                    if !metadata.is_empty() {
+                        result_height += wrapped_height(&metadata, results_area.width as usize);
+                    }
+                }
+                let visible_height =
+                    result_height.min((results_area.height as usize).saturating_sub(row));
+                if visible_height == 0 {
+                    break;
+                }
+                add_mouse_area(
+                    app,
+                    Rect::new(
+                        results_area.x,
+                        results_area.y + row as u16,
+                        results_area.width,
+                        visible_height as u16,
+                    ),
+                    MouseTarget::SearchResult(index),
+                );
+                row += result_height;
+            }
+            add_mouse_area(app, result_chunks[1], MouseTarget::ResultsScrollBar);
+
             let scroll_bar = ScrollBar::new(
                 result_chunks[1].height as usize,
                 app.search.selected_index,
@@ -250,10 +758,122 @@ fn draw_search<'a, B: Backend>(frame: &mut Frame<B>, app: &App) {
                 Paragraph::new(Span::styled(waiting_message, app.theme.loading()))
                     .style(result_block_style)
                     .block(Block::default().borders(Borders::ALL).title("Results")),
-                chunks[1],
+                results_box_area,
             );
         }
     }
+
+    if app.search.showing_suggestions() {
+        draw_suggestions(frame, app, chunks[0], results_box_area);
+    }
+}
+
+/// The summary of the selected result, next to the results
+fn draw_search_preview<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Preview")
+        .style(app.theme.block_border_unfocus());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if app.search.preview.shown_for.is_none() {
+        return;
+    }
+
+    let summary = match &*app.search.preview.summary.lock().unwrap() {
+        SummaryState::Loaded(summary) => summary.clone(),
+        SummaryState::Loading => {
+            frame.render_widget(
+                Paragraph::new(Span::styled("Loading preview...", app.theme.loading())),
+                inner,
+            );
+            return;
+        }
+        SummaryState::Failed => {
+            frame.render_widget(
+                Paragraph::new(Span::styled("[no preview available]", app.theme.caption())),
+                inner,
+            );
+            return;
+        }
+    };
+
+    let mut heading = vec![Spans::from(Span::styled(
+        summary.title.clone(),
+        app.theme.highlighted_title_style(),
+    ))];
+    if let Some(description) = &summary.description {
+        heading.push(Spans::from(Span::styled(
+            description.clone(),
+            app.theme.caption(),
+        )));
+    }
+    let heading_height = heading.len() as u16;
+    frame.render_widget(Paragraph::new(heading), inner);
+
+    // The thumbnail goes under the heading, and gets at most half of what's left
+    let mut text_top = inner.y + heading_height + 1;
+    if let Some(thumbnail) = &summary.thumbnail {
+        let image = ArticleImage {
+            url: thumbnail.source.clone(),
+            width: thumbnail.width,
+            height: thumbnail.height,
+        };
+        let (columns, rows) = image_size_in_cells(&image, inner.width as usize);
+        let rows = rows.min(inner.height.saturating_sub(heading_height + 1) / 2);
+        if rows > 0 {
+            let image_area = Rect::new(inner.x, text_top, columns, rows);
+            draw_article_image(frame, app, &image, image_area, 0, inner.width as usize);
+            text_top += rows + 1;
+        }
+    }
+
+    let text_area = Rect {
+        y: text_top.min(inner.bottom()),
+        height: inner.bottom().saturating_sub(text_top),
+        ..inner
+    };
+    frame.render_widget(
+        Paragraph::new(summary.extract)
+            .style(app.theme.unselected_option())
+            .wrap(Wrap { trim: true }),
+        text_area,
+    );
+}
+
+/// The dropdown of title suggestions, over the top of the results
+fn draw_suggestions<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    app: &App,
+    input_area: Rect,
+    results_area: Rect,
+) {
+    let suggestions = app.search.autocomplete.suggestions.lock().unwrap().clone();
+    let height = (suggestions.len() as u16 + 2).min(results_area.height);
+    let area = Rect::new(input_area.x, input_area.bottom(), input_area.width, height);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Suggestions")
+        .style(app.theme.block_border_focus());
+    let list_area = block.inner(area);
+    for row in 0..suggestions.len().min(list_area.height as usize) {
+        let row_area = Rect::new(list_area.x, list_area.y + row as u16, list_area.width, 1);
+        add_mouse_area(app, row_area, MouseTarget::Suggestion(row));
+    }
+
+    let rows: Vec<Spans> = suggestions
+        .into_iter()
+        .enumerate()
+        .map(|(index, title)| {
+            let style = match app.search.autocomplete.selected_index == Some(index) {
+                true => app.theme.selected_option(),
+                false => app.theme.unselected_option(),
+            };
+            Spans::from(Span::styled(title, style))
+        })
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(rows).block(block), area);
 }
 
 fn create_option_spans<'a>(
@@ -275,21 +895,337 @@ fn create_option_spans<'a>(
         .collect()
 }
 
-fn draw_menu<'a, B: Backend>(frame: &mut Frame<B>, app: &App, menu: &MenuState) {
+fn draw_menu<B: Backend>(frame: &mut Frame<B>, app: &App, menu: &MenuState, area: Rect) {
     let menu_items = create_option_spans(menu.get_options(), menu.get_index(), &app.theme);
 
-    let area = centered_rect(50, 50, frame.size());
+    let area = centered_rect(50, 50, area);
+    let menu_block = Block::default().borders(Borders::ALL).title("Menu");
+    add_menu_rows(app, menu_block.inner(area), 0, menu.total_options());
     frame.render_widget(
         Paragraph::new(menu_items)
             .style(app.theme.block_border_focus())
-            .block(Block::default().borders(Borders::ALL).title("Menu"))
+            .block(menu_block)
             .alignment(Alignment::Center),
         area,
     );
 }
 
-fn draw_credit<B: Backend>(frame: &mut Frame<'_, B>, app: &App) {
-    let area = centered_rect(50, 50, frame.size());
+fn draw_theme_picker<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let picker = &app.theme_picker;
+    let mut picker_text = create_option_spans(
+        picker.menu.get_options(),
+        picker.menu.get_index(),
+        &app.theme,
+    );
+
+    if !picker.load_errors.is_empty() {
+        picker_text.push(Spans::from(""));
+        picker_text.push(Spans::from(Span::styled(
+            "Some theme files couldn't be loaded:",
+            app.theme.loading(),
+        )));
+        for error in picker.load_errors.iter() {
+            picker_text.push(Spans::from(error.as_str()));
+        }
+    }
+
+    let area = centered_rect(50, 50, area);
+    let picker_block = Block::default().borders(Borders::ALL).title("Themes");
+    add_menu_rows(
+        app,
+        picker_block.inner(area),
+        0,
+        picker.menu.total_options(),
+    );
+    frame.render_widget(
+        Paragraph::new(picker_text)
+            .style(app.theme.block_border_focus())
+            .block(picker_block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+fn draw_command_palette<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let palette = &app.command_palette;
+    let area = centered_rect(60, 60, area);
+    frame.render_widget(Clear, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let (title, no_matches) = match palette.kind {
+        PaletteKind::Commands => ("Command", "No matching commands"),
+        PaletteKind::SearchHistory => ("Search history", "No matching searches"),
+    };
+    frame.render_widget(
+        search_box_widget(app, &palette.input, title.to_string())
+            .style(app.theme.block_border_focus()),
+        chunks[0],
+    );
+
+    let list_block = Block::default()
+        .borders(Borders::ALL)
+        .style(app.theme.block_border_focus());
+    let list_area = list_block.inner(chunks[1]);
+    let visible_rows = (list_area.height as usize).max(1);
+    // Keep the selected command on screen
+    let first_index = palette.selected_index.saturating_sub(visible_rows - 1);
+
+    let matches = palette.matches();
+    let rows: Vec<Spans> = match matches.is_empty() {
+        true => vec![Spans::from(Span::styled(
+            no_matches,
+            app.theme.unselected_option(),
+        ))],
+        false => matches
+            .iter()
+            .enumerate()
+            .skip(first_index)
+            .take(visible_rows)
+            .map(|(index, (command, positions))| {
+                let row_style = match index == palette.selected_index {
+                    true => app.theme.selected_option(),
+                    false => app.theme.unselected_option(),
+                };
+                let match_style = row_style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+                Spans::from(
+                    command
+                        .label()
+                        .chars()
+                        .enumerate()
+                        .map(|(i, c)| match positions.contains(&i) {
+                            true => Span::styled(c.to_string(), match_style),
+                            false => Span::styled(c.to_string(), row_style),
+                        })
+                        .collect::<Vec<Span>>(),
+                )
+            })
+            .collect(),
+    };
+    add_menu_rows(
+        app,
+        list_area,
+        first_index,
+        matches.len().saturating_sub(first_index),
+    );
+    frame.render_widget(Paragraph::new(rows).block(list_block), chunks[1]);
+}
+
+fn draw_bookmarks<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let screen = &app.bookmarks_screen;
+    let area = centered_rect(70, 70, area);
+    frame.render_widget(Clear, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(5),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    // The filter keeps its text but not its cursor while a bookmark is being edited
+    let filter_widget = match screen.edit {
+        Some(_) => Paragraph::new(screen.filter.as_str())
+            .block(Block::default().borders(Borders::ALL).title("Filter"))
+            .style(app.theme.block_border_unfocus()),
+        None => search_box_widget(app, &screen.filter, String::from("Filter"))
+            .style(app.theme.block_border_focus()),
+    };
+    frame.render_widget(filter_widget, chunks[0]);
+
+    // Both lists in the title, the one being shown picked out
+    let mut list_title = vec![Span::raw(" ")];
+    for list in [BookmarkList::Bookmarks, BookmarkList::ReadLater] {
+        let label = format!("{} ({})", list.label(), app.bookmarks.in_list(list).len());
+        list_title.push(match list == screen.list {
+            true => Span::styled(label, app.theme.selected_option()),
+            false => Span::raw(label),
+        });
+        list_title.push(Span::raw(" "));
+    }
+    let list_block = Block::default()
+        .borders(Borders::ALL)
+        .title(Spans::from(list_title))
+        .style(app.theme.block_border_focus());
+    let list_area = list_block.inner(chunks[1]);
+    let visible_rows = (list_area.height as usize).max(1);
+    let first_index = screen.selected_index.saturating_sub(visible_rows - 1);
+
+    let visible = app.visible_bookmarks();
+    let rows: Vec<Spans> = match visible.is_empty() {
+        true => vec![Spans::from(Span::styled(
+            match (screen.filter.as_str().is_empty(), screen.list) {
+                (false, _) => "No matching bookmarks",
+                (true, BookmarkList::Bookmarks) => "Nothing bookmarked yet",
+                (true, BookmarkList::ReadLater) => "Nothing saved to read later",
+            },
+            app.theme.unselected_option(),
+        ))],
+        false => visible
+            .iter()
+            .enumerate()
+            .skip(first_index)
+            .take(visible_rows)
+            .map(|(row, &index)| {
+                let bookmark = &app.bookmarks.entries[index];
+                let row_style = match row == screen.selected_index {
+                    true => app.theme.selected_option(),
+                    false => app.theme.unselected_option(),
+                };
+                let mut spans = vec![
+                    Span::styled(bookmark.title.clone(), row_style),
+                    Span::styled(format!("  {}", bookmark.language), app.theme.loading()),
+                ];
+                for tag in bookmark.tags.iter() {
+                    spans.push(Span::styled(format!(" #{tag}"), app.theme.loading()));
+                }
+                Spans::from(spans)
+            })
+            .collect(),
+    };
+    add_menu_rows(
+        app,
+        list_area,
+        first_index,
+        visible.len().saturating_sub(first_index),
+    );
+    frame.render_widget(Paragraph::new(rows).block(list_block), chunks[1]);
+
+    let selected = visible
+        .get(screen.selected_index)
+        .map(|&index| &app.bookmarks.entries[index]);
+    match (&screen.edit, selected) {
+        (Some(edit), _) => {
+            let title = match edit.field {
+                BookmarkField::Note => String::from("Note"),
+                BookmarkField::Tags => String::from("Tags, separated by commas"),
+            };
+            let edit_area = Rect {
+                height: chunks[2].height.min(3),
+                ..chunks[2]
+            };
+            frame.render_widget(
+                search_box_widget(app, &edit.input, title).style(app.theme.block_border_focus()),
+                edit_area,
+            );
+        }
+        (None, Some(bookmark)) => {
+            let mut details = vec![Spans::from(format!(
+                "Added {} · {}.wikipedia.org · line {}",
+                format_timestamp(bookmark.added_at),
+                bookmark.language,
+                bookmark.scroll + 1
+            ))];
+            if !bookmark.note.is_empty() {
+                details.push(Spans::from(bookmark.note.as_str()));
+            }
+            frame.render_widget(
+                Paragraph::new(details)
+                    .block(Block::default().borders(Borders::ALL))
+                    .style(app.theme.block_border_unfocus())
+                    .wrap(Wrap { trim: true }),
+                chunks[2],
+            );
+        }
+        (None, None) => {}
+    }
+}
+
+fn draw_settings<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let settings = &app.settings;
+    let area = centered_rect(70, 70, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Settings")
+            .style(app.theme.block_border_focus()),
+        area,
+    );
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let label_width = Setting::ALL
+        .iter()
+        .map(|setting| setting.label().len())
+        .max()
+        .unwrap_or(0);
+    let row_style = |row: usize| match row == settings.selected_index {
+        true => app.theme.selected_option(),
+        false => app.theme.unselected_option(),
+    };
+    let mut rows: Vec<Spans> = Setting::ALL
+        .iter()
+        .enumerate()
+        .map(|(row, setting)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:<label_width$}  ", setting.label()),
+                    row_style(row),
+                ),
+                Span::raw(setting.value(&app.config)),
+            ])
+        })
+        .collect();
+    rows.push(Spans::from(""));
+    rows.push(Spans::from(Span::styled(
+        format!("Save to {}", app.config_path.display()),
+        row_style(SettingsState::SAVE_ROW),
+    )));
+    rows.push(Spans::from(Span::styled(
+        "Back",
+        row_style(SettingsState::BACK_ROW),
+    )));
+    frame.render_widget(Paragraph::new(rows), chunks[0]);
+    add_menu_rows(app, chunks[0], 0, Setting::ALL.len());
+    // The save and back rows come after a blank line
+    let actions_area = Rect {
+        y: chunks[0].y + Setting::ALL.len() as u16 + 1,
+        height: chunks[0]
+            .height
+            .saturating_sub(Setting::ALL.len() as u16 + 1),
+        ..chunks[0]
+    };
+    add_menu_rows(app, actions_area, SettingsState::SAVE_ROW, 2);
+
+    match settings.selected_setting() {
+        Some(setting) if settings.is_editing => {
+            frame.render_widget(
+                search_box_widget(app, &settings.input, setting.label().to_string()),
+                chunks[1],
+            );
+        }
+        _ => {}
+    }
+
+    if let Some(message) = &settings.message {
+        frame.render_widget(
+            Paragraph::new(message.as_str())
+                .style(app.theme.loading())
+                .wrap(Wrap { trim: true }),
+            chunks[2],
+        );
+    }
+}
+
+fn draw_credit<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let area = centered_rect(50, 50, area);
 
     let mut credit_paragraph_text = vec![Spans::from("Made by Mazza :)")];
 
@@ -299,20 +1235,110 @@ fn draw_credit<B: Backend>(frame: &mut Frame<'_, B>, app: &App) {
         &app.theme,
     ));
 
+    let credit_block = Block::default().borders(Borders::ALL).title("Credit");
+    // The options start below the "Made by" line
+    let options_area = credit_block.inner(area);
+    let options_area = Rect {
+        y: options_area.y + 1,
+        height: options_area.height.saturating_sub(1),
+        ..options_area
+    };
+    add_menu_rows(app, options_area, 0, app.credit.total_options());
     frame.render_widget(
         Paragraph::new(credit_paragraph_text)
             .style(app.theme.block_border_focus())
-            .block(Block::default().borders(Borders::ALL).title("Credit"))
+            .block(credit_block)
             .alignment(Alignment::Center),
         area,
     );
 }
 
-fn draw_title<B: Backend>(frame: &mut Frame<'_, B>, app: &App) {
-    let full_area = centered_rect_by_lengths(30, 12, frame.size());
+/// Lines of the featured article's extract shown on the title screen
+const FEATURED_EXTRACT_LINES: u16 = 4;
+
+fn draw_title<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let items = app.landing_items();
+    let featured = app.title.featured.lock().unwrap().clone();
 
+    // Headings and item rows, remembering which row holds which item for the mouse
+    let item_style = |index: usize| match app.title.selected_item == Some(index) {
+        true => app.theme.selected_option(),
+        false => app.theme.unselected_option(),
+    };
+    let mut rows: Vec<Spans> = Vec::new();
+    let mut item_rows: Vec<(usize, usize)> = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        match item {
+            LandingItem::RecentSearch(query) => {
+                if index == 0 {
+                    rows.push(Spans::from(Span::styled(
+                        "Recent searches",
+                        app.theme.landing_heading(),
+                    )));
+                }
+                item_rows.push((rows.len(), index));
+                rows.push(Spans::from(Span::styled(
+                    format!("  {query}"),
+                    item_style(index),
+                )));
+            }
+            LandingItem::Bookmark(bookmark) => {
+                // A heading starts each list
+                let continues_list = index
+                    .checked_sub(1)
+                    .and_then(|previous| items.get(previous))
+                    .is_some_and(|previous| {
+                        matches!(previous, LandingItem::Bookmark(previous) if previous.list == bookmark.list)
+                    });
+                if !continues_list {
+                    if !rows.is_empty() {
+                        rows.push(Spans::from(""));
+                    }
+                    rows.push(Spans::from(Span::styled(
+                        bookmark.list.label(),
+                        app.theme.landing_heading(),
+                    )));
+                }
+                item_rows.push((rows.len(), index));
+                rows.push(Spans::from(Span::styled(
+                    format!("  {}", bookmark.title),
+                    item_style(index),
+                )));
+            }
+            LandingItem::Article(title) => {
+                if !rows.is_empty() {
+                    rows.push(Spans::from(""));
+                }
+                rows.push(Spans::from(Span::styled(
+                    "Today's featured article",
+                    app.theme.landing_heading(),
+                )));
+                item_rows.push((rows.len(), index));
+                rows.push(Spans::from(Span::styled(
+                    format!("  {title}"),
+                    item_style(index).add_modifier(Modifier::BOLD),
+                )));
+            }
+        }
+    }
+    let extract = featured.map(|featured| match featured.description {
+        Some(description) => format!("{description}. {}", featured.extract),
+        None => featured.extract,
+    });
+    let extract_height = match extract {
+        Some(_) => FEATURED_EXTRACT_LINES,
+        None => 0,
+    };
+
+    let landing_height = rows.len() as u16 + extract_height;
+    let full_area = centered_rect_by_lengths(60, 13 + landing_height, area);
     let title_areas = Layout::default()
-        .constraints(vec![Constraint::Min(0), Constraint::Length(2)])
+        .constraints(vec![
+            Constraint::Length(10),
+            Constraint::Length(3),
+            Constraint::Length(rows.len() as u16),
+            Constraint::Min(0),
+        ])
         .direction(Direction::Vertical)
         .split(full_area);
 
@@ -324,91 +1350,305 @@ fn draw_title<B: Backend>(frame: &mut Frame<'_, B>, app: &App) {
                     .border_type(BorderType::Double),
             )
             .alignment(Alignment::Center),
-        title_areas[0],
+        centered_rect_by_lengths(30, 10, title_areas[0]),
     );
 
-    let input_widget = search_box_widget(&app, &app.title, String::from(""));
-    frame.render_widget(input_widget, title_areas[1]);
+    let input_style = match app.title.selected_item {
+        None => app.theme.block_border_focus(),
+        Some(_) => app.theme.block_border_unfocus(),
+    };
+    let input_widget = search_box_widget(app, &app.title.input, String::from("Search Wikipedia"));
+    frame.render_widget(input_widget.style(input_style), title_areas[1]);
+
+    let list_area = title_areas[2];
+    for (row, index) in item_rows {
+        if row < list_area.height as usize {
+            let row_area = Rect::new(list_area.x, list_area.y + row as u16, list_area.width, 1);
+            add_mouse_area(app, row_area, MouseTarget::MenuItem(index));
+        }
+    }
+    frame.render_widget(Paragraph::new(rows), list_area);
+
+    if let Some(extract) = extract {
+        frame.render_widget(
+            Paragraph::new(extract)
+                .style(app.theme.caption())
+                .block(Block::default().borders(Borders::LEFT))
+                .wrap(Wrap { trim: true }),
+            title_areas[3],
+        );
+    }
 }
 
-fn draw_article<B: Backend>(frame: &mut Frame<'_, B>, app: &App) {
-    let article_content: Vec<Spans> = match app.article.is_loading_article.try_lock() {
+fn formatted_span_style(formatted_span: &FormattedSpan, theme: &Theme) -> Style {
+    if formatted_span.is_heading {
+        return if formatted_span.heading_level > 2 {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::ITALIC)
+        };
+    }
+
+    let mut style = if formatted_span.math_source.is_some() {
+        theme.math()
+    } else if formatted_span.is_caption {
+        theme.caption()
+    } else if formatted_span.is_citation {
+        theme.citation()
+    } else if let Some(link) = &formatted_span.link {
+        theme.link(&link.kind)
+    } else {
+        Style::default()
+    };
+    if formatted_span.is_bold {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if formatted_span.is_italic {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    style
+}
+
+/// Longest a tab's title gets in the tab bar before it's cut short
+const MAX_TAB_TITLE_WIDTH: usize = 24;
+
+/// One row of tab titles, starting late enough that the current tab is on screen
+fn draw_tab_bar<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let labels: Vec<String> = app
+        .tab_titles()
+        .into_iter()
+        .enumerate()
+        .map(|(index, title)| {
+            let title = match title.width() > MAX_TAB_TITLE_WIDTH {
+                true => format!("{}…", title.substring(0, MAX_TAB_TITLE_WIDTH - 1)),
+                false => title.to_string(),
+            };
+            format!(" {}:{} ", index + 1, title.replace('_', " "))
+        })
+        .collect();
+
+    let mut first_tab = 0;
+    while first_tab < app.active_tab
+        && labels[first_tab..=app.active_tab]
+            .iter()
+            .map(|label| label.width() + 1)
+            .sum::<usize>()
+            > area.width as usize
+    {
+        first_tab += 1;
+    }
+
+    let mut x = area.x;
+    let mut tab_spans: Vec<Span> = Vec::new();
+    for (index, label) in labels.into_iter().enumerate().skip(first_tab) {
+        let width = (label.width() as u16).min(area.right().saturating_sub(x));
+        add_mouse_area(app, Rect::new(x, area.y, width, 1), MouseTarget::Tab(index));
+        x = x.saturating_add(width + 1);
+        let style = match index == app.active_tab {
+            true => app.theme.selected_option().add_modifier(Modifier::BOLD),
+            false => app.theme.unselected_option(),
+        };
+        tab_spans.push(Span::styled(label, style));
+        tab_spans.push(Span::styled("│", app.theme.block_border_unfocus()));
+    }
+    frame.render_widget(Paragraph::new(Spans::from(tab_spans)), area);
+}
+
+fn draw_article<B: Backend>(frame: &mut Frame<'_, B>, app: &App, area: Rect) {
+    let area = match app.total_tabs() > 1 {
+        true => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(area);
+            draw_tab_bar(frame, app, chunks[0]);
+            chunks[1]
+        }
+        false => area,
+    };
+
+    let Some(split) = &app.split else {
+        return draw_article_pane(frame, app, &app.article, area, true);
+    };
+    let direction = match split.direction {
+        SplitDirection::Vertical => Direction::Horizontal,
+        SplitDirection::Horizontal => Direction::Vertical,
+    };
+    let panes = Layout::default()
+        .direction(direction)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    let (focused_area, other_area) = match split.focus_second {
+        true => (panes[1], panes[0]),
+        false => (panes[0], panes[1]),
+    };
+    draw_article_pane(frame, app, &split.other, other_area, false);
+    draw_article_pane(frame, app, &app.article, focused_area, true);
+}
+
+fn draw_article_pane<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    app: &App,
+    article: &ArticleState,
+    area: Rect,
+    focused: bool,
+) {
+    let mut title = article.article_name.clone();
+    if app.split.as_ref().is_some_and(|split| split.sync_scroll) {
+        title.push_str(" [synced]");
+    }
+    let article_block = Block::default().borders(Borders::ALL).title(title);
+    let article_area = article_block.inner(area);
+    let border_style = match focused {
+        true => app.theme.block_border_focus(),
+        false => app.theme.block_border_unfocus(),
+    };
+    frame.render_widget(article_block.style(border_style), area);
+
+    let article_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(article_area);
+    let text_area = article_chunks[0];
+    let (width, height) = (text_area.width as usize, text_area.height as usize);
+    article.view_size.set((width, height));
+    if focused {
+        add_mouse_area(app, text_area, MouseTarget::ArticleText);
+        add_mouse_area(app, article_chunks[1], MouseTarget::ArticleScrollBar);
+    } else {
+        add_mouse_area(app, area, MouseTarget::OtherPane);
+    }
+
+    let mut article_images: Vec<(ArticleImage, Rect, u16)> = Vec::new();
+    let article_content: Vec<Spans> = match article.is_loading_article.try_lock() {
         Ok(loading_result) => match *loading_result {
             false => {
-                let vecs_of_formatted_spans = app
-                    .article
-                    .markdown_spans
-                    .lock()
-                    .unwrap()
-                    .deref()
-                    .split(|formatted_span| formatted_span.is_break)
-                    .map(|slice| -> Vec<FormattedSpan> { slice.to_vec() })
-                    .collect::<Vec<Vec<FormattedSpan>>>();
-
-                vecs_of_formatted_spans
+                let spans = article.visible_spans(&app.section_filter);
+                let lines = wrap_spans(&spans, width);
+                let selected_link = article.selected_link_spans(&spans);
+                let max_scroll = lines.len().saturating_sub(height);
+                let scroll = article.scroll.min(max_scroll);
+                article.max_scroll.set(max_scroll);
+                article.word_count.set(
+                    spans
+                        .iter()
+                        .map(|span| span.text.split_whitespace().count())
+                        .sum(),
+                );
+
+                let scroll_bar = ScrollBar::new(height, scroll, max_scroll + 1)
+                    .bar_style(Style::default().fg(app.theme.secondary))
+                    .handle_style(Style::default().fg(app.theme.tertiary));
+                frame.render_widget(scroll_bar, article_chunks[1]);
+
+                // Images cover a run of consecutive wrapped lines, which may be cut off at the top
+                let visible_lines = &lines[scroll..(scroll + height).min(lines.len())];
+                let mut y = 0;
+                while y < visible_lines.len() {
+                    let image_span = visible_lines[y]
+                        .image_row
+                        .and(visible_lines[y].fragments.first())
+                        .map(|fragment| fragment.span_index);
+                    let run = visible_lines[y..]
+                        .iter()
+                        .take_while(|line| {
+                            line.image_row.is_some()
+                                && line.fragments.first().map(|fragment| fragment.span_index)
+                                    == image_span
+                        })
+                        .count()
+                        .max(1);
+                    if let Some(image) = image_span.and_then(|index| spans[index].image.as_ref()) {
+                        let (columns, _) = image_size_in_cells(image, width);
+                        let first_row = visible_lines[y].image_row.unwrap_or(0) as u16;
+                        let area =
+                            Rect::new(text_area.x, text_area.y + y as u16, columns, run as u16);
+                        article_images.push((image.clone(), area, first_row));
+                    }
+                    y += run;
+                }
+
+                lines
                     .iter()
-                    .enumerate()
-                    .map(|(_, formatted_spans)| -> Spans {
+                    .skip(scroll)
+                    .take(height)
+                    .map(|line| -> Spans {
                         Spans::from(
-                            formatted_spans
+                            line.fragments
                                 .iter()
-                                .enumerate()
-                                .map(|(_, formatted_span)| -> Span {
-                                    if formatted_span.is_heading {
-                                        Span::styled(
-                                            formatted_span.text.clone(),
-                                            if formatted_span.heading_level > 2 {
-                                                Style::default().add_modifier(Modifier::BOLD)
-                                            } else {
-                                                Style::default()
-                                                    .add_modifier(Modifier::BOLD)
-                                                    .add_modifier(Modifier::ITALIC)
-                                            },
-                                        )
-                                    } else if let Some(link) = &formatted_span.link {
-                                        Span::styled(
-                                            formatted_span.text.clone(),
-                                            Style::default().add_modifier(Modifier::UNDERLINED),
-                                        )
+                                .map(|fragment| -> Span {
+                                    let style = if selected_link.contains(&fragment.span_index) {
+                                        app.theme.selected_link()
                                     } else {
-                                        Span::raw(formatted_span.text.clone())
-                                    }
+                                        formatted_span_style(
+                                            &spans[fragment.span_index],
+                                            &app.theme,
+                                        )
+                                    };
+                                    Span::styled(fragment.text.clone(), style)
                                 })
                                 .collect::<Vec<Span>>(),
                         )
                     })
                     .collect()
             }
-            /*
-            Vec<Span> {
-                if formatted_span.is_break {
-                    vec![Span::raw("\n")]
-                } else if formatted_span.is_heading {
-                    vec![Span::styled(
-                        formatted_span.text.clone(),
-                        Style::default().add_modifier(Modifier::BOLD),
-                    )]
-                } else if let Some(link) = &formatted_span.link {
-                    vec![Span::raw(formatted_span.text.clone())]
-                } else {
-                    vec![Span::raw(formatted_span.text.clone())]
-                }
-            })
-            .collect::<Vec<Span>>()
-            */
             true => vec![Spans::from(vec![Span::raw("Loading...")])],
         },
         Err(_) => vec![Spans::from(vec![Span::raw("Error loading page...")])],
     };
     frame.render_widget(
-        Paragraph::new(article_content)
-            .style(app.theme.block_border_focus())
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(app.article.article_name.clone()),
-            )
-            .wrap(Wrap { trim: true }),
-        frame.size(),
+        Paragraph::new(article_content).style(border_style),
+        text_area,
     );
+
+    for (image, area, first_row) in article_images {
+        draw_article_image(frame, app, &image, area, first_row, width);
+    }
+}
+
+fn draw_article_image<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    app: &App,
+    image: &ArticleImage,
+    area: Rect,
+    first_row: u16,
+    view_width: usize,
+) {
+    let (_, total_rows) = image_size_in_cells(image, view_width);
+    let images = app.images.lock().unwrap();
+    match images.get(&image.url) {
+        Some(ImageState::Loaded(loaded_image)) => {
+            let fully_visible = first_row == 0 && area.height == total_rows;
+            if app.graphics_protocol != GraphicsProtocol::HalfBlocks && fully_visible {
+                // Left blank here, `GraphicsOutput` draws over it after the frame
+                app.image_placements.borrow_mut().push(ImagePlacement {
+                    url: image.url.clone(),
+                    area,
+                });
+            } else {
+                let mut half_block_images = app.half_block_images.borrow_mut();
+                let pixels = half_block_images.get(
+                    &image.url,
+                    loaded_image,
+                    area.width as u32,
+                    total_rows as u32 * 2,
+                    app.theme.background_rgb(),
+                );
+                frame.render_widget(
+                    HalfBlockImage::new(pixels, total_rows).first_row(first_row),
+                    area,
+                );
+            }
+        }
+        Some(ImageState::Failed) => frame.render_widget(
+            Paragraph::new(Span::styled("[image unavailable]", app.theme.caption())),
+            area,
+        ),
+        _ => frame.render_widget(
+            Paragraph::new(Span::styled("Loading image...", app.theme.loading())),
+            area,
+        ),
+    }
 }
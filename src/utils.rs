@@ -2,6 +2,7 @@ use std::num;
 use std::ops::{Add, Rem, Sub};
 use std::process::Output;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rand::seq::index;
 
@@ -17,6 +18,49 @@ pub fn shared_copy<T>(value_to_copy: &Shared<T>) -> Shared<T> {
     Arc::clone(value_to_copy)
 }
 
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Today's date in UTC, as (year, month, day)
+pub fn today_utc() -> (i64, u32, u32) {
+    civil_from_days((unix_now() / 86_400) as i64)
+}
+
+/// `seconds` since the Unix epoch as a UTC date and time, like "2024-05-01 13:45"
+pub fn format_timestamp(seconds: u64) -> String {
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let minutes = seconds % 86_400 / 60;
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02}",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// The calendar date `days` after 1970-01-01, from Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 pub fn remainder<T: Add + Sub + Rem + Copy>(
     dividend: T,
     divisor: T,
@@ -35,6 +79,10 @@ pub fn hex_to_rgb(hex: &str) -> Result<(u8, u8, u8), String> {
     if hex.len() != 6 {
         return Err("Hex code must be 6 characters long".to_string());
     }
+    // Checked before slicing, which would panic in the middle of a multi-byte character
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("\"{hex}\" isn't a hex code"));
+    }
 
     let red = u8::from_str_radix(&hex[0..2], 16).map_err(|_| "Invalid red component")?;
     let green = u8::from_str_radix(&hex[2..4], 16).map_err(|_| "Invalid green component")?;
@@ -43,6 +91,56 @@ pub fn hex_to_rgb(hex: &str) -> Result<(u8, u8, u8), String> {
     Ok((red, green, blue))
 }
 
+/// Decodes `%XX` escapes (as found in Parsoid `href`s) back into UTF-8 text
+pub fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex_digits = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex_digits, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Matches `query` against `candidate` as a case-insensitive subsequence, scoring runs of
+/// consecutive characters and matches at the start of words higher. Gives the score and the
+/// positions of the matched characters, or `None` if the query doesn't match.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let mut positions: Vec<usize> = Vec::new();
+    let mut score = 0;
+    let mut next = 0;
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..candidate_chars.len()).find(|&i| {
+            candidate_chars[i]
+                .to_lowercase()
+                .eq(query_char.to_lowercase())
+        })?;
+        score += 1;
+        if found > 0 && positions.last() == Some(&(found - 1)) {
+            score += 5;
+        }
+        if found == 0 || !candidate_chars[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - next) as i64;
+        positions.push(found);
+        next = found + 1;
+    }
+
+    Some((score, positions))
+}
+
 pub fn wrapped_iter_enumerate<T>(vec: &Vec<T>, start: usize) -> impl Iterator<Item = (usize, &T)> {
     let len = vec.len();
     (0..len).map(move |i| {
@@ -58,3 +156,45 @@ pub const wik_title: &str = r"
 [ \ [ \ [  ][  |  | '' <   
  \ \/\ \/ /  | |  | |`\ \  
   \__/\__/  [___][__|  \_] ";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked<'a>(query: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+        let mut scored: Vec<(i64, &str)> = candidates
+            .iter()
+            .filter_map(|candidate| Some((fuzzy_match(query, candidate)?.0, *candidate)))
+            .collect();
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, candidate)| candidate).collect()
+    }
+
+    #[test]
+    fn ranks_runs_and_word_starts_first() {
+        // A run of the query beats the same letters spread out
+        assert_eq!(
+            ranked("tab", &["Settings about", "Close tab"]),
+            ["Close tab", "Settings about"]
+        );
+        // A letter starting a word beats an earlier one inside a word
+        assert_eq!(ranked("h", &["Show", "Go home"]), ["Go home", "Show"]);
+        // Equally good matches keep their order
+        assert_eq!(ranked("o", &["Go", "No"]), ["Go", "No"]);
+        // Candidates that don't contain the query in order are left out
+        assert_eq!(
+            ranked("bookmark", &["Open bookmarks", "Go back", "Mark book"]),
+            ["Open bookmarks"]
+        );
+    }
+
+    #[test]
+    fn matches_case_insensitively_by_character() {
+        // "ō" isn't "o", so the last letter is found in the second word
+        assert_eq!(fuzzy_match("KYO", "Tōkyō kyoto"), Some((2, vec![2, 3, 8])));
+        let (_, positions) = fuzzy_match("東 京", "Open 東京都").unwrap();
+        assert_eq!(positions, [5, 6]);
+        assert_eq!(fuzzy_match("", "anything"), Some((0, Vec::new())));
+        assert_eq!(fuzzy_match("x", ""), None);
+    }
+}
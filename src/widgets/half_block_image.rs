@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use image::{RgbImage, RgbaImage};
+use tui::{
+    style::{Color, Style},
+    widgets::Widget,
+};
+
+use crate::images::flatten;
+
+/// Draws an image with "▀" characters, the foreground colouring the top pixel of each cell and
+/// the background the bottom one. Works in any terminal with true colour.
+pub struct HalfBlockImage<'a> {
+    /// Already scaled to the width of the area and two pixels per row
+    pixels: &'a RgbImage,
+    total_rows: u16,
+    first_row: u16,
+}
+
+impl<'a> HalfBlockImage<'a> {
+    pub fn new(pixels: &'a RgbImage, total_rows: u16) -> Self {
+        Self {
+            pixels,
+            total_rows,
+            first_row: 0,
+        }
+    }
+
+    /// Skips the top rows of the image, for when it is partly scrolled out of view
+    pub fn first_row(mut self, first_row: u16) -> Self {
+        self.first_row = first_row;
+        self
+    }
+}
+
+impl<'a> Widget for HalfBlockImage<'a> {
+    fn render(self, area: tui::layout::Rect, buf: &mut tui::buffer::Buffer) {
+        let colour_at = |x: u16, y: u32| match self.pixels.get_pixel_checked(x as u32, y) {
+            Some(pixel) => {
+                let [red, green, blue] = pixel.0;
+                Color::Rgb(red, green, blue)
+            }
+            None => Color::Reset,
+        };
+
+        let visible_rows = area
+            .height
+            .min(self.total_rows.saturating_sub(self.first_row));
+        for row in 0..visible_rows {
+            let top_pixel = (self.first_row + row) as u32 * 2;
+            for x in 0..area.width {
+                buf.set_string(
+                    area.x + x,
+                    area.y + row,
+                    "▀",
+                    Style::default()
+                        .fg(colour_at(x, top_pixel))
+                        .bg(colour_at(x, top_pixel + 1)),
+                );
+            }
+        }
+    }
+}
+
+/// The width, height and background an image was scaled for
+type ScaledFor = (u32, u32, (u8, u8, u8));
+
+/// Images scaled for `HalfBlockImage`, by URL, so they aren't resized on every frame. Only the
+/// size each one was last drawn at is kept.
+#[derive(Default)]
+pub struct HalfBlockCache {
+    scaled: HashMap<String, (ScaledFor, RgbImage)>,
+}
+
+impl HalfBlockCache {
+    /// `image` scaled to `width` x `height` pixels over `background`
+    pub fn get(
+        &mut self,
+        url: &str,
+        image: &RgbaImage,
+        width: u32,
+        height: u32,
+        background: (u8, u8, u8),
+    ) -> &RgbImage {
+        let scaled_for = (width, height, background);
+        let entry = self
+            .scaled
+            .entry(url.to_string())
+            .or_insert_with(|| (scaled_for, flatten(image, width, height, background)));
+        if entry.0 != scaled_for {
+            *entry = (scaled_for, flatten(image, width, height, background));
+        }
+        &entry.1
+    }
+}
@@ -1,2 +1,4 @@
+pub mod half_block_image;
 pub mod scrollbar;
+pub use half_block_image::{HalfBlockCache, HalfBlockImage};
 pub use scrollbar::ScrollBar;
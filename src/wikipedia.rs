@@ -1,204 +1,1052 @@
-use htmd::HtmlToMarkdown;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use std::clone;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use std::{error::Error, result, sync::Arc, thread};
 use tui::text::{Span, Spans};
 
-use crate::parsing::FormattedSpan;
+use crate::images::{self, ImageStore};
+use crate::parsing::{FormattedSpan, Snippet};
 use crate::{app::App, caching::CachingSession, styles::Theme, utils::Shared};
-use crate::{caching, parsing};
+use crate::{caching, parsing, utils};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SearchResult {
     pub title: String,
     pub pageid: i32,
+    /// HTML, with the matches in `<span class="searchmatch">`
     pub snippet: String,
+    /// `snippet` as text, filled in once the results are loaded
+    #[serde(skip)]
+    pub parsed_snippet: Snippet,
+    /// The redirect to this page that matched the query
+    #[serde(default)]
+    pub redirecttitle: Option<String>,
+    /// The section of this page that matched the query
+    #[serde(default)]
+    pub sectiontitle: Option<String>,
+    /// Page size in bytes
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub wordcount: Option<u64>,
+    /// When the page was last edited, as an ISO 8601 timestamp
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    /// The categories that matched the query, marked up like `snippet`
+    #[serde(default)]
+    pub categorysnippet: Option<String>,
 }
 
-const OPENING_TAG: &str = "<span class=\"searchmatch\">";
-const CLOSING_TAG: &str = "</span>";
+/// How search results are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchSort {
+    #[default]
+    Relevance,
+    LastEdited,
+    Created,
+    Size,
+    Alphabetical,
+}
+
+impl SearchSort {
+    pub const ALL: [SearchSort; 5] = [
+        SearchSort::Relevance,
+        SearchSort::LastEdited,
+        SearchSort::Created,
+        SearchSort::Size,
+        SearchSort::Alphabetical,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchSort::Relevance => "relevance",
+            SearchSort::LastEdited => "last edited",
+            SearchSort::Created => "creation date",
+            SearchSort::Size => "size",
+            SearchSort::Alphabetical => "title",
+        }
+    }
+
+    /// The `srsort` value to ask the API for. It can't sort by size or title, so those are
+    /// fetched by relevance and each page is sorted as it loads.
+    fn srsort(&self) -> &'static str {
+        match self {
+            SearchSort::LastEdited => "last_edit_desc",
+            SearchSort::Created => "create_timestamp_desc",
+            SearchSort::Relevance | SearchSort::Size | SearchSort::Alphabetical => "relevance",
+        }
+    }
+
+    /// Whether results are sorted after loading, a page at a time, rather than by the API
+    pub fn sorts_each_page(&self) -> bool {
+        matches!(self, SearchSort::Size | SearchSort::Alphabetical)
+    }
+
+    /// What gets put in order: "results", or "this page" for the sorts done a page at a time
+    pub fn scope(&self) -> &'static str {
+        match self.sorts_each_page() {
+            true => "this page",
+            false => "results",
+        }
+    }
+
+    /// Puts a loaded page in order, for the sorts the API can't do
+    fn sort_page(&self, results: &mut [SearchResult]) {
+        match self {
+            SearchSort::Size => results.sort_by_key(|result| std::cmp::Reverse(result.size)),
+            SearchSort::Alphabetical => results.sort_by_key(|result| result.title.to_lowercase()),
+            _ => {}
+        }
+    }
+
+    pub fn next(&self) -> SearchSort {
+        let index = SearchSort::ALL
+            .iter()
+            .position(|sort| sort == self)
+            .unwrap_or(0);
+        SearchSort::ALL[(index + 1) % SearchSort::ALL.len()]
+    }
+}
+
+/// Namespaces a search can be limited to, by label and `srnamespace` value
+pub const NAMESPACES: [(&str, &str); 9] = [
+    ("Articles", "0"),
+    ("Talk", "1"),
+    ("User", "2"),
+    ("Wikipedia", "4"),
+    ("File", "6"),
+    ("Template", "10"),
+    ("Help", "12"),
+    ("Category", "14"),
+    ("All", "*"),
+];
+
+/// How a query is run: the order of its results and the namespace they come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub sort: SearchSort,
+    /// Index into `NAMESPACES`, articles by default
+    pub namespace: usize,
+}
+
+impl SearchOptions {
+    pub fn namespace_label(&self) -> &'static str {
+        NAMESPACES[self.namespace].0
+    }
+
+    fn srnamespace(&self) -> &'static str {
+        NAMESPACES[self.namespace].1
+    }
+}
+
+/// The CirrusSearch operators the advanced search form has a field for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOperator {
+    Words,
+    InTitle,
+    InCategory,
+    LinksTo,
+    HasTemplate,
+    InSource,
+}
+
+impl SearchOperator {
+    pub const ALL: [SearchOperator; 6] = [
+        SearchOperator::Words,
+        SearchOperator::InTitle,
+        SearchOperator::InCategory,
+        SearchOperator::LinksTo,
+        SearchOperator::HasTemplate,
+        SearchOperator::InSource,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchOperator::Words => "Words",
+            SearchOperator::InTitle => "Title contains",
+            SearchOperator::InCategory => "In category",
+            SearchOperator::LinksTo => "Links to",
+            SearchOperator::HasTemplate => "Uses template",
+            SearchOperator::InSource => "Source contains",
+        }
+    }
+
+    /// The keyword in front of the value, `None` for plain search words
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            SearchOperator::Words => None,
+            SearchOperator::InTitle => Some("intitle"),
+            SearchOperator::InCategory => Some("incategory"),
+            SearchOperator::LinksTo => Some("linksto"),
+            SearchOperator::HasTemplate => Some("hastemplate"),
+            SearchOperator::InSource => Some("insource"),
+        }
+    }
+}
+
+/// Builds a `srsearch` query out of a value per operator, leaving out the empty ones
+pub fn compile_search_query(values: &[(SearchOperator, &str)]) -> String {
+    values
+        .iter()
+        .filter_map(|(operator, value)| {
+            let value = value.trim();
+            if value.is_empty() {
+                return None;
+            }
+            Some(match operator.keyword() {
+                None => value.to_string(),
+                // A /regular expression/ can't be quoted
+                Some("insource") if value.starts_with('/') => format!("insource:{value}"),
+                Some(keyword) if value.contains(char::is_whitespace) => {
+                    format!("{keyword}:\"{}\"", value.replace('"', ""))
+                }
+                Some(keyword) => format!("{keyword}:{value}"),
+            })
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Most title suggestions shown under the search box
+const MAX_SUGGESTIONS: u16 = 8;
+
+/// Where a loaded page came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSource {
+    Cache,
+    Network,
+}
+
+/// How the last page on a screen was loaded, for the status bar
+#[derive(Debug, Clone, Copy)]
+pub struct LoadInfo {
+    pub source: PageSource,
+    pub duration: Duration,
+}
+
+/// Which sections are stripped from articles, per wiki language.
+///
+/// A language with no entry, or an empty list, keeps every section.
+#[derive(Debug, Clone)]
+pub struct SectionFilter {
+    pub hidden_sections: HashMap<String, Vec<String>>,
+}
 
-const SEARCH_RESULT_LIMIT: u16 = 25;
+impl Default for SectionFilter {
+    fn default() -> Self {
+        let defaults: [(&str, &[&str]); 4] = [
+            (
+                "en",
+                &[
+                    "Notes",
+                    "References",
+                    "Citations",
+                    "Sources",
+                    "Footnotes",
+                    "Further reading",
+                    "External links",
+                ],
+            ),
+            (
+                "de",
+                &["Einzelnachweise", "Anmerkungen", "Literatur", "Weblinks"],
+            ),
+            (
+                "fr",
+                &[
+                    "Notes et références",
+                    "Notes",
+                    "Références",
+                    "Bibliographie",
+                    "Liens externes",
+                ],
+            ),
+            (
+                "es",
+                &["Notas", "Referencias", "Bibliografía", "Enlaces externos"],
+            ),
+        ];
+
+        Self {
+            hidden_sections: defaults
+                .iter()
+                .map(|(language, sections)| {
+                    (
+                        language.to_string(),
+                        sections.iter().map(|section| section.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SectionFilter {
+    pub fn sections_for(&self, language: &str) -> &[String] {
+        match self.hidden_sections.get(language) {
+            Some(sections) => sections,
+            None => &[],
+        }
+    }
+
+    pub fn is_hidden(&self, language: &str, heading: &str) -> bool {
+        let heading = heading.trim().to_lowercase();
+        self.sections_for(language)
+            .iter()
+            .any(|section| section.trim().to_lowercase() == heading)
+    }
+}
+
+pub fn wikipedia_host(language: &str) -> String {
+    format!("https://{language}.wikipedia.org")
+}
 
 impl SearchResult {
+    /// Says which redirect or section matched, when it wasn't the page title itself
+    pub fn match_hint(&self) -> Option<String> {
+        match (&self.redirecttitle, &self.sectiontitle) {
+            (Some(redirect), Some(section)) => {
+                Some(format!("from \"{redirect}\", section \"{section}\""))
+            }
+            (Some(redirect), None) => Some(format!("from \"{redirect}\"")),
+            (None, Some(section)) => Some(format!("section \"{section}\"")),
+            (None, None) => None,
+        }
+    }
+
+    /// Word count, size, last edit date and matching categories, whichever are known
+    pub fn metadata(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(wordcount) = self.wordcount {
+            parts.push(format!("{wordcount} words"));
+        }
+        if let Some(size) = self.size {
+            parts.push(match size {
+                0..=1023 => format!("{size} B"),
+                _ => format!("{:.1} KB", size as f64 / 1024.0),
+            });
+        }
+        if let Some(timestamp) = &self.timestamp {
+            let date = timestamp.split('T').next().unwrap_or(timestamp);
+            parts.push(format!("edited {date}"));
+        }
+        if let Some(categories) = self
+            .categorysnippet
+            .as_ref()
+            .filter(|categories| !categories.is_empty())
+        {
+            parts.push(format!("in {}", parsing::parse_snippet(categories).text));
+        }
+        parts.join(" · ")
+    }
+
     pub fn highlighted_snippets<'a>(
         search_results: &'a SearchResult,
         theme: &'a Theme,
     ) -> Spans<'a> {
-        // The search results from the Wikipedia API encloses the parts of the snippet
-        // that matches the search term with the values in OPENING_TAG and then CLOSING_TAG,
-        // This provides the snippet with the matches highlighted.
-
+        let snippet = &search_results.parsed_snippet;
         let mut spans = Vec::new();
-        let parts: Vec<&str> = search_results.snippet.split(OPENING_TAG).collect();
-
-        for (i, &part) in parts.iter().enumerate() {
-            // the strings in `part` will start with the highlighted text, followed by closing tag, and then rest of text
-            // unless its the first part, in which case its just the starting unhighlighted bit
-            if i > 0 {
-                let highlighted_part = part.split(CLOSING_TAG).next().unwrap_or("");
-                let remaining_part = part.split(CLOSING_TAG).nth(1).unwrap_or("");
-                spans.push(Span::styled(
-                    highlighted_part.to_string(),
-                    theme.highlighted_snippet_style(),
-                ));
-                spans.push(Span::styled(
-                    remaining_part.to_string(),
-                    theme.unhighlighted_snippet_style(),
-                ));
-            } else {
-                spans.push(Span::styled(part, theme.unhighlighted_snippet_style()));
-            }
+        let mut position = 0;
+        for highlight in &snippet.highlights {
+            spans.push(Span::styled(
+                &snippet.text[position..highlight.start],
+                theme.unhighlighted_snippet_style(),
+            ));
+            spans.push(Span::styled(
+                &snippet.text[highlight.clone()],
+                theme.highlighted_snippet_style(),
+            ));
+            position = highlight.end;
         }
-        return Spans::from(spans);
+        spans.push(Span::styled(
+            &snippet.text[position..],
+            theme.unhighlighted_snippet_style(),
+        ));
+        Spans::from(spans)
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+struct SearchInfo {
+    totalhits: u64,
+    /// A respelling of the query that would find more, like "did you mean"
+    suggestion: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Query {
     search: Vec<SearchResult>,
+    searchinfo: Option<SearchInfo>,
+}
+
+/// Where the next page of results starts, if there is one
+#[derive(Debug, Deserialize, Serialize)]
+struct SearchContinue {
+    sroffset: u32,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WikiSearchResponse {
     pub query: Query,
+    #[serde(rename = "continue")]
+    continuation: Option<SearchContinue>,
+}
+
+/// The parts of the search state a loading thread fills in
+pub struct SearchOutput {
+    pub loading_flag: Shared<bool>,
+    pub results: Shared<Vec<SearchResult>>,
+    pub paging: Shared<SearchPaging>,
+    pub load_info: Shared<Option<LoadInfo>>,
+}
+
+/// One page of search results
+#[derive(Debug)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    pub total_hits: Option<u64>,
+    pub suggestion: Option<String>,
+    pub next_offset: Option<u32>,
+}
+
+impl From<WikiSearchResponse> for SearchPage {
+    fn from(response: WikiSearchResponse) -> Self {
+        Self {
+            results: response.query.search,
+            total_hits: response
+                .query
+                .searchinfo
+                .as_ref()
+                .map(|info| info.totalhits),
+            suggestion: response.query.searchinfo.and_then(|info| info.suggestion),
+            // The API leaves out "continue" on the last page
+            next_offset: response
+                .continuation
+                .map(|continuation| continuation.sroffset),
+        }
+    }
+}
+
+/// What is known so far about a query's results, and how far through them the app has got
+#[derive(Debug, Clone, Default)]
+pub struct SearchPaging {
+    /// The query the pages belong to
+    pub query: String,
+    pub total_hits: Option<u64>,
+    /// The query the wiki suggests instead
+    pub suggestion: Option<String>,
+    /// Where the next page starts, `None` once every result is loaded
+    pub next_offset: Option<u32>,
+    pub is_loading_more: bool,
+    /// The order and namespace the pages were asked for with
+    pub options: SearchOptions,
+}
+
+/// Today's featured article, as shown on the title screen
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FeaturedArticle {
+    pub title: String,
+    pub description: Option<String>,
+    pub extract: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedTitles {
+    normalized: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedArticle {
+    titles: FeedTitles,
+    description: Option<String>,
+    extract: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeaturedFeedResponse {
+    tfa: Option<FeedArticle>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WikiPageData {
     pub title: String,
-    pub markdown_content: String,
+    pub spans: Vec<FormattedSpan>,
+}
+
+/// The API request for a page of results for `query`, starting at result `offset`
+fn search_url(
+    query: &str,
+    language: &str,
+    result_limit: u16,
+    offset: u32,
+    options: SearchOptions,
+) -> Result<reqwest::Url, Box<dyn Error>> {
+    let mut url = reqwest::Url::parse(&format!(
+        "{}/w/api.php?action=query&list=search&srlimit={result_limit}&sroffset={offset}&srsort={}&srnamespace={}&srprop=snippet|redirecttitle|sectiontitle|size|wordcount|timestamp|categorysnippet&srinfo=totalhits|suggestion&format=json",
+        wikipedia_host(language),
+        options.sort.srsort(),
+        options.srnamespace()
+    ))?;
+    // Operators like insource:/regex/ can contain characters that mean something in a URL
+    url.query_pairs_mut().append_pair("srsearch", query);
+    Ok(url)
 }
 
 pub fn get_wikipedia_query(
     query: &str,
+    language: &str,
     shared_caching_session: Shared<CachingSession>,
-) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+    result_limit: u16,
+    offset: u32,
+    options: SearchOptions,
+) -> Result<(SearchPage, PageSource), Box<dyn Error>> {
+    let url = search_url(query, language, result_limit, offset, options)?.to_string();
+    // Searches in other threads can use the cache while this one waits on the API
+    let cached = shared_caching_session
+        .lock()
+        .unwrap()
+        .get_from_cache::<WikiSearchResponse>(&url);
+    let (response, source) = match cached {
+        Some(response) => (response, PageSource::Cache),
+        None => {
+            let response = Client::new()
+                .get(&url)
+                .send()?
+                .json::<WikiSearchResponse>()?;
+            shared_caching_session
+                .lock()
+                .unwrap()
+                .write_to_cache(&url, &response)?;
+            (response, PageSource::Network)
+        }
+    };
+
+    Ok((SearchPage::from(response), source))
+}
+
+pub fn get_wikipedia_page(
+    page_title: &str,
+    language: &str,
+    shared_caching_session: Shared<CachingSession>,
+) -> Result<(Vec<FormattedSpan>, PageSource), Box<dyn Error>> {
     let url = format!(
-        "https://en.wikipedia.org/w/api.php?action=query&list=search&srsearch={}&srlimit={SEARCH_RESULT_LIMIT}&format=json",
-        query
+        "{}/w/rest.php/v1/page/{page_title}/html",
+        wikipedia_host(language)
     );
-    let mut caching_session = shared_caching_session.lock().unwrap();
+    let cached = shared_caching_session
+        .lock()
+        .unwrap()
+        .get_from_cache::<WikiPageData>(&url);
+    if let Some(page_data) = cached {
+        return Ok((page_data.spans, PageSource::Cache));
+    }
 
-    let query_response: Option<WikiSearchResponse> = match caching_session.has_url(&url) {
-        true => {
-            // get form cache
-            caching_session.get_from_cache::<WikiSearchResponse>(&url)
-        }
-        false => {
-            let client = Client::new();
-            let fresh_response = client.get(&url).send()?.json::<WikiSearchResponse>()?;
-            caching_session.write_to_cache(&url, &fresh_response)?;
-            Some(fresh_response)
-        }
+    // Big articles take a while to download and parse, so the cache is only locked to store it
+    let html_content = reqwest::blocking::get(&url)
+        .and_then(|response| response.text())
+        .map_err(|_| "Could not get page data")?;
+    let page_data = WikiPageData {
+        title: page_title.to_string(),
+        spans: parsing::parse_html(&html_content),
     };
+    shared_caching_session
+        .lock()
+        .unwrap()
+        .write_to_cache(&url, &page_data)?;
+    Ok((page_data.spans, PageSource::Network))
+}
 
-    match query_response {
-        Some(response) => Ok(response.query.search),
-        None => Err("Could not get the query".into()),
+/// Today's featured article, which only some wikis have
+pub fn get_featured_article(
+    language: &str,
+    shared_caching_session: Shared<CachingSession>,
+) -> Result<FeaturedArticle, Box<dyn Error>> {
+    let (year, month, day) = utils::today_utc();
+    let url = format!(
+        "{}/api/rest_v1/feed/featured/{year}/{month:02}/{day:02}",
+        wikipedia_host(language)
+    );
+    // Left unlocked while the feed downloads
+    let cached = shared_caching_session
+        .lock()
+        .unwrap()
+        .get_from_cache::<FeaturedArticle>(&url);
+    if let Some(featured) = cached {
+        return Ok(featured);
     }
+
+    let feed = Client::new()
+        .get(&url)
+        .send()?
+        .json::<FeaturedFeedResponse>()?;
+    let article = feed
+        .tfa
+        .ok_or(format!("{language}.wikipedia.org has no featured article"))?;
+    let featured = FeaturedArticle {
+        title: article.titles.normalized,
+        description: article.description,
+        extract: article.extract,
+    };
+    shared_caching_session
+        .lock()
+        .unwrap()
+        .write_to_cache(&url, &featured)?;
+    Ok(featured)
 }
 
-pub fn get_wikipedia_page(
+pub fn load_featured_article_to_app(
+    language: String,
+    featured: Shared<Option<FeaturedArticle>>,
+    cache: Shared<CachingSession>,
+) {
+    *featured.lock().unwrap() = None;
+    thread::spawn(move || {
+        if let Ok(article) = get_featured_article(&language, cache) {
+            *featured.lock().unwrap() = Some(article);
+        }
+    });
+}
+
+/// The REST summary of a page, shown in the search preview pane
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PageSummary {
+    pub title: String,
+    pub description: Option<String>,
+    pub extract: String,
+    pub thumbnail: Option<SummaryThumbnail>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SummaryThumbnail {
+    pub source: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where a preview is at, like `ImageState` for images
+#[derive(Debug, Clone)]
+pub enum SummaryState {
+    Loading,
+    Loaded(PageSummary),
+    Failed,
+}
+
+pub fn get_page_summary(
     page_title: &str,
+    language: &str,
     shared_caching_session: Shared<CachingSession>,
-) -> Result<Vec<FormattedSpan>, Box<dyn Error>> {
-    let url = format!("https://en.wikipedia.org/w/rest.php/v1/page/{page_title}/html");
-    let mut caching_session = shared_caching_session.lock().unwrap();
-
-    let page_data_response: Option<WikiPageData> = match caching_session.has_url(&url.clone()) {
-        true => caching_session.get_from_cache::<WikiPageData>(&url),
-        false => {
-            if let Ok(response) = reqwest::blocking::get(url.clone()) {
-                if let Ok(html_content) = response.text() {
-                    let converter = HtmlToMarkdown::builder()
-                        .skip_tags(vec!["script", "style", "table", "sup"])
-                        .build();
-
-                    let markdown_text = match converter.convert(&html_content) {
-                        Ok(content) => content,
-                        Err(_) => String::from(""),
-                    };
-
-                    let page_data = WikiPageData {
-                        title: url.clone(),
-                        markdown_content: markdown_text.clone(),
-                    };
-                    caching_session.write_to_cache(&url.clone(), &page_data)?;
-                    Some(page_data)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+) -> Result<PageSummary, Box<dyn Error>> {
+    let mut url = reqwest::Url::parse(&format!(
+        "{}/api/rest_v1/page/summary",
+        wikipedia_host(language)
+    ))?;
+    // Titles like "AC/DC" or "What?" have to stay one path segment
+    url.path_segments_mut()
+        .map_err(|_| "the wiki's URL can't have a path")?
+        .push(page_title);
+    let url = url.to_string();
+    // The cache is only locked around lookups, so other loads aren't held up by the request
+    let cached = shared_caching_session
+        .lock()
+        .unwrap()
+        .get_from_cache::<PageSummary>(&url);
+    if let Some(summary) = cached {
+        return Ok(summary);
+    }
+
+    let summary = Client::new()
+        .get(&url)
+        .send()?
+        .error_for_status()?
+        .json::<PageSummary>()?;
+    shared_caching_session
+        .lock()
+        .unwrap()
+        .write_to_cache(&url, &summary)?;
+    Ok(summary)
+}
+
+/// Fetches the summary of `page_title` in the background, dropping it if `current_generation`
+/// has moved on from `generation` by then because another result was selected
+pub fn load_summary_to_app(
+    page_title: String,
+    language: String,
+    cache: Shared<CachingSession>,
+    generation: u64,
+    current_generation: Arc<AtomicU64>,
+    summary: Shared<SummaryState>,
+) {
+    thread::spawn(move || {
+        let is_current = || current_generation.load(Ordering::SeqCst) == generation;
+        if !is_current() {
+            return;
         }
-    };
+        let loaded = match get_page_summary(&page_title, &language, cache) {
+            Ok(page_summary) => SummaryState::Loaded(page_summary),
+            Err(_) => SummaryState::Failed,
+        };
+        let mut summary = summary.lock().unwrap();
+        if is_current() {
+            *summary = loaded;
+        }
+    });
+}
+
+/// Titles starting with `query`, from the OpenSearch API
+pub fn get_title_suggestions(
+    query: &str,
+    language: &str,
+    limit: u16,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let url = format!("{}/w/api.php", wikipedia_host(language));
+    let limit = limit.to_string();
+    // The response is [query, titles, descriptions, urls]
+    let (_, titles, _, _): (String, Vec<String>, Vec<String>, Vec<String>) = Client::new()
+        .get(url)
+        .query(&[
+            ("action", "opensearch"),
+            ("search", query),
+            ("limit", limit.as_str()),
+            ("namespace", "0"),
+            ("format", "json"),
+        ])
+        .send()?
+        .json()?;
+    Ok(titles)
+}
 
-    match page_data_response {
-        Some(page_data) => {
-            let mut spans = parsing::parse_markdown(&page_data.markdown_content);
-            spans = remove_unnecessary_spans(spans);
-            Ok(spans)
+/// Fetches suggestions for `query` in the background. The answer is thrown away if
+/// `current_generation` has moved on from `generation` by the time it arrives, as the input
+/// has changed since.
+pub fn load_suggestions_to_app(
+    query: String,
+    language: String,
+    generation: u64,
+    current_generation: Arc<AtomicU64>,
+    suggestions: Shared<Vec<String>>,
+) {
+    thread::spawn(move || {
+        let is_current = || current_generation.load(Ordering::SeqCst) == generation;
+        if !is_current() {
+            return;
         }
-        None => Err("Could not get page data".into()),
-    }
+        if let Ok(titles) = get_title_suggestions(&query, &language, MAX_SUGGESTIONS) {
+            let mut suggestions = suggestions.lock().unwrap();
+            if is_current() {
+                *suggestions = titles;
+            }
+        }
+    });
 }
 
+/// Loads the results starting at `offset`. The first page replaces the results, later ones are
+/// added to them, unless the query or options have changed in the meantime.
 pub fn load_search_query_to_app(
     input: String,
-    loading_flag: Shared<bool>,
-    search_results: Shared<Vec<SearchResult>>,
+    language: String,
     cache: Shared<CachingSession>,
+    result_limit: u16,
+    offset: u32,
+    options: SearchOptions,
+    output: SearchOutput,
 ) {
-    *loading_flag.lock().unwrap() = true;
+    let SearchOutput {
+        loading_flag,
+        results: search_results,
+        paging,
+        load_info,
+    } = output;
+    if offset == 0 {
+        *loading_flag.lock().unwrap() = true;
+        *paging.lock().unwrap() = SearchPaging {
+            query: input.clone(),
+            options,
+            ..SearchPaging::default()
+        };
+    } else {
+        paging.lock().unwrap().is_loading_more = true;
+    }
     thread::spawn(move || {
-        if let Ok(mut results) = get_wikipedia_query(input.as_str(), cache) {
-            for search_result in results.iter_mut() {
-                search_result.snippet = format!("...{}...", search_result.snippet);
+        let started = Instant::now();
+        let page = get_wikipedia_query(
+            input.as_str(),
+            language.as_str(),
+            cache,
+            result_limit,
+            offset,
+            options,
+        );
+        let mut paging = paging.lock().unwrap();
+        paging.is_loading_more = false;
+        match page {
+            Ok((mut page, source)) if paging.query == input && paging.options == options => {
+                for search_result in page.results.iter_mut() {
+                    search_result.parsed_snippet =
+                        parsing::parse_snippet(&format!("...{}...", search_result.snippet));
+                }
+                // Only the new page is sorted, so the results already shown stay where they are
+                options.sort.sort_page(&mut page.results);
+                let mut results = search_results.lock().unwrap();
+                if offset == 0 {
+                    results.clear();
+                }
+                results.extend(page.results);
+                paging.total_hits = page.total_hits;
+                paging.suggestion = page.suggestion;
+                paging.next_offset = page.next_offset;
+                *load_info.lock().unwrap() = Some(LoadInfo {
+                    source,
+                    duration: started.elapsed(),
+                });
+                *loading_flag.lock().unwrap() = false;
             }
-            *search_results.lock().unwrap() = results;
-            *loading_flag.lock().unwrap() = false;
+            // Stop asking for more after a page fails, rather than retrying it every frame
+            Err(_) if offset > 0 => paging.next_offset = None,
+            _ => {}
         }
     });
 }
 
 pub fn load_article_to_app(
     title: String,
+    language: String,
     loading_flag: Shared<bool>,
-    markdown_spans: Shared<Vec<FormattedSpan>>,
+    article_spans: Shared<Vec<FormattedSpan>>,
     cache: Shared<CachingSession>,
+    image_store: ImageStore,
+    load_info: Shared<Option<LoadInfo>>,
 ) {
     *loading_flag.lock().unwrap() = true;
     thread::spawn(move || {
-        if let Ok(results) = get_wikipedia_page(title.as_str(), cache) {
-            *markdown_spans.lock().unwrap() = results;
+        let started = Instant::now();
+        if let Ok((results, source)) = get_wikipedia_page(title.as_str(), language.as_str(), cache)
+        {
+            images::load_images_to_app(&results, image_store);
+            *article_spans.lock().unwrap() = results;
+            *load_info.lock().unwrap() = Some(LoadInfo {
+                source,
+                duration: started.elapsed(),
+            });
             *loading_flag.lock().unwrap() = false;
         }
     });
 }
 
-pub fn remove_unnecessary_spans(mut spans: Vec<FormattedSpan>) -> Vec<FormattedSpan> {
+/// Drops every section that `section_filter` hides for `language` (headings compare case-insensitively).
+/// A section runs until the next heading of the same or a higher level, so its subsections go too.
+pub fn remove_unnecessary_spans(
+    mut spans: Vec<FormattedSpan>,
+    section_filter: &SectionFilter,
+    language: &str,
+) -> Vec<FormattedSpan> {
     let mut remove_by_index: Vec<bool> = Vec::new();
-    let mut found_see_also_header = false;
-    let mut removing_flag = false;
-    let flagged_titles = ["Notes", "References"];
-    for (i, span) in spans.iter().enumerate() {
-        if span.is_heading && found_see_also_header {
-            removing_flag = true;
+    let mut hidden_heading_level: Option<usize> = None;
+    for span in spans.iter() {
+        if span.is_heading {
+            if let Some(level) = hidden_heading_level {
+                if span.heading_level <= level {
+                    hidden_heading_level = None;
+                }
+            }
+            if hidden_heading_level.is_none() && section_filter.is_hidden(language, &span.text) {
+                hidden_heading_level = Some(span.heading_level);
+            }
         }
-        if span.is_heading && flagged_titles.contains(&span.text.as_str()) {
-            removing_flag = true;
+
+        remove_by_index.push(hidden_heading_level.is_some());
+    }
+
+    let mut remove_by_index = remove_by_index.into_iter();
+    spans.retain(|_| !remove_by_index.next().unwrap_or(false));
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> FormattedSpan {
+        FormattedSpan {
+            text: String::from(text),
+            ..FormattedSpan::default()
         }
-        if span.is_heading && span.text == "See Also" {
-            found_see_also_header = true;
+    }
+
+    fn heading(text: &str, level: usize) -> FormattedSpan {
+        FormattedSpan {
+            text: String::from(text),
+            is_heading: true,
+            heading_level: level,
+            ..FormattedSpan::default()
         }
+    }
 
-        remove_by_index.push(removing_flag);
+    fn article() -> Vec<FormattedSpan> {
+        vec![
+            text("Intro"),
+            heading("History", 2),
+            text("Founded long ago"),
+            heading("External links", 2),
+            text("Official site"),
+            heading("Archives", 3),
+            text("Old site"),
+            heading("Gallery", 2),
+            text("Pictures"),
+        ]
     }
 
-    spans.retain(|span| remove_by_index[span.index] == false);
+    fn texts(spans: &[FormattedSpan]) -> Vec<&str> {
+        spans.iter().map(|span| span.text.as_str()).collect()
+    }
 
-    spans
+    #[test]
+    fn compiles_advanced_search_fields() {
+        use SearchOperator::*;
+        let cases: [(&[(SearchOperator, &str)], &str); 9] = [
+            (&[(Words, "  "), (InTitle, "")], ""),
+            (&[(Words, "rust borrow checker")], "rust borrow checker"),
+            (&[(Words, "\"exact phrase\"")], "\"exact phrase\""),
+            (&[(InTitle, "Kyoto")], "intitle:Kyoto"),
+            (
+                &[(InCategory, "Living people")],
+                "incategory:\"Living people\"",
+            ),
+            // Quotes inside a value would end the quoted one early
+            (
+                &[(LinksTo, "The \"Big\" Apple")],
+                "linksto:\"The Big Apple\"",
+            ),
+            (
+                &[(InSource, "/[Kk]yoto\\s+city/")],
+                "insource:/[Kk]yoto\\s+city/",
+            ),
+            (
+                &[(HasTemplate, "Infobox Stadt"), (InSource, "Präfektur")],
+                "hastemplate:\"Infobox Stadt\" insource:Präfektur",
+            ),
+            (
+                &[
+                    (Words, "京都 寺"),
+                    (InTitle, "Kyōto"),
+                    (InCategory, "Städte in Japan"),
+                ],
+                "京都 寺 intitle:Kyōto incategory:\"Städte in Japan\"",
+            ),
+        ];
+        for (values, expected) in cases {
+            assert_eq!(compile_search_query(values), expected, "for {values:?}");
+        }
+    }
+
+    #[test]
+    fn puts_the_query_and_namespace_in_the_search_url() {
+        let options = SearchOptions {
+            sort: SearchSort::LastEdited,
+            namespace: NAMESPACES
+                .iter()
+                .position(|(label, _)| *label == "Category")
+                .unwrap(),
+        };
+        let query = "intitle:\"Kyōto\" insource:/a&b=c/";
+        let url = search_url(query, "de", 20, 40, options).unwrap();
+        assert_eq!(url.host_str(), Some("de.wikipedia.org"));
+
+        let pairs: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs["srsearch"], query);
+        assert_eq!(pairs["srnamespace"], "14");
+        assert_eq!(pairs["srsort"], "last_edit_desc");
+        assert_eq!(pairs["srlimit"], "20");
+        assert_eq!(pairs["sroffset"], "40");
+    }
+
+    #[test]
+    fn sorts_a_page_by_what_the_api_cant() {
+        let mut results: Vec<SearchResult> = serde_json::from_str(
+            r#"[
+                {"title": "kyoto", "pageid": 1, "snippet": "", "size": 200},
+                {"title": "Aomori", "pageid": 2, "snippet": "", "size": 900},
+                {"title": "Nara", "pageid": 3, "snippet": "", "size": 500},
+                {"title": "Ise", "pageid": 4, "snippet": ""}
+            ]"#,
+        )
+        .unwrap();
+        let titles = |results: &[SearchResult]| {
+            results
+                .iter()
+                .map(|result| result.title.clone())
+                .collect::<Vec<String>>()
+        };
+
+        // Pages without a size go last
+        SearchSort::Size.sort_page(&mut results);
+        assert_eq!(titles(&results), ["Aomori", "Nara", "kyoto", "Ise"]);
+        SearchSort::Alphabetical.sort_page(&mut results);
+        assert_eq!(titles(&results), ["Aomori", "Ise", "kyoto", "Nara"]);
+        // The API already put these in order
+        SearchSort::LastEdited.sort_page(&mut results[1..]);
+        assert_eq!(titles(&results), ["Aomori", "Ise", "kyoto", "Nara"]);
+
+        assert_eq!(SearchSort::Size.scope(), "this page");
+        assert_eq!(SearchSort::Created.scope(), "results");
+    }
+
+    #[test]
+    fn reads_where_the_next_page_starts() {
+        let response: WikiSearchResponse = serde_json::from_str(
+            r#"{
+                "continue": {"sroffset": 40, "continue": "-||"},
+                "query": {
+                    "searchinfo": {"totalhits": 41},
+                    "search": [{"title": "Kyoto", "pageid": 1, "snippet": ""}]
+                }
+            }"#,
+        )
+        .unwrap();
+        let page = SearchPage::from(response);
+        assert_eq!(page.next_offset, Some(40));
+        assert_eq!(page.total_hits, Some(41));
+        assert_eq!(page.results.len(), 1);
+
+        let last_page: WikiSearchResponse =
+            serde_json::from_str(r#"{"query": {"search": []}}"#).unwrap();
+        assert_eq!(SearchPage::from(last_page).next_offset, None);
+    }
+
+    #[test]
+    fn removes_configured_sections_with_their_subsections() {
+        let spans = remove_unnecessary_spans(article(), &SectionFilter::default(), "en");
+        assert_eq!(
+            texts(&spans),
+            [
+                "Intro",
+                "History",
+                "Founded long ago",
+                "Gallery",
+                "Pictures"
+            ]
+        );
+
+        // Headings are matched without regard to case or surrounding space
+        let filter = SectionFilter {
+            hidden_sections: HashMap::from([(String::from("en"), vec![String::from(" history ")])]),
+        };
+        let spans = remove_unnecessary_spans(article(), &filter, "en");
+        assert_eq!(texts(&spans)[..2], ["Intro", "External links"]);
+    }
+
+    #[test]
+    fn only_uses_the_list_for_the_article_s_language() {
+        let spans = remove_unnecessary_spans(article(), &SectionFilter::default(), "ja");
+        assert_eq!(texts(&spans), texts(&article()));
+
+        // The German list names German headings, so the English ones stay
+        let spans = remove_unnecessary_spans(article(), &SectionFilter::default(), "de");
+        assert_eq!(texts(&spans), texts(&article()));
+    }
 }